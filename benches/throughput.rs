@@ -0,0 +1,83 @@
+// Measures how many instructions per second the processor runs headlessly
+// with each backend, on the ROMs in tests/roms/. Run it with:
+//
+//     cargo bench --bench throughput
+//
+// Each ROM runs for CYCLES cycles from a fresh processor with no keys held,
+// a frame at a time, which is what our batch simulations do.
+extern crate chip8;
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use chip8::processor::{Backend, Processor};
+use chip8::CYCLES_PER_FRAME;
+
+const CYCLES: u32 = 5_000_000;
+
+// The test ROMs spend most of their time spinning on a jump once they've
+// drawn their screen. This is closer to a game: a loop of arithmetic that
+// updates and draws a score every 256 iterations.
+const COMPUTE_LOOP: [u8; 32] = [
+    0xA3, 0x00, // LD I, 0x300
+    0x60, 0x00, // LD V0, 0
+    0x70, 0x01, // loop: ADD V0, 1
+    0x81, 0x00, // LD V1, V0
+    0x81, 0x14, // ADD V1, V1
+    0x81, 0x23, // XOR V1, V2
+    0x82, 0x16, // SHR V2, V1
+    0x83, 0x04, // ADD V3, V0
+    0x84, 0x35, // SUB V4, V3
+    0x30, 0x00, // SE V0, 0
+    0x12, 0x04, // JP loop
+    0xF3, 0x33, // LD B, V3
+    0xF2, 0x65, // LD V2, [I]
+    0xF0, 0x29, // LD F, V0
+    0xD5, 0x65, // DRW V5, V6, 5
+    0x12, 0x04, // JP loop
+];
+
+fn instructions_per_second(rom: &[u8], backend: Backend) -> f64 {
+    let mut processor = Processor::new();
+    processor.set_backend(backend);
+    processor.load(rom);
+    let start = Instant::now();
+    for _ in 0..CYCLES / CYCLES_PER_FRAME {
+        processor.run(CYCLES_PER_FRAME, [false; 16]);
+    }
+    let elapsed = start.elapsed();
+    CYCLES as f64 / (elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9)
+}
+
+fn main() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms");
+    let mut roms: Vec<_> = fs::read_dir(&dir)
+        .expect("couldn't read tests/roms")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ch8"))
+        .collect();
+    roms.sort();
+    let mut roms: Vec<(String, Vec<u8>)> = roms
+        .iter()
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read(path).expect("couldn't read ROM"))
+        })
+        .collect();
+    roms.push(("compute loop".to_string(), COMPUTE_LOOP.to_vec()));
+
+    println!(
+        "{:<20} {:>16} {:>16} {:>16}",
+        "instructions/s", "interpreter", "cached", "blocks"
+    );
+    for (name, rom) in roms {
+        let interpreter = instructions_per_second(&rom, Backend::Interpreter);
+        let mut row = format!("{:<20} {:>16.0}", name, interpreter);
+        for &backend in &[Backend::DecodeCache, Backend::Blocks] {
+            let speed = instructions_per_second(&rom, backend);
+            row += &format!(" {:>9.0} {:>5.2}x", speed, speed / interpreter);
+        }
+        println!("{}", row);
+    }
+}
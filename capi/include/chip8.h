@@ -0,0 +1,134 @@
+/* The C API of the CHIP-8 emulator core. Generated from capi/src/lib.rs by cbindgen; don't edit. */
+
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct Chip8 Chip8;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Creates a machine. `machine` and `quirks` take the same names as the
+// emulator's `--machine` and `--quirks` options, or NULL for the defaults.
+// Returns NULL if either name is unknown.
+//
+// # Safety
+//
+// The names must be NULL or point to NUL-terminated strings.
+Chip8 *chip8_create(const char *machine, const char *quirks);
+
+// Frees a machine. Passing NULL does nothing.
+//
+// # Safety
+//
+// `chip8` must be NULL or come from chip8_create, and can't be used again.
+void chip8_destroy(Chip8 *chip8);
+
+// Copies a ROM to the machine's load address.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create and `rom` must point to `len` bytes.
+void chip8_load_rom(Chip8 *chip8, const uint8_t *rom, uintptr_t len);
+
+// Makes the random numbers CXKK produces repeatable.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create.
+void chip8_seed(Chip8 *chip8, uint64_t seed);
+
+// Runs the given number of cycles with the keys as they are. Returns
+// whether the display changed.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create.
+bool chip8_run(Chip8 *chip8, uint32_t cycles);
+
+// Runs the given number of 60Hz frames, 8 cycles each. Unless the timers
+// are manual, they count down once per cycle. Returns whether the display
+// changed.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create.
+bool chip8_run_frames(Chip8 *chip8, uint32_t frames);
+
+// Counts the delay and sound timers down by one without running an
+// instruction. Running already does this every cycle, except while the
+// machine waits for a key or once the timers are manual.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create.
+void chip8_tick_timers(Chip8 *chip8);
+
+// Makes the timers manual: running no longer counts them down, and the
+// host calls chip8_tick_timers at 60Hz instead. Off by default.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create.
+void chip8_set_manual_timers(Chip8 *chip8, bool manual);
+
+// Presses or releases one of the 16 keys. Other numbers are ignored.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create.
+void chip8_set_key(Chip8 *chip8, uint8_t key, bool pressed);
+
+// The display, chip8_width() bytes per row and chip8_height() rows, one
+// byte per pixel and 1 for lit. It's only valid until the machine next
+// runs or is destroyed.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create.
+const uint8_t *chip8_framebuffer(const Chip8 *chip8);
+
+uint32_t chip8_width(void);
+
+// # Safety
+//
+// `chip8` must come from chip8_create.
+uint32_t chip8_height(const Chip8 *chip8);
+
+// Whether the buzzer should be sounding.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create.
+bool chip8_sound_active(const Chip8 *chip8);
+
+// Saves the machine's state into `buffer`, if it's big enough. Returns the
+// size of the state either way, so passing a NULL buffer and 0 finds out
+// how big a buffer to allocate.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create and `buffer` must point to `len`
+// writable bytes.
+uintptr_t chip8_save_state(const Chip8 *chip8, uint8_t *buffer, uintptr_t len);
+
+// Restores a state saved by chip8_save_state. Returns false, leaving the
+// machine as it was, if the state isn't one or is for another machine.
+//
+// # Safety
+//
+// `chip8` must come from chip8_create and `state` must point to `len`
+// bytes.
+bool chip8_load_state(Chip8 *chip8, const uint8_t *state, uintptr_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,230 @@
+// A C API for embedding the emulator core in other programs. This crate
+// builds it as a shared library, and include/chip8.h declares these
+// functions. After changing them, regenerate the header from this
+// directory with:
+//
+//     cbindgen --config cbindgen.toml --output include/chip8.h
+//
+// Hosts get an opaque Chip8 handle from chip8_create and pass it to
+// everything else. Nothing here keeps time: the host calls chip8_run (or
+// chip8_run_frames) as often as it likes. The delay and sound timers count
+// down once per cycle as the machine runs, CYCLES_PER_FRAME times a frame,
+// unless the host turns on chip8_set_manual_timers and calls
+// chip8_tick_timers at 60Hz itself.
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use chip8_core::machine::{MachineConfig, Quirks};
+use chip8_core::processor::Processor;
+use chip8_core::{CHIP8_WIDTH, CYCLES_PER_FRAME};
+
+// A machine and the keys the host is holding down.
+pub struct Chip8 {
+    processor: Processor,
+    keys: [bool; 16],
+}
+
+// Reads a name passed from C. A null pointer means the default.
+unsafe fn name<'a>(name: *const c_char) -> Option<Result<&'a str, ()>> {
+    if name.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(name).to_str().map_err(|_| ()))
+    }
+}
+
+/// Creates a machine. `machine` and `quirks` take the same names as the
+/// emulator's `--machine` and `--quirks` options, or NULL for the defaults.
+/// Returns NULL if either name is unknown.
+///
+/// # Safety
+///
+/// The names must be NULL or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_create(machine: *const c_char, quirks: *const c_char) -> *mut Chip8 {
+    let mut config = match name(machine) {
+        None => MachineConfig::chip8(),
+        Some(Ok(machine)) => match MachineConfig::from_name(machine) {
+            Some(config) => config,
+            None => return ptr::null_mut(),
+        },
+        Some(Err(())) => return ptr::null_mut(),
+    };
+    match name(quirks) {
+        None => {}
+        Some(Ok(quirks)) => match Quirks::from_name(quirks) {
+            Some(quirks) => config.quirks = quirks,
+            None => return ptr::null_mut(),
+        },
+        Some(Err(())) => return ptr::null_mut(),
+    }
+    Box::into_raw(Box::new(Chip8 {
+        processor: Processor::with_config(config),
+        keys: [false; 16],
+    }))
+}
+
+/// Frees a machine. Passing NULL does nothing.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from chip8_create, and can't be used again.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_destroy(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Copies a ROM to the machine's load address.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create and `rom` must point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, rom: *const u8, len: usize) {
+    let chip8 = &mut *chip8;
+    if len > 0 {
+        chip8.processor.load(slice::from_raw_parts(rom, len));
+    }
+}
+
+/// Makes the random numbers CXKK produces repeatable.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_seed(chip8: *mut Chip8, seed: u64) {
+    (*chip8).processor.seed_rng(seed);
+}
+
+/// Runs the given number of cycles with the keys as they are. Returns
+/// whether the display changed.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run(chip8: *mut Chip8, cycles: u32) -> bool {
+    let chip8 = &mut *chip8;
+    chip8.processor.run(cycles, chip8.keys).vram_changed
+}
+
+/// Runs the given number of 60Hz frames, 8 cycles each. Unless the timers
+/// are manual, they count down once per cycle. Returns whether the display
+/// changed.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frames(chip8: *mut Chip8, frames: u32) -> bool {
+    let chip8 = &mut *chip8;
+    chip8.processor.run(frames.saturating_mul(CYCLES_PER_FRAME), chip8.keys).vram_changed
+}
+
+/// Counts the delay and sound timers down by one without running an
+/// instruction. Running already does this every cycle, except while the
+/// machine waits for a key or once the timers are manual.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick_timers(chip8: *mut Chip8) {
+    (*chip8).processor.tick_timers();
+}
+
+/// Makes the timers manual: running no longer counts them down, and the
+/// host calls chip8_tick_timers at 60Hz instead. Off by default.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_manual_timers(chip8: *mut Chip8, manual: bool) {
+    (*chip8).processor.set_manual_timers(manual);
+}
+
+/// Presses or releases one of the 16 keys. Other numbers are ignored.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, pressed: bool) {
+    let chip8 = &mut *chip8;
+    if let Some(held) = chip8.keys.get_mut(key as usize) {
+        *held = pressed;
+    }
+}
+
+/// The display, chip8_width() bytes per row and chip8_height() rows, one
+/// byte per pixel and 1 for lit. It's only valid until the machine next
+/// runs or is destroyed.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const Chip8) -> *const u8 {
+    (*chip8).processor.vram().as_ptr() as *const u8
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_width() -> u32 {
+    CHIP8_WIDTH as u32
+}
+
+/// # Safety
+///
+/// `chip8` must come from chip8_create.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_height(chip8: *const Chip8) -> u32 {
+    (*chip8).processor.vram().len() as u32
+}
+
+/// Whether the buzzer should be sounding.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound_active(chip8: *const Chip8) -> bool {
+    (*chip8).processor.registers().sound_timer > 0
+}
+
+/// Saves the machine's state into `buffer`, if it's big enough. Returns the
+/// size of the state either way, so passing a NULL buffer and 0 finds out
+/// how big a buffer to allocate.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create and `buffer` must point to `len`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(chip8: *const Chip8, buffer: *mut u8, len: usize) -> usize {
+    let state = (*chip8).processor.save_state();
+    if !buffer.is_null() && len >= state.len() {
+        ptr::copy_nonoverlapping(state.as_ptr(), buffer, state.len());
+    }
+    state.len()
+}
+
+/// Restores a state saved by chip8_save_state. Returns false, leaving the
+/// machine as it was, if the state isn't one or is for another machine.
+///
+/// # Safety
+///
+/// `chip8` must come from chip8_create and `state` must point to `len`
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(chip8: *mut Chip8, state: *const u8, len: usize) -> bool {
+    if state.is_null() {
+        return false;
+    }
+    (*chip8).processor.load_state(slice::from_raw_parts(state, len)).is_ok()
+}
@@ -0,0 +1,63 @@
+// Builds tests/test.c against the shared library and include/chip8.h with the
+// system C compiler (cc, or $CC), and runs it.
+#![cfg(unix)]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+// Builds the shared library. Tests don't link against a cdylib, so cargo
+// doesn't build it for them; this builds it into the test's own target
+// directory, where it can't clash with the build running the test.
+fn build_library() -> PathBuf {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("capi");
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(&cargo)
+        .arg("build")
+        .arg("--manifest-path")
+        .arg(root.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .output()
+        .unwrap_or_else(|e| panic!("couldn't run {}: {}", cargo, e));
+    assert!(
+        output.status.success(),
+        "the C API library didn't build:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    target_dir.join("debug")
+}
+
+#[test]
+fn test_c_api() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let lib_dir = build_library();
+    let program = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("capi_test");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let status = Command::new(&compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(root.join("tests/test.c"))
+        .arg("-o")
+        .arg(&program)
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lchip8")
+        .status()
+        .unwrap_or_else(|e| panic!("couldn't run {}: {}", compiler, e));
+    assert!(status.success(), "test.c didn't build");
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "test.c failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
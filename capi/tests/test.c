@@ -0,0 +1,107 @@
+/* Drives the core through the C API. Built and run by tests/capi.rs. */
+#include <stdio.h>
+#include <string.h>
+
+#include "chip8.h"
+
+static int failures = 0;
+
+#define CHECK(condition)                                            \
+    do {                                                            \
+        if (!(condition)) {                                         \
+            fprintf(stderr, "%s:%d: %s\n", __FILE__, __LINE__, #condition); \
+            failures++;                                             \
+        }                                                           \
+    } while (0)
+
+/* Draws a 5 in the top left corner, waits for a key and then beeps. */
+static const uint8_t ROM[] = {
+    0x60, 0x05, /* LD V0, 5 */
+    0xF0, 0x29, /* LD F, V0 */
+    0x61, 0x00, /* LD V1, 0 */
+    0xD1, 0x15, /* DRW V1, V1, 5 */
+    0xF2, 0x0A, /* LD V2, K */
+    0x63, 0x40, /* LD V3, 0x40 */
+    0xF3, 0x18, /* LD ST, V3 */
+    0x12, 0x0E, /* JP 0x20E */
+};
+
+int main(void) {
+    CHECK(chip8_create("nes", NULL) == NULL);
+    CHECK(chip8_create(NULL, "weird") == NULL);
+    chip8_destroy(NULL);
+
+    Chip8 *chip8 = chip8_create("hires", "vip");
+    CHECK(chip8 != NULL);
+    CHECK(chip8_height(chip8) == 64);
+    chip8_destroy(chip8);
+
+    chip8 = chip8_create(NULL, NULL);
+    CHECK(chip8_width() == 64);
+    CHECK(chip8_height(chip8) == 32);
+    chip8_load_rom(chip8, ROM, sizeof ROM);
+    CHECK(chip8_run_frames(chip8, 2));
+
+    /* The top of a 5 is four lit pixels. */
+    const uint8_t *pixels = chip8_framebuffer(chip8);
+    const uint8_t top[] = {1, 1, 1, 1, 0};
+    CHECK(memcmp(pixels, top, sizeof top) == 0);
+    CHECK(pixels[chip8_width()] == 1);
+    CHECK(pixels[chip8_width() + 3] == 0);
+    CHECK(!chip8_sound_active(chip8));
+
+    /* Still waiting for the key, so no beep. */
+    CHECK(!chip8_run(chip8, 100));
+    CHECK(!chip8_sound_active(chip8));
+    chip8_set_key(chip8, 7, true);
+    chip8_set_key(chip8, 200, true);
+    chip8_run_frames(chip8, 1);
+    chip8_set_key(chip8, 7, false);
+    CHECK(chip8_sound_active(chip8));
+
+    size_t size = chip8_save_state(chip8, NULL, 0);
+    CHECK(size > 4096);
+    uint8_t *state = malloc(size);
+    CHECK(chip8_save_state(chip8, state, size - 1) == size);
+    CHECK(chip8_save_state(chip8, state, size) == size);
+
+    /* The timers count down once per cycle, so 0x40 runs out within 20
+       frames of 8 cycles. */
+    chip8_run_frames(chip8, 20);
+    CHECK(!chip8_sound_active(chip8));
+    CHECK(chip8_load_state(chip8, state, size));
+    CHECK(chip8_sound_active(chip8));
+    for (int i = 0; i < 0x40; i++) {
+        chip8_tick_timers(chip8);
+    }
+    CHECK(!chip8_sound_active(chip8));
+    CHECK(chip8_load_state(chip8, state, size));
+    uint8_t *again = malloc(size);
+    CHECK(chip8_save_state(chip8, again, size) == size);
+    CHECK(memcmp(state, again, size) == 0);
+    free(again);
+
+    /* With manual timers, only ticking them counts them down. */
+    chip8_set_manual_timers(chip8, true);
+    chip8_run_frames(chip8, 20);
+    CHECK(chip8_sound_active(chip8));
+    for (int i = 0; i < 0x40; i++) {
+        chip8_tick_timers(chip8);
+    }
+    CHECK(!chip8_sound_active(chip8));
+    chip8_set_manual_timers(chip8, false);
+
+    CHECK(chip8_load_state(chip8, state, size));
+    CHECK(!chip8_load_state(chip8, state, size - 1));
+    CHECK(!chip8_load_state(chip8, NULL, 0));
+    Chip8 *other = chip8_create("eti660", NULL);
+    CHECK(!chip8_load_state(other, state, size));
+    chip8_destroy(other);
+    free(state);
+
+    chip8_destroy(chip8);
+    if (failures == 0) {
+        printf("ok\n");
+    }
+    return failures == 0 ? 0 : 1;
+}
@@ -0,0 +1,207 @@
+// How a microcontroller port fits together. The `port` module is what would
+// go on the device: it uses nothing but core and embedded-graphics, draws to
+// any monochrome DrawTarget (an SSD1306 OLED, a Sharp memory LCD, ...),
+// scans a 4x4 key matrix and takes its random numbers from the device. The
+// rest stands in for the hardware with a simulated 128x64 display, so it
+// runs on the desktop:
+//
+//     cargo run --example embedded
+//
+// The device build uses the library without std, which needs no heap:
+//
+//     chip8 = { version = "0.1", default-features = false }
+extern crate chip8;
+extern crate embedded_graphics;
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+
+use chip8::machine::MachineConfig;
+use chip8::processor::Processor;
+use chip8::CYCLES_PER_FRAME;
+
+mod port {
+    use chip8::processor::Processor;
+    use chip8::random::Random;
+    use chip8::CHIP8_WIDTH;
+    use embedded_graphics::pixelcolor::BinaryColor;
+    use embedded_graphics::prelude::*;
+    use embedded_graphics::primitives::Rectangle;
+
+    // The keys on a COSMAC VIP's keypad, a row at a time, which is how
+    // 4x4 membrane keypads are usually wired.
+    pub const KEY_MATRIX: [[usize; 4]; 4] = [
+        [0x1, 0x2, 0x3, 0xC],
+        [0x4, 0x5, 0x6, 0xD],
+        [0x7, 0x8, 0x9, 0xE],
+        [0xA, 0x0, 0xB, 0xF],
+    ];
+
+    // Drives each row of the matrix in turn and reads back which of its
+    // columns are connected.
+    pub fn scan_keypad<F: FnMut(usize) -> [bool; 4]>(mut read_row: F) -> [bool; 16] {
+        let mut keypad = [false; 16];
+        for (row, keys) in KEY_MATRIX.iter().enumerate() {
+            let columns = read_row(row);
+            for (&key, &pressed) in keys.iter().zip(columns.iter()) {
+                keypad[key] = pressed;
+            }
+        }
+        keypad
+    }
+
+    // A 16-bit Galois LFSR, standing in for a hardware random number
+    // generator. Real ports would read the device's instead.
+    pub struct Lfsr(pub u16);
+
+    impl Random for Lfsr {
+        fn next_byte(&mut self) -> u8 {
+            for _ in 0..8 {
+                let carry = self.0 & 1;
+                self.0 >>= 1;
+                if carry == 1 {
+                    self.0 ^= 0xB400;
+                }
+            }
+            self.0 as u8
+        }
+    }
+
+    // Draws the screen as large as it fits on the display, in whole pixels,
+    // in the top left corner.
+    pub fn draw<R, D>(processor: &Processor<R>, display: &mut D) -> Result<(), D::Error>
+    where
+        R: Random,
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let rows = processor.vram();
+        let size = display.bounding_box().size;
+        let scale = (size.width / CHIP8_WIDTH as u32).min(size.height / rows.len() as u32).max(1);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                let area = Rectangle::new(
+                    Point::new(x as i32 * scale as i32, y as i32 * scale as i32),
+                    Size::new(scale, scale),
+                );
+                let color = if pixel == 0 { BinaryColor::Off } else { BinaryColor::On };
+                display.fill_solid(&area, color)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+const WIDTH: usize = 128;
+const HEIGHT: usize = 64;
+
+// A 128x64 monochrome display kept in memory.
+struct SimulatedDisplay {
+    pixels: [[bool; WIDTH]; HEIGHT],
+}
+
+impl SimulatedDisplay {
+    fn new() -> Self {
+        SimulatedDisplay {
+            pixels: [[false; WIDTH]; HEIGHT],
+        }
+    }
+
+    // The display as text, # for lit pixels and . for dark ones.
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        for row in self.pixels.iter() {
+            for &pixel in row.iter() {
+                text.push(if pixel { '#' } else { '.' });
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl OriginDimensions for SimulatedDisplay {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for SimulatedDisplay {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            // Like real displays, ignore anything off the edge.
+            if point.x >= 0 && point.y >= 0 && (point.x as usize) < WIDTH && (point.y as usize) < HEIGHT {
+                self.pixels[point.y as usize][point.x as usize] = color.is_on();
+            }
+        }
+        Ok(())
+    }
+}
+
+// Runs a ROM for some 60Hz frames with no keys held, then draws it.
+fn run(rom: &[u8], frames: u32, display: &mut SimulatedDisplay) -> Processor<port::Lfsr> {
+    let mut processor = Processor::with_rng(MachineConfig::chip8(), port::Lfsr(0xACE1));
+    processor.load(rom);
+    for _ in 0..frames {
+        let keypad = port::scan_keypad(|_| [false; 4]);
+        processor.run(CYCLES_PER_FRAME, keypad);
+    }
+    port::draw(&processor, display).unwrap();
+    processor
+}
+
+fn main() {
+    let mut display = SimulatedDisplay::new();
+    run(include_bytes!("../tests/roms/digits.ch8"), 30, &mut display);
+    print!("{}", display.to_text());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::random::Random;
+
+    #[test]
+    fn test_draws_scaled_screen() {
+        let mut display = SimulatedDisplay::new();
+        run(include_bytes!("../tests/roms/digits.ch8"), 30, &mut display);
+
+        // Each CHIP-8 pixel is two pixels square on a 128x64 display.
+        let mut expected = String::new();
+        for line in include_str!("../tests/golden/digits.txt").lines() {
+            let row: String = line.chars().flat_map(|pixel| vec![pixel, pixel]).collect();
+            expected.push_str(&row);
+            expected.push('\n');
+            expected.push_str(&row);
+            expected.push('\n');
+        }
+        assert_eq!(display.to_text(), expected);
+    }
+
+    #[test]
+    fn test_scan_keypad() {
+        // Key 5 is in the second row and column, and B in the fourth row
+        // and third column.
+        let keypad = port::scan_keypad(|row| match row {
+            1 => [false, true, false, false],
+            3 => [false, false, true, false],
+            _ => [false; 4],
+        });
+        let pressed: Vec<usize> = (0..16).filter(|&key| keypad[key]).collect();
+        assert_eq!(pressed, vec![0x5, 0xB]);
+    }
+
+    #[test]
+    fn test_uses_injected_rng() {
+        // C0FF: V0 = random byte.
+        let mut processor = Processor::with_rng(MachineConfig::chip8(), port::Lfsr(0xACE1));
+        processor.load(&[0xC0, 0xFF]);
+        processor.run(1, [false; 16]);
+        assert_eq!(processor.registers().v[0], port::Lfsr(0xACE1).next_byte());
+    }
+}
@@ -0,0 +1,203 @@
+// Python bindings for the emulator core. Build and install them into the
+// current virtualenv with `maturin develop` from this directory.
+
+// The wrappers pyo3 0.22 generates for methods returning `PyResult` convert
+// `PyErr` into itself, and clippy blames the methods for it.
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use chip8::machine::{MachineConfig, Quirks};
+use chip8::processor::{Backend, Processor};
+use chip8::{CHIP8_WIDTH, CYCLES_PER_FRAME};
+
+/// A CHIP-8 machine with no display, sound or timing of its own: it runs
+/// exactly as many cycles as it's asked to, as fast as it can.
+///
+/// `machine`, `quirks` and `backend` take the same names as the emulator's
+/// `--machine`, `--quirks` and `--backend` options. Pass `seed` to make
+/// random numbers repeatable.
+#[pyclass(module = "chip8")]
+#[derive(Clone)]
+struct Chip8 {
+    processor: Processor,
+    keys: [bool; 16],
+}
+
+fn bad_value(what: &str, name: &str) -> PyErr {
+    PyValueError::new_err(format!("unknown {}: {}", what, name))
+}
+
+#[pymethods]
+impl Chip8 {
+    #[new]
+    #[pyo3(signature = (machine = "chip8", quirks = None, backend = "cached", seed = None))]
+    fn new(machine: &str, quirks: Option<&str>, backend: &str, seed: Option<u64>) -> PyResult<Self> {
+        let mut config = MachineConfig::from_name(machine).ok_or_else(|| bad_value("machine", machine))?;
+        if let Some(quirks) = quirks {
+            config.quirks = Quirks::from_name(quirks).ok_or_else(|| bad_value("quirks", quirks))?;
+        }
+        let backend = Backend::from_name(backend).ok_or_else(|| bad_value("backend", backend))?;
+        let mut processor = Processor::with_config(config);
+        processor.set_backend(backend);
+        if let Some(seed) = seed {
+            processor.seed_rng(seed);
+        }
+        Ok(Chip8 {
+            processor,
+            keys: [false; 16],
+        })
+    }
+
+    /// Copies a ROM to the machine's load address.
+    fn load(&mut self, rom: &[u8]) {
+        self.processor.load(rom);
+    }
+
+    /// Reseeds the random numbers `CXKK` produces.
+    fn seed(&mut self, seed: u64) {
+        self.processor.seed_rng(seed);
+    }
+
+    /// Presses or releases one key, 0 to 15.
+    fn set_key(&mut self, key: usize, pressed: bool) -> PyResult<()> {
+        if key >= self.keys.len() {
+            return Err(PyIndexError::new_err(format!("no key {}", key)));
+        }
+        self.keys[key] = pressed;
+        Ok(())
+    }
+
+    /// Sets all 16 keys at once from a sequence of booleans.
+    fn set_keys(&mut self, keys: Vec<bool>) -> PyResult<()> {
+        if keys.len() != self.keys.len() {
+            return Err(PyValueError::new_err(format!("expected 16 keys, got {}", keys.len())));
+        }
+        self.keys.copy_from_slice(&keys);
+        Ok(())
+    }
+
+    #[getter]
+    fn keys(&self) -> Vec<bool> {
+        self.keys.to_vec()
+    }
+
+    /// Runs the given number of cycles with the keys as they are. Returns
+    /// whether the display changed.
+    #[pyo3(signature = (cycles = 1))]
+    fn step(&mut self, cycles: u32) -> bool {
+        self.processor.run(cycles, self.keys).vram_changed
+    }
+
+    /// Runs the given number of 60Hz frames. Returns whether the display
+    /// changed.
+    #[pyo3(signature = (frames = 1))]
+    fn run_frames(&mut self, frames: u32) -> bool {
+        self.processor.run(frames.saturating_mul(CYCLES_PER_FRAME), self.keys).vram_changed
+    }
+
+    /// The whole of RAM.
+    #[getter]
+    fn ram<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, self.processor.ram())
+    }
+
+    /// Writes bytes to RAM starting at an address.
+    fn write_ram(&mut self, addr: usize, data: &[u8]) -> PyResult<()> {
+        let ram = self.processor.ram_mut();
+        let end = match addr.checked_add(data.len()) {
+            Some(end) if end <= ram.len() => end,
+            _ => return Err(PyIndexError::new_err("write runs past the end of RAM")),
+        };
+        ram[addr..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// The display a row at a time, one byte per pixel, 1 for lit. With
+    /// numpy, `numpy.frombuffer(c.vram, numpy.uint8).reshape(c.height,
+    /// c.width)` gives it as an image.
+    #[getter]
+    fn vram<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let rows = self.processor.vram();
+        let mut pixels = Vec::with_capacity(rows.len() * CHIP8_WIDTH);
+        for row in rows {
+            pixels.extend_from_slice(row);
+        }
+        PyBytes::new_bound(py, &pixels)
+    }
+
+    #[getter]
+    fn width(&self) -> usize {
+        CHIP8_WIDTH
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.processor.vram().len()
+    }
+
+    /// V0 to VF.
+    #[getter]
+    fn v<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.processor.registers().v)
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        self.processor.registers().i
+    }
+
+    #[getter]
+    fn pc(&self) -> usize {
+        self.processor.pc()
+    }
+
+    #[getter]
+    fn sp(&self) -> usize {
+        self.processor.sp()
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        self.processor.registers().delay_timer
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        self.processor.registers().sound_timer
+    }
+
+    /// Return addresses of the subroutines being run, outermost first.
+    #[getter]
+    fn stack(&self) -> Vec<usize> {
+        self.processor.stack().to_vec()
+    }
+
+    /// Everything needed to carry on from here later, as bytes.
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.processor.save_state())
+    }
+
+    /// Restores a state from `save_state`. It must be for the same machine.
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.processor.load_state(state).map_err(PyValueError::new_err)
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "chip8")]
+fn chip8_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Chip8>()?;
+    m.add("CYCLES_PER_FRAME", CYCLES_PER_FRAME)?;
+    Ok(())
+}
@@ -0,0 +1,144 @@
+# Run with `pytest` after `maturin develop`.
+import copy
+from pathlib import Path
+
+import pytest
+
+import chip8
+
+ROMS = Path(__file__).resolve().parents[2] / "tests" / "roms"
+GOLDEN = Path(__file__).resolve().parents[2] / "tests" / "golden"
+
+# Draws a random digit at a random place, over and over.
+RANDOM_DIGITS = bytes([
+    0xC0, 0x3F,  # RND V0, 0x3F
+    0xC1, 0x1F,  # RND V1, 0x1F
+    0xC2, 0x0F,  # RND V2, 0x0F
+    0xF2, 0x29,  # LD F, V2
+    0xD0, 0x15,  # DRW V0, V1, 5
+    0x12, 0x00,  # JP 0x200
+])
+
+
+def test_registers():
+    c = chip8.Chip8()
+    c.load(bytes([
+        0x60, 0x12,  # LD V0, 0x12
+        0xA3, 0x45,  # LD I, 0x345
+        0x22, 0x08,  # CALL 0x208
+        0x00, 0x00,
+        0x61, 0x09,  # LD V1, 9
+        0xF1, 0x15,  # LD DT, V1
+    ]))
+    assert c.pc == 0x200
+    c.step(5)
+    assert c.v[:2] == bytes([0x12, 0x09])
+    assert len(c.v) == 16
+    assert c.i == 0x345
+    assert c.pc == 0x20C
+    assert c.sp == 1
+    assert c.stack == [0x206]
+    assert c.delay_timer == 9
+    assert c.sound_timer == 0
+
+
+def test_ram():
+    c = chip8.Chip8()
+    c.load(b"\x60\x07")
+    assert len(c.ram) == 4096
+    assert c.ram[0x200:0x202] == b"\x60\x07"
+    c.write_ram(0x201, b"\x08")
+    c.step()
+    assert c.v[0] == 8
+    with pytest.raises(IndexError):
+        c.write_ram(4095, b"\x00\x00")
+
+
+def test_digits_rom_matches_golden():
+    c = chip8.Chip8()
+    c.load((ROMS / "digits.ch8").read_bytes())
+    c.run_frames(30)
+    golden = (GOLDEN / "digits.txt").read_text().split()
+    rows = [c.vram[row * c.width:(row + 1) * c.width] for row in range(c.height)]
+    assert ["".join("#" if pixel else "." for pixel in row) for row in rows] == golden
+
+
+def test_keys():
+    c = chip8.Chip8()
+    c.load(bytes([
+        0xF0, 0x0A,  # LD V0, K
+        0x12, 0x02,  # JP 0x202
+    ]))
+    c.run_frames(2)
+    assert c.pc == 0x202
+    c.set_key(0xB, True)
+    assert c.keys[0xB]
+    c.run_frames(2)
+    assert c.v[0] == 0xB
+    c.set_keys([False] * 16)
+    assert not any(c.keys)
+    with pytest.raises(IndexError):
+        c.set_key(16, True)
+    with pytest.raises(ValueError):
+        c.set_keys([True])
+
+
+def test_seed_is_repeatable():
+    def run(seed):
+        c = chip8.Chip8(seed=seed)
+        c.load(RANDOM_DIGITS)
+        c.run_frames(20)
+        return c.vram
+
+    assert run(1) == run(1)
+    assert run(1) != run(2)
+
+
+def test_save_and_load_state():
+    c = chip8.Chip8(quirks="vip", backend="blocks")
+    c.load(RANDOM_DIGITS)
+    c.run_frames(10)
+    state = c.save_state()
+    c.run_frames(10)
+    after = (c.vram, c.ram, c.v, c.pc)
+
+    c.load_state(state)
+    c.run_frames(10)
+    assert (c.vram, c.ram, c.v, c.pc) == after
+
+    with pytest.raises(ValueError, match="not a save state"):
+        c.load_state(b"nonsense")
+    with pytest.raises(ValueError, match="eti660"):
+        chip8.Chip8(machine="eti660").load_state(state)
+
+
+def test_copy_is_independent():
+    c = chip8.Chip8(seed=5)
+    c.load(RANDOM_DIGITS)
+    c.run_frames(3)
+    clone = copy.deepcopy(c)
+    c.run_frames(3)
+    clone.run_frames(3)
+    assert clone.vram == c.vram
+    clone.write_ram(0x300, b"\x01")
+    assert c.ram[0x300] == 0
+
+
+def test_bad_names():
+    with pytest.raises(ValueError, match="unknown machine: nes"):
+        chip8.Chip8(machine="nes")
+    with pytest.raises(ValueError, match="unknown quirks"):
+        chip8.Chip8(quirks="weird")
+    with pytest.raises(ValueError, match="unknown backend"):
+        chip8.Chip8(backend="jit")
+
+
+def test_numpy():
+    numpy = pytest.importorskip("numpy")
+    c = chip8.Chip8(machine="hires")
+    assert c.height == 64
+    screen = numpy.frombuffer(c.vram, numpy.uint8).reshape(c.height, c.width)
+    assert screen.shape == (64, 64)
+    assert not screen.any()
+    assert numpy.frombuffer(c.ram, numpy.uint8).shape == (4096,)
+    assert chip8.CYCLES_PER_FRAME == 8
@@ -0,0 +1,79 @@
+use chip8::cheats::CheatList;
+use drivers::Hotkey;
+
+// The in-window list of cheats, drawn over the game's top left corner.
+// Up and Down pick a cheat and Enter turns it on or off. Cheats are added
+// and saved from the prompt in the terminal (--cheats).
+pub struct CheatMenu {
+    pub visible: bool,
+    selected: usize,
+}
+
+impl CheatMenu {
+    pub fn new() -> Self {
+        CheatMenu {
+            visible: false,
+            selected: 0,
+        }
+    }
+
+    // Acts on a hotkey. Returns a message to show the user, if any.
+    pub fn handle(&mut self, hotkey: &Hotkey, list: &mut CheatList) -> Option<String> {
+        if *hotkey == Hotkey::ToggleCheats {
+            self.visible = !self.visible;
+            return None;
+        }
+        if !self.visible || list.cheats.is_empty() {
+            return None;
+        }
+        // The list can shrink from the prompt while the menu is open.
+        self.selected = self.selected.min(list.cheats.len() - 1);
+        match *hotkey {
+            Hotkey::MenuUp => self.selected = self.selected.saturating_sub(1),
+            Hotkey::MenuDown => self.selected = (self.selected + 1).min(list.cheats.len() - 1),
+            Hotkey::MenuSelect => {
+                let cheat = &mut list.cheats[self.selected];
+                cheat.enabled = !cheat.enabled;
+                let state = if cheat.enabled { "on" } else { "off" };
+                return Some(format!("{} {}", label(&cheat.name, cheat.addr), state));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    // The lines to draw, with the selected cheat marked.
+    pub fn lines(&self, list: &CheatList) -> Vec<String> {
+        if !self.visible {
+            return Vec::new();
+        }
+        if list.cheats.is_empty() {
+            return vec!["No cheats".to_string()];
+        }
+        let selected = self.selected.min(list.cheats.len() - 1);
+        list.cheats
+            .iter()
+            .enumerate()
+            .map(|(index, cheat)| {
+                let marker = if index == selected { '>' } else { ' ' };
+                let state = if cheat.enabled { '+' } else { '-' };
+                let line = format!("{} {} {:04X}={} {}", marker, state, cheat.addr, cheat.value, cheat.name);
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+}
+
+// What to call a cheat in messages: its name, or its address if it has
+// none.
+fn label(name: &str, addr: usize) -> String {
+    if name.is_empty() {
+        format!("{:04X}", addr)
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+#[path = "./cheat_menu_test.rs"]
+mod cheat_menu_test;
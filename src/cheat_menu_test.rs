@@ -0,0 +1,42 @@
+use super::*;
+fn build_list() -> CheatList {
+    CheatList::parse("03A0 05 on Lives\n03A2 63 off\n").unwrap()
+}
+#[test]
+fn test_hidden_by_default() {
+    let menu = CheatMenu::new();
+    assert!(menu.lines(&build_list()).is_empty());
+}
+#[test]
+fn test_lines() {
+    let mut menu = CheatMenu::new();
+    let mut list = build_list();
+    menu.handle(&Hotkey::ToggleCheats, &mut list);
+    assert_eq!(menu.lines(&list), vec!["> + 03A0=5 Lives", "  - 03A2=99"]);
+
+    menu.handle(&Hotkey::MenuDown, &mut list);
+    menu.handle(&Hotkey::MenuDown, &mut list);
+    assert_eq!(menu.lines(&list), vec!["  + 03A0=5 Lives", "> - 03A2=99"]);
+    menu.handle(&Hotkey::MenuUp, &mut list);
+    assert_eq!(menu.lines(&list)[0], "> + 03A0=5 Lives");
+
+    assert_eq!(menu.lines(&CheatList::default()), vec!["No cheats"]);
+}
+#[test]
+fn test_toggle_cheat() {
+    let mut menu = CheatMenu::new();
+    let mut list = build_list();
+    menu.handle(&Hotkey::ToggleCheats, &mut list);
+    assert_eq!(menu.handle(&Hotkey::MenuSelect, &mut list), Some("Lives off".to_string()));
+    assert!(!list.cheats[0].enabled);
+    menu.handle(&Hotkey::MenuDown, &mut list);
+    assert_eq!(menu.handle(&Hotkey::MenuSelect, &mut list), Some("03A2 on".to_string()));
+    assert!(list.cheats[1].enabled);
+}
+#[test]
+fn test_keys_ignored_while_hidden() {
+    let mut menu = CheatMenu::new();
+    let mut list = build_list();
+    assert_eq!(menu.handle(&Hotkey::MenuSelect, &mut list), None);
+    assert!(list.cheats[0].enabled);
+}
@@ -0,0 +1,39 @@
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// A prompt in the terminal for cheat commands, for --cheats. Lines are
+// read on a thread of their own, so waiting for one never holds up a
+// frame, and picked up once a frame.
+pub struct CheatPrompt {
+    lines: Receiver<String>,
+}
+
+impl CheatPrompt {
+    pub fn new() -> Self {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let prompt = CheatPrompt { lines };
+        prompt.reply("Type help for a list of cheat commands.");
+        prompt
+    }
+
+    // The lines typed since the last poll.
+    pub fn poll(&self) -> Vec<String> {
+        self.lines.try_iter().collect()
+    }
+
+    // Prints the answer to a command and prompts for the next one.
+    pub fn reply(&self, text: &str) {
+        println!("{}", text);
+        print!("> ");
+        let _ = io::stdout().flush();
+    }
+}
@@ -0,0 +1,344 @@
+use std::fmt;
+
+use line_map::parse_address;
+use processor::Processor;
+
+// How many search results the results command lists before giving up.
+const MAX_RESULTS: usize = 32;
+
+pub const HELP: &str = "search VALUE             keep addresses holding VALUE
+search changed           keep addresses that changed since the last search
+search unchanged         ...that didn't change
+search increased         ...that went up
+search decreased         ...that went down
+search new               forget the results and start again
+results                  list the addresses still in the running
+freeze ADDR VALUE [NAME] hold a byte at a value every frame
+unfreeze ADDR            stop holding it
+toggle ADDR              turn a cheat off or back on
+cheats                   list the cheats
+save [LIST]              save the cheats for this ROM as LIST (default \"default\")
+load [LIST]              replace the cheats with a saved list
+lists                    list the saved lists for this ROM
+help                     show this
+
+Addresses are hex. Values are decimal, or hex with 0x.";
+
+// How a search narrows down its candidates, comparing each byte with the
+// same byte when the search last looked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Filter {
+    fn keeps(&self, old: u8, new: u8) -> bool {
+        match *self {
+            Filter::Equal(value) => new == value,
+            Filter::Changed => new != old,
+            Filter::Unchanged => new == old,
+            Filter::Increased => new > old,
+            Filter::Decreased => new < old,
+        }
+    }
+}
+
+// Finds where a game keeps something, like its lives or score, by
+// narrowing down every address in RAM a step at a time: search for 3 with
+// three lives left, lose one, search for "decreased", and so on.
+pub struct Search {
+    snapshot: Vec<u8>,
+    candidates: Vec<usize>,
+}
+
+impl Search {
+    pub fn new(ram: &[u8]) -> Self {
+        Search {
+            snapshot: ram.to_vec(),
+            candidates: (0..ram.len()).collect(),
+        }
+    }
+
+    // Keeps the candidates the filter accepts, and remembers RAM as it is
+    // now for the next comparison.
+    pub fn filter(&mut self, ram: &[u8], filter: Filter) {
+        let snapshot = &self.snapshot;
+        self.candidates
+            .retain(|&addr| addr < ram.len() && filter.keeps(snapshot[addr], ram[addr]));
+        self.snapshot = ram.to_vec();
+    }
+
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+}
+
+// A byte held at a value. Disabled cheats stay in the list so they can be
+// turned back on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat {
+    pub addr: usize,
+    pub value: u8,
+    pub enabled: bool,
+    pub name: String,
+}
+
+// Cheat lists are saved one cheat per line: the address and value in hex,
+// on or off, then the name.
+//
+//   03A0 05 on Infinite lives
+//
+// Blank lines and lines starting with # are skipped.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CheatList {
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    pub fn parse(text: &str) -> Result<CheatList, String> {
+        let mut cheats = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.splitn(4, char::is_whitespace).filter(|field| !field.is_empty());
+            let error = || format!("line {}: expected ADDR VALUE on|off [NAME]", index + 1);
+            let addr = fields.next().and_then(parse_address).ok_or_else(error)?;
+            let value = fields
+                .next()
+                .and_then(|value| u8::from_str_radix(value, 16).ok())
+                .ok_or_else(error)?;
+            let enabled = match fields.next() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => return Err(error()),
+            };
+            let name = fields.next().unwrap_or("").trim().to_string();
+            cheats.push(Cheat { addr, value, enabled, name });
+        }
+        Ok(CheatList { cheats })
+    }
+
+    // Adds an enabled cheat, replacing any other cheat on the same address.
+    pub fn freeze(&mut self, addr: usize, value: u8, name: &str) {
+        let cheat = Cheat {
+            addr,
+            value,
+            enabled: true,
+            name: name.to_string(),
+        };
+        match self.cheats.iter_mut().find(|cheat| cheat.addr == addr) {
+            Some(existing) => *existing = cheat,
+            None => self.cheats.push(cheat),
+        }
+    }
+
+    pub fn unfreeze(&mut self, addr: usize) -> bool {
+        let count = self.cheats.len();
+        self.cheats.retain(|cheat| cheat.addr != addr);
+        self.cheats.len() != count
+    }
+
+    // Turns the cheat on an address off or back on. Returns whether it's
+    // on now, or None if there's no cheat there.
+    pub fn toggle(&mut self, addr: usize) -> Option<bool> {
+        let cheat = self.cheats.iter_mut().find(|cheat| cheat.addr == addr)?;
+        cheat.enabled = !cheat.enabled;
+        Some(cheat.enabled)
+    }
+
+    // Writes the enabled cheats' values into RAM. Call once a frame.
+    pub fn apply(&self, processor: &mut Processor) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            if cheat.addr < processor.ram().len() {
+                processor.write_ram(cheat.addr, cheat.value);
+            }
+        }
+    }
+}
+
+impl fmt::Display for CheatList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for cheat in &self.cheats {
+            let state = if cheat.enabled { "on" } else { "off" };
+            let line = format!("{:04X} {:02X} {} {}", cheat.addr, cheat.value, state, cheat.name);
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+// A line typed at the cheat prompt.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Search(Filter),
+    NewSearch,
+    Results,
+    Freeze { addr: usize, value: u8, name: String },
+    Unfreeze(usize),
+    Toggle(usize),
+    List,
+    // Saving and loading go through the host's storage, so the caller
+    // handles these.
+    Save(String),
+    Load(String),
+    Lists,
+    Help,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let mut arg = |what: &str| words.next().ok_or_else(|| format!("{} needs {}", command, what));
+        let parsed = match command {
+            "search" => match arg("a value or changed, unchanged, increased, decreased or new")? {
+                "changed" => Command::Search(Filter::Changed),
+                "unchanged" => Command::Search(Filter::Unchanged),
+                "increased" => Command::Search(Filter::Increased),
+                "decreased" => Command::Search(Filter::Decreased),
+                "new" => Command::NewSearch,
+                value => Command::Search(Filter::Equal(parse_value(value)?)),
+            },
+            "results" => Command::Results,
+            "freeze" => {
+                let addr = address(arg("an address")?)?;
+                let value = parse_value(arg("a value")?)?;
+                let name = words.collect::<Vec<_>>().join(" ");
+                Command::Freeze { addr, value, name }
+            }
+            "unfreeze" => Command::Unfreeze(address(arg("an address")?)?),
+            "toggle" => Command::Toggle(address(arg("an address")?)?),
+            "cheats" => Command::List,
+            "save" => Command::Save(list_name(words.next())?),
+            "load" => Command::Load(list_name(words.next())?),
+            "lists" => Command::Lists,
+            "help" => Command::Help,
+            _ => return Err(format!("unknown command: {}; try help", command)),
+        };
+        Ok(parsed)
+    }
+}
+
+fn parse_value(text: &str) -> Result<u8, String> {
+    let value = match text.strip_prefix("0x") {
+        Some(digits) => u8::from_str_radix(digits, 16),
+        None => text.parse(),
+    };
+    value.map_err(|_| format!("not a byte: {}", text))
+}
+
+fn address(text: &str) -> Result<usize, String> {
+    parse_address(text).ok_or_else(|| format!("not an address: {}", text))
+}
+
+// Lists are saved as files, so their names are kept to characters that are
+// safe in a file name everywhere.
+fn list_name(name: Option<&str>) -> Result<String, String> {
+    let name = name.unwrap_or("default");
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(name.to_string())
+    } else {
+        Err(format!("list names can only have letters, digits, - and _: {}", name))
+    }
+}
+
+// The cheats for a running game and the search in progress, if any.
+#[derive(Default)]
+pub struct Cheats {
+    pub list: CheatList,
+    pub search: Option<Search>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Cheats::default()
+    }
+
+    // Runs a command that doesn't need storage, returning what to tell the
+    // user.
+    pub fn run(&mut self, command: Command, processor: &mut Processor) -> Result<String, String> {
+        let reply = match command {
+            Command::Search(filter) => {
+                let ram = processor.ram();
+                let search = match (self.search.as_mut(), filter) {
+                    (Some(search), _) => search,
+                    // A value can be searched for straight away, but there's
+                    // nothing yet to compare with.
+                    (None, Filter::Equal(_)) => self.search.get_or_insert_with(|| Search::new(ram)),
+                    (None, _) => {
+                        self.search = Some(Search::new(ram));
+                        return Ok("Started a new search; search again once the value has changed".to_string());
+                    }
+                };
+                search.filter(ram, filter);
+                match search.candidates().len() {
+                    0 => "No addresses left; search new to start again".to_string(),
+                    1 => format!("Found it: {:04X}", search.candidates()[0]),
+                    count => format!("{} addresses left", count),
+                }
+            }
+            Command::NewSearch => {
+                self.search = Some(Search::new(processor.ram()));
+                "Started a new search".to_string()
+            }
+            Command::Results => {
+                let search = self.search.as_ref().ok_or("no search in progress")?;
+                let candidates = search.candidates();
+                let mut lines: Vec<String> = candidates
+                    .iter()
+                    .take(MAX_RESULTS)
+                    .map(|&addr| format!("{:04X} = {}", addr, processor.ram()[addr]))
+                    .collect();
+                if candidates.len() > MAX_RESULTS {
+                    lines.push(format!("... and {} more", candidates.len() - MAX_RESULTS));
+                }
+                if lines.is_empty() {
+                    "No addresses left".to_string()
+                } else {
+                    lines.join("\n")
+                }
+            }
+            Command::Freeze { addr, value, name } => {
+                if addr >= processor.ram().len() {
+                    return Err(format!("{:04X} is past the end of RAM", addr));
+                }
+                self.list.freeze(addr, value, &name);
+                self.list.apply(processor);
+                format!("Froze {:04X} at {}", addr, value)
+            }
+            Command::Unfreeze(addr) => {
+                if !self.list.unfreeze(addr) {
+                    return Err(format!("no cheat on {:04X}", addr));
+                }
+                format!("Unfroze {:04X}", addr)
+            }
+            Command::Toggle(addr) => match self.list.toggle(addr) {
+                Some(true) => format!("{:04X} on", addr),
+                Some(false) => format!("{:04X} off", addr),
+                None => return Err(format!("no cheat on {:04X}", addr)),
+            },
+            Command::List => {
+                if self.list.cheats.is_empty() {
+                    "No cheats".to_string()
+                } else {
+                    self.list.to_string().trim_end().to_string()
+                }
+            }
+            Command::Help => HELP.to_string(),
+            Command::Save(_) | Command::Load(_) | Command::Lists => {
+                return Err("saving and loading cheats isn't available here".to_string())
+            }
+        };
+        Ok(reply)
+    }
+}
+
+#[cfg(test)]
+#[path = "./cheats_test.rs"]
+mod cheats_test;
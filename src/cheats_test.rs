@@ -0,0 +1,138 @@
+use super::*;
+use processor::Processor;
+
+#[test]
+fn test_search() {
+    let mut ram = vec![0u8; 8];
+    ram[2] = 3;
+    ram[5] = 3;
+    let mut search = Search::new(&ram);
+    search.filter(&ram, Filter::Equal(3));
+    assert_eq!(search.candidates(), &[2, 5]);
+
+    // Losing a life.
+    ram[2] = 2;
+    search.filter(&ram, Filter::Decreased);
+    assert_eq!(search.candidates(), &[2]);
+
+    search.filter(&ram, Filter::Unchanged);
+    assert_eq!(search.candidates(), &[2]);
+    ram[2] = 1;
+    search.filter(&ram, Filter::Increased);
+    assert!(search.candidates().is_empty());
+}
+
+#[test]
+fn test_filters() {
+    let ram = [1, 2, 3];
+    let later = [1, 3, 2];
+    let run = |filter| {
+        let mut search = Search::new(&ram);
+        search.filter(&later, filter);
+        search.candidates().to_vec()
+    };
+    assert_eq!(run(Filter::Changed), vec![1, 2]);
+    assert_eq!(run(Filter::Unchanged), vec![0]);
+    assert_eq!(run(Filter::Increased), vec![1]);
+    assert_eq!(run(Filter::Decreased), vec![2]);
+    assert_eq!(run(Filter::Equal(2)), vec![2]);
+}
+
+#[test]
+fn test_freeze() {
+    let mut processor = Processor::new();
+    processor.load(&[
+        0x60, 0x00, // LD V0, 0
+        0xA3, 0x00, // LD I, 0x300
+        0xF0, 0x55, // LD [I], V0
+        0x12, 0x00, // JP 0x200
+    ]);
+    let mut list = CheatList::default();
+    list.freeze(0x300, 9, "Nine");
+    processor.run(3, [false; 16]);
+    assert_eq!(processor.ram()[0x300], 0);
+    list.apply(&mut processor);
+    assert_eq!(processor.ram()[0x300], 9);
+
+    assert_eq!(list.toggle(0x300), Some(false));
+    processor.run(4, [false; 16]);
+    list.apply(&mut processor);
+    assert_eq!(processor.ram()[0x300], 0);
+
+    // Freezing the same address again replaces the cheat.
+    list.freeze(0x300, 4, "Four");
+    assert_eq!(list.cheats.len(), 1);
+    assert_eq!(list.cheats[0].value, 4);
+    assert!(list.cheats[0].enabled);
+    assert!(list.unfreeze(0x300));
+    assert!(!list.unfreeze(0x300));
+    assert_eq!(list.toggle(0x300), None);
+}
+
+#[test]
+fn test_cheat_list_round_trip() {
+    let text = "# Lives and score\n03A0 05 on Infinite lives\n\n3A2 ff off\n";
+    let list = CheatList::parse(text).unwrap();
+    assert_eq!(
+        list.cheats,
+        vec![
+            Cheat { addr: 0x3A0, value: 5, enabled: true, name: "Infinite lives".to_string() },
+            Cheat { addr: 0x3A2, value: 0xFF, enabled: false, name: String::new() },
+        ]
+    );
+    assert_eq!(list.to_string(), "03A0 05 on Infinite lives\n03A2 FF off\n");
+    assert_eq!(CheatList::parse(&list.to_string()).unwrap(), list);
+
+    assert_eq!(
+        CheatList::parse("03A0 05 maybe"),
+        Err("line 1: expected ADDR VALUE on|off [NAME]".to_string())
+    );
+}
+
+#[test]
+fn test_parse_commands() {
+    assert_eq!(Command::parse("search 3"), Ok(Command::Search(Filter::Equal(3))));
+    assert_eq!(Command::parse("search 0x1f"), Ok(Command::Search(Filter::Equal(0x1F))));
+    assert_eq!(Command::parse("search decreased"), Ok(Command::Search(Filter::Decreased)));
+    assert_eq!(Command::parse("search new"), Ok(Command::NewSearch));
+    assert_eq!(
+        Command::parse("freeze 3a0 5 Infinite lives"),
+        Ok(Command::Freeze { addr: 0x3A0, value: 5, name: "Infinite lives".to_string() })
+    );
+    assert_eq!(Command::parse("unfreeze 0x3a0"), Ok(Command::Unfreeze(0x3A0)));
+    assert_eq!(Command::parse("save"), Ok(Command::Save("default".to_string())));
+    assert_eq!(Command::parse("load boss-fight"), Ok(Command::Load("boss-fight".to_string())));
+
+    assert!(Command::parse("search 300").is_err());
+    assert!(Command::parse("freeze 3a0").is_err());
+    assert!(Command::parse("save ../flags").is_err());
+    assert!(Command::parse("fly").is_err());
+}
+
+#[test]
+fn test_run_commands() {
+    let mut processor = Processor::new();
+    processor.ram_mut()[0x300] = 3;
+    processor.ram_mut()[0x301] = 3;
+    let mut cheats = Cheats::new();
+    let run = |cheats: &mut Cheats, processor: &mut Processor, line| {
+        Command::parse(line).and_then(|command| cheats.run(command, processor))
+    };
+
+    assert!(run(&mut cheats, &mut processor, "results").is_err());
+    assert_eq!(
+        run(&mut cheats, &mut processor, "search changed"),
+        Ok("Started a new search; search again once the value has changed".to_string())
+    );
+    assert_eq!(run(&mut cheats, &mut processor, "search 3"), Ok("2 addresses left".to_string()));
+    processor.ram_mut()[0x300] = 2;
+    assert_eq!(run(&mut cheats, &mut processor, "search decreased"), Ok("Found it: 0300".to_string()));
+    assert_eq!(run(&mut cheats, &mut processor, "results"), Ok("0300 = 2".to_string()));
+
+    assert_eq!(run(&mut cheats, &mut processor, "freeze 300 9 Lives"), Ok("Froze 0300 at 9".to_string()));
+    assert_eq!(processor.ram()[0x300], 9);
+    assert_eq!(run(&mut cheats, &mut processor, "cheats"), Ok("0300 09 on Lives".to_string()));
+    assert_eq!(run(&mut cheats, &mut processor, "toggle 300"), Ok("0300 off".to_string()));
+    assert!(run(&mut cheats, &mut processor, "freeze 1000 1").is_err());
+    assert!(run(&mut cheats, &mut processor, "save").is_err());
+}
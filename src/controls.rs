@@ -0,0 +1,107 @@
+use drivers::{Hotkey, Indicator};
+
+// Runtime emulator controls that sit outside the CHIP-8 itself: pausing,
+// single-stepping frames and running faster or slower than normal.
+pub struct Controls {
+    pub paused: bool,
+    pub turbo: bool,
+    pub slow_motion: bool,
+    pub fast_forward: bool,
+    fast_forward_speed: f32,
+    turbo_speed: f32,
+    slow_motion_speed: f32,
+    frame_advance: bool,
+    reset: bool,
+    // Fractional cycles carried over between frames, so speeds that don't
+    // divide evenly into whole cycles per frame still average out.
+    cycle_remainder: f32,
+}
+
+impl Controls {
+    pub fn new(fast_forward_speed: f32, turbo_speed: f32, slow_motion_speed: f32) -> Self {
+        Controls {
+            paused: false,
+            turbo: false,
+            slow_motion: false,
+            fast_forward: false,
+            fast_forward_speed,
+            turbo_speed,
+            slow_motion_speed,
+            frame_advance: false,
+            reset: false,
+            cycle_remainder: 0.0,
+        }
+    }
+
+    pub fn handle(&mut self, hotkey: &Hotkey) {
+        match *hotkey {
+            Hotkey::Pause => self.paused = !self.paused,
+            Hotkey::Reset => self.reset = true,
+            Hotkey::FrameAdvance if self.paused => self.frame_advance = true,
+            Hotkey::Turbo => {
+                self.turbo = !self.turbo;
+                self.slow_motion = false;
+            }
+            Hotkey::SlowMotion => {
+                self.slow_motion = !self.slow_motion;
+                self.turbo = false;
+            }
+            _ => {}
+        }
+    }
+
+    // Returns true once after a reset was requested.
+    pub fn take_reset(&mut self) -> bool {
+        let reset = self.reset;
+        self.reset = false;
+        reset
+    }
+
+    // Holding fast-forward wins over the turbo and slow-motion toggles.
+    pub fn speed(&self) -> f32 {
+        if self.fast_forward {
+            self.fast_forward_speed
+        } else if self.turbo {
+            self.turbo_speed
+        } else if self.slow_motion {
+            self.slow_motion_speed
+        } else {
+            1.0
+        }
+    }
+
+    // How many processor cycles to run this frame. While paused that's
+    // nothing, unless a single frame advance was requested.
+    pub fn cycles_this_frame(&mut self, cycles_per_frame: u32) -> u32 {
+        let speed = if !self.paused {
+            self.speed()
+        } else if self.frame_advance {
+            self.frame_advance = false;
+            1.0
+        } else {
+            return 0;
+        };
+
+        let cycles = cycles_per_frame as f32 * speed + self.cycle_remainder;
+        self.cycle_remainder = cycles.fract();
+        cycles as u32
+    }
+
+    pub fn indicator(&self) -> Option<Indicator> {
+        if self.paused {
+            Some(Indicator::Paused)
+        } else if self.fast_forward {
+            Some(Indicator::FastForward)
+        } else if self.turbo {
+            Some(Indicator::Turbo)
+        } else if self.slow_motion {
+            Some(Indicator::SlowMotion)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./controls_test.rs"]
+mod controls_test;
@@ -0,0 +1,62 @@
+use super::*;
+fn build_controls() -> Controls {
+    Controls::new(4.0, 2.0, 0.25)
+}
+#[test]
+fn test_normal_speed() {
+    let mut controls = build_controls();
+    assert_eq!(controls.cycles_this_frame(8), 8);
+    assert_eq!(controls.indicator(), None);
+}
+#[test]
+fn test_pause_and_frame_advance() {
+    let mut controls = build_controls();
+    controls.handle(&Hotkey::Pause);
+    assert_eq!(controls.cycles_this_frame(8), 0);
+    assert_eq!(controls.indicator(), Some(Indicator::Paused));
+
+    controls.handle(&Hotkey::FrameAdvance);
+    assert_eq!(controls.cycles_this_frame(8), 8);
+    assert_eq!(controls.cycles_this_frame(8), 0);
+
+    controls.handle(&Hotkey::Pause);
+    assert_eq!(controls.cycles_this_frame(8), 8);
+}
+#[test]
+fn test_frame_advance_ignored_while_running() {
+    let mut controls = build_controls();
+    controls.handle(&Hotkey::FrameAdvance);
+    controls.handle(&Hotkey::Pause);
+    assert_eq!(controls.cycles_this_frame(8), 0);
+}
+#[test]
+fn test_fast_forward_beats_toggles() {
+    let mut controls = build_controls();
+    controls.handle(&Hotkey::SlowMotion);
+    controls.fast_forward = true;
+    assert_eq!(controls.cycles_this_frame(8), 32);
+    assert_eq!(controls.indicator(), Some(Indicator::FastForward));
+}
+#[test]
+fn test_turbo_and_slow_motion_are_exclusive() {
+    let mut controls = build_controls();
+    controls.handle(&Hotkey::Turbo);
+    assert_eq!(controls.cycles_this_frame(8), 16);
+    controls.handle(&Hotkey::SlowMotion);
+    assert!(!controls.turbo);
+    assert_eq!(controls.indicator(), Some(Indicator::SlowMotion));
+}
+#[test]
+fn test_slow_motion_carries_remainder() {
+    let mut controls = build_controls();
+    controls.handle(&Hotkey::SlowMotion);
+    let cycles: Vec<u32> = (0..8).map(|_| controls.cycles_this_frame(1)).collect();
+    assert_eq!(cycles, [0, 0, 0, 1, 0, 0, 0, 1]);
+}
+#[test]
+fn test_reset_is_taken_once() {
+    let mut controls = build_controls();
+    controls.handle(&Hotkey::Reset);
+    assert!(controls.take_reset());
+    assert!(!controls.take_reset());
+}
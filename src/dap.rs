@@ -0,0 +1,607 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+
+use serde_json::Value;
+
+use debugger::{Debugger, StopReason};
+use disassembler::disassemble;
+use line_map::LineMap;
+use machine::{MachineConfig, Quirks};
+use processor::Processor;
+use CYCLES_PER_FRAME;
+
+// CHIP-8 has one thread of execution.
+const THREAD_ID: i64 = 1;
+// Variable references for the scopes every stack frame shares.
+const REGISTERS: i64 = 1;
+const STACK: i64 = 2;
+const RAM: i64 = 3;
+// Bytes per variable in the RAM scope.
+const RAM_ROW: usize = 16;
+
+// When a step or continue should stop, apart from breakpoints.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Until {
+    Stopped,
+    // Until the stack is no deeper than this, for stepping over calls and
+    // out of subroutines.
+    Depth(usize),
+}
+
+// One Debug Adapter Protocol session. Requests go in through handle, and
+// the responses and events they cause come out of take_messages. While
+// the program runs, run_frame advances it a frame at a time.
+pub struct Session {
+    seq: i64,
+    messages: Vec<Value>,
+    events: Vec<Value>,
+    debugger: Option<Debugger>,
+    line_map: LineMap,
+    // Breakpoint addresses by the source path they were set in.
+    source_breakpoints: BTreeMap<String, Vec<usize>>,
+    instruction_breakpoints: Vec<usize>,
+    running: Option<Until>,
+    stop_on_entry: bool,
+    keypad: [bool; 16],
+    finished: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session {
+            seq: 0,
+            messages: Vec::new(),
+            events: Vec::new(),
+            debugger: None,
+            line_map: LineMap::default(),
+            source_breakpoints: BTreeMap::new(),
+            instruction_breakpoints: Vec::new(),
+            running: None,
+            stop_on_entry: false,
+            keypad: [false; 16],
+            finished: false,
+        }
+    }
+
+    pub fn processor(&self) -> Option<&Processor> {
+        self.debugger.as_ref().map(|debugger| &debugger.processor)
+    }
+
+    pub fn running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    // True once the client has disconnected.
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn take_messages(&mut self) -> Vec<Value> {
+        self.messages.split_off(0)
+    }
+
+    // Ends the session from our side, e.g. when the window is closed.
+    pub fn terminate(&mut self) {
+        self.event("terminated", json!({}));
+        self.flush_events();
+        self.finished = true;
+    }
+
+    pub fn handle(&mut self, request: &Value) {
+        let command = request["command"].as_str().unwrap_or("");
+        let args = &request["arguments"];
+        let result = match command {
+            "initialize" => Ok(capabilities()),
+            "launch" => self.launch(args),
+            "setBreakpoints" => Ok(self.set_breakpoints(args)),
+            "setInstructionBreakpoints" => Ok(self.set_instruction_breakpoints(args)),
+            "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+            "configurationDone" => self.configuration_done(),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(scopes()),
+            "variables" => self.variables(args),
+            "continue" => self
+                .resume(Until::Stopped)
+                .map(|()| json!({ "allThreadsContinued": true })),
+            "next" => self.next(),
+            "stepIn" => self.step_in(),
+            "stepOut" => self.step_out(),
+            "pause" => self.pause(),
+            "readMemory" => self.read_memory(args),
+            "disassemble" => self.disassemble(args),
+            "disconnect" | "terminate" => {
+                self.finished = true;
+                Ok(json!({}))
+            }
+            _ => Err(format!("unsupported request: {}", command)),
+        };
+
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response);
+        self.flush_events();
+    }
+
+    // Runs one frame's worth of cycles if the program is running.
+    pub fn run_frame(&mut self, keypad: [bool; 16]) {
+        self.keypad = keypad;
+        let until = match self.running {
+            Some(until) => until,
+            None => return,
+        };
+        let debugger = match self.debugger {
+            Some(ref mut debugger) => debugger,
+            None => return,
+        };
+        for _ in 0..CYCLES_PER_FRAME {
+            let reason = match debugger.run(1, keypad) {
+                Some(StopReason::Breakpoint(_)) => "breakpoint",
+                Some(StopReason::Watchpoint(_)) => "data breakpoint",
+                None => match until {
+                    Until::Depth(depth) if debugger.processor.sp() <= depth => "step",
+                    _ => continue,
+                },
+            };
+            self.stopped(reason);
+            break;
+        }
+        self.flush_events();
+    }
+
+    fn send(&mut self, mut message: Value) {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        self.messages.push(message);
+    }
+
+    // Events raised while handling a request go out after its response.
+    fn event(&mut self, event: &str, body: Value) {
+        self.events.push(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn flush_events(&mut self) {
+        for event in self.events.split_off(0) {
+            self.send(event);
+        }
+    }
+
+    fn stopped(&mut self, reason: &str) {
+        self.running = None;
+        self.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        );
+    }
+
+    fn debugger(&mut self) -> Result<&mut Debugger, String> {
+        self.debugger.as_mut().ok_or_else(|| "no program has been launched".to_string())
+    }
+
+    // Arguments: program (the ROM), and optionally machine, quirks,
+    // lineMap and stopOnEntry. Without a lineMap we look for one next to
+    // the ROM with a .map extension.
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let program = args["program"].as_str().ok_or("launch needs a program")?;
+        let mut config = match args["machine"].as_str() {
+            Some(name) => {
+                MachineConfig::from_name(name).ok_or_else(|| format!("unknown machine: {}", name))?
+            }
+            None => MachineConfig::chip8(),
+        };
+        if let Some(name) = args["quirks"].as_str() {
+            config.quirks = Quirks::from_name(name).ok_or_else(|| format!("unknown quirks: {}", name))?;
+        }
+        let rom = fs::read(program).map_err(|e| format!("{}: {}", program, e))?;
+
+        let default_map = Path::new(program).with_extension("map");
+        self.line_map = match args["lineMap"].as_str() {
+            Some(path) => LineMap::load(Path::new(path))?,
+            None if default_map.exists() => LineMap::load(&default_map)?,
+            None => LineMap::default(),
+        };
+
+        let mut processor = Processor::with_config(config);
+        processor.load(&rom);
+        self.debugger = Some(Debugger::new(processor));
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.event("initialized", json!({}));
+        Ok(json!({}))
+    }
+
+    fn configuration_done(&mut self) -> Result<Value, String> {
+        self.debugger()?;
+        if self.stop_on_entry {
+            self.stopped("entry");
+        } else {
+            self.running = Some(Until::Stopped);
+        }
+        Ok(json!({}))
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Value {
+        let path = args["source"]["path"].as_str().unwrap_or("").to_string();
+        let mut addresses = Vec::new();
+        let mut breakpoints = Vec::new();
+        for requested in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = requested["line"].as_u64().unwrap_or(0) as usize;
+            breakpoints.push(match self.line_map.address(&path, line) {
+                Some((addr, line)) => {
+                    addresses.push(addr);
+                    json!({ "verified": true, "line": line, "instructionReference": hex(addr) })
+                }
+                None => json!({ "verified": false, "line": line, "message": "No code at this line" }),
+            });
+        }
+        self.source_breakpoints.insert(path, addresses);
+        self.apply_breakpoints();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Value {
+        let mut breakpoints = Vec::new();
+        self.instruction_breakpoints.clear();
+        for requested in args["breakpoints"].as_array().into_iter().flatten() {
+            let addr = requested["instructionReference"]
+                .as_str()
+                .and_then(parse_reference)
+                .map(|addr| addr + requested["offset"].as_i64().unwrap_or(0));
+            breakpoints.push(match addr {
+                Some(addr) if addr >= 0 => {
+                    self.instruction_breakpoints.push(addr as usize);
+                    json!({ "verified": true, "instructionReference": hex(addr as usize) })
+                }
+                _ => json!({ "verified": false, "message": "Not an address" }),
+            });
+        }
+        self.apply_breakpoints();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn apply_breakpoints(&mut self) {
+        if let Some(ref mut debugger) = self.debugger {
+            debugger.clear_breakpoints();
+            let from_sources = self.source_breakpoints.values().flatten();
+            for &addr in from_sources.chain(&self.instruction_breakpoints) {
+                debugger.add_breakpoint(addr);
+            }
+        }
+    }
+
+    // The current instruction, then the CALL that entered each subroutine
+    // we're in, innermost first.
+    fn stack_trace(&mut self) -> Result<Value, String> {
+        let processor = &self.debugger()?.processor;
+        let mut addresses = vec![processor.pc()];
+        addresses.extend(processor.stack().iter().rev().map(|&ret| ret - 2));
+        let frames: Vec<Value> = addresses
+            .iter()
+            .enumerate()
+            .map(|(id, &addr)| {
+                let name = match self.line_map.symbol(addr) {
+                    Some(symbol) => format!("{} ({})", symbol, hex(addr)),
+                    None => hex(addr),
+                };
+                let mut frame = json!({
+                    "id": id,
+                    "name": name,
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": hex(addr),
+                });
+                if let Some((file, line)) = self.line_map.location(addr) {
+                    frame["line"] = json!(line);
+                    frame["column"] = json!(1);
+                    frame["source"] = json!({
+                        "name": file,
+                        "path": self.line_map.source_path(file).to_string_lossy(),
+                    });
+                }
+                frame
+            })
+            .collect();
+        Ok(json!({ "stackFrames": frames, "totalFrames": addresses.len() }))
+    }
+
+    fn variables(&mut self, args: &Value) -> Result<Value, String> {
+        let reference = args["variablesReference"].as_i64().unwrap_or(0);
+        let processor = &self.debugger()?.processor;
+        let variable = |name: String, value: String| {
+            json!({ "name": name, "value": value, "variablesReference": 0 })
+        };
+        let variables: Vec<Value> = match reference {
+            REGISTERS => {
+                let registers = processor.registers();
+                let mut variables: Vec<Value> = registers
+                    .v
+                    .iter()
+                    .enumerate()
+                    .map(|(x, v)| variable(format!("V{:X}", x), format!("0x{:02X}", v)))
+                    .collect();
+                let mut i = variable("I".to_string(), hex(registers.i as usize));
+                i["memoryReference"] = json!(hex(registers.i as usize));
+                variables.push(i);
+                variables.push(variable("PC".to_string(), hex(processor.pc())));
+                variables.push(variable("SP".to_string(), processor.sp().to_string()));
+                variables.push(variable("DT".to_string(), registers.delay_timer.to_string()));
+                variables.push(variable("ST".to_string(), registers.sound_timer.to_string()));
+                variables
+            }
+            STACK => processor
+                .stack()
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(depth, &ret)| variable(format!("#{}", depth), hex(ret)))
+                .collect(),
+            RAM => processor
+                .ram()
+                .chunks(RAM_ROW)
+                .enumerate()
+                .map(|(row, bytes)| {
+                    let text: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    let mut variable = variable(hex(row * RAM_ROW), text.join(" "));
+                    variable["memoryReference"] = json!(hex(row * RAM_ROW));
+                    variable
+                })
+                .collect(),
+            _ => return Err(format!("unknown variables reference {}", reference)),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn resume(&mut self, until: Until) -> Result<(), String> {
+        self.debugger()?;
+        self.running = Some(until);
+        Ok(())
+    }
+
+    // Steps over CALLs: they run until the subroutine returns.
+    fn next(&mut self) -> Result<Value, String> {
+        let processor = &self.debugger()?.processor;
+        let pc = processor.pc();
+        if processor.ram()[pc] & 0xF0 == 0x20 {
+            let depth = processor.sp();
+            self.resume(Until::Depth(depth)).map(|()| json!({}))
+        } else {
+            self.step_in()
+        }
+    }
+
+    fn step_in(&mut self) -> Result<Value, String> {
+        let keypad = self.keypad;
+        let reason = match self.debugger()?.step(keypad) {
+            Some(_) => "data breakpoint",
+            None => "step",
+        };
+        self.stopped(reason);
+        Ok(json!({}))
+    }
+
+    fn step_out(&mut self) -> Result<Value, String> {
+        let until = match self.debugger()?.processor.sp() {
+            0 => Until::Stopped,
+            sp => Until::Depth(sp - 1),
+        };
+        self.resume(until).map(|()| json!({}))
+    }
+
+    fn pause(&mut self) -> Result<Value, String> {
+        self.debugger()?;
+        if self.running.is_some() {
+            self.stopped("pause");
+        }
+        Ok(json!({}))
+    }
+
+    fn read_memory(&mut self, args: &Value) -> Result<Value, String> {
+        let ram = self.debugger()?.processor.ram();
+        let start = memory_address(args)?;
+        // Nothing past the end of RAM is readable, so neither is a count
+        // bigger than RAM.
+        let count = (args["count"].as_u64().unwrap_or(0) as usize).min(ram.len());
+        let readable = if start < 0 || start as usize >= ram.len() {
+            &[][..]
+        } else {
+            let start = start as usize;
+            &ram[start..start.saturating_add(count).min(ram.len())]
+        };
+        Ok(json!({
+            "address": format!("0x{:03X}", start.max(0)),
+            "data": base64(readable),
+            "unreadableBytes": count - readable.len(),
+        }))
+    }
+
+    fn disassemble(&mut self, args: &Value) -> Result<Value, String> {
+        let offset = args["instructionOffset"].as_i64().unwrap_or(0).saturating_mul(2);
+        let start = memory_address(args)?.saturating_add(offset);
+        let processor = &self.debugger.as_ref().ok_or("no program has been launched")?.processor;
+        let ram = processor.ram();
+        // There aren't more instructions than bytes of RAM to show.
+        let count = args["instructionCount"].as_i64().unwrap_or(0).min(ram.len() as i64);
+        let instructions: Vec<Value> = (0..count)
+            .map(|index| start.saturating_add(2 * index))
+            .map(|addr| {
+                if addr < 0 || addr as usize >= ram.len() - 1 {
+                    return json!({
+                        "address": format!("0x{:X}", addr.max(0)),
+                        "instruction": "",
+                        "presentationHint": "invalid",
+                    });
+                }
+                let addr = addr as usize;
+                let opcode = (ram[addr] as u16) << 8 | ram[addr + 1] as u16;
+                let mut instruction = json!({
+                    "address": hex(addr),
+                    "instructionBytes": format!("{:02X} {:02X}", ram[addr], ram[addr + 1]),
+                    "instruction": disassemble(opcode),
+                });
+                if let Some(symbol) = self.line_map.symbol(addr) {
+                    instruction["symbol"] = json!(symbol);
+                }
+                if let Some((file, line)) = self.line_map.location(addr) {
+                    instruction["line"] = json!(line);
+                    instruction["location"] = json!({
+                        "name": file,
+                        "path": self.line_map.source_path(file).to_string_lossy(),
+                    });
+                }
+                instruction
+            })
+            .collect();
+        Ok(json!({ "instructions": instructions }))
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsInstructionBreakpoints": true,
+        "supportsReadMemoryRequest": true,
+        "supportsDisassembleRequest": true,
+        "supportsTerminateRequest": true,
+    })
+}
+
+fn scopes() -> Value {
+    json!({
+        "scopes": [
+            { "name": "Registers", "variablesReference": REGISTERS, "expensive": false },
+            { "name": "Stack", "variablesReference": STACK, "expensive": false },
+            { "name": "RAM", "variablesReference": RAM, "expensive": true },
+        ]
+    })
+}
+
+fn hex(addr: usize) -> String {
+    format!("0x{:03X}", addr)
+}
+
+fn parse_reference(reference: &str) -> Option<i64> {
+    let digits = reference.strip_prefix("0x").unwrap_or(reference);
+    i64::from_str_radix(digits, 16).ok()
+}
+
+// The memoryReference plus offset from a readMemory or disassemble request.
+fn memory_address(args: &Value) -> Result<i64, String> {
+    let reference = args["memoryReference"].as_str().unwrap_or("");
+    let addr = parse_reference(reference).ok_or_else(|| format!("bad memory reference: {}", reference))?;
+    Ok(addr.saturating_add(args["offset"].as_i64().unwrap_or(0)))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| group | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+// Reads one message: a Content-Length header, a blank line, then that
+// many bytes of JSON. Returns None at the end of the input.
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() && length.is_some() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0; length.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+// Serves a session over a pair of streams (usually stdin and stdout) until
+// the client disconnects. Once a program is launched, frame is called at
+// the start of every frame, running or not, and returns the keys held down
+// or None to end the session; it's also where the caller draws and keeps
+// time.
+pub fn serve<R, W, F>(input: R, mut output: W, mut frame: F) -> io::Result<()>
+where
+    R: BufRead + Send + 'static,
+    W: Write,
+    F: FnMut(&Processor) -> Option<[bool; 16]>,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut input = input;
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = Session::new();
+    while !session.finished() {
+        match session.processor().map(&mut frame) {
+            // Nothing to do until a program is launched.
+            None => match receiver.recv() {
+                Ok(message) => session.handle(&message),
+                Err(_) => break,
+            },
+            Some(Some(keypad)) => {
+                loop {
+                    match receiver.try_recv() {
+                        Ok(message) => session.handle(&message),
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => return Ok(()),
+                    }
+                }
+                session.run_frame(keypad);
+            }
+            Some(None) => session.terminate(),
+        }
+        for message in session.take_messages() {
+            write_message(&mut output, &message)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "./dap_test.rs"]
+mod dap_test;
@@ -0,0 +1,194 @@
+use super::*;
+use std::env;
+use std::io::Cursor;
+const PROGRAM: [u8; 12] = [
+    0x60, 0x05, // LD V0, 5
+    0x22, 0x08, // loop: CALL sub
+    0x70, 0x01, // ADD V0, 1
+    0x12, 0x02, // JP loop
+    0xA3, 0x00, // sub: LD I, 0x300
+    0x00, 0xEE, // RET
+];
+const MAP: &str = "line 0x200 test.asm 1
+line 0x202 test.asm 2
+line 0x204 test.asm 3
+line 0x206 test.asm 4
+line 0x208 test.asm 6
+line 0x20A test.asm 7
+symbol 0x200 start
+symbol 0x208 sub
+";
+// Writes the ROM and its line map to a temporary directory, and returns
+// the ROM's path.
+fn write_program(name: &str) -> String {
+    let dir = env::temp_dir().join(format!("chip8_dap_test_{}", name));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("test.ch8"), &PROGRAM[..]).unwrap();
+    fs::write(dir.join("test.map"), MAP).unwrap();
+    dir.join("test.ch8").to_string_lossy().into_owned()
+}
+fn request(session: &mut Session, command: &str, arguments: Value) -> Vec<Value> {
+    session.handle(&json!({ "seq": 1, "type": "request", "command": command, "arguments": arguments }));
+    session.take_messages()
+}
+fn launch(name: &str, stop_on_entry: bool) -> Session {
+    let mut session = Session::new();
+    request(&mut session, "initialize", json!({}));
+    let program = write_program(name);
+    let messages = request(&mut session, "launch", json!({ "program": program, "stopOnEntry": stop_on_entry }));
+    assert_eq!(messages[0]["success"], true);
+    assert_eq!(messages[1]["event"], "initialized");
+    session
+}
+fn run_until_stopped(session: &mut Session) -> Value {
+    for _ in 0..100 {
+        session.run_frame([false; 16]);
+        if let Some(event) = session.take_messages().into_iter().find(|m| m["event"] == "stopped") {
+            return event;
+        }
+    }
+    panic!("never stopped");
+}
+#[test]
+fn test_source_breakpoint() {
+    let mut session = launch("source_breakpoint", false);
+    let messages = request(
+        &mut session,
+        "setBreakpoints",
+        json!({ "source": { "path": "/somewhere/test.asm" }, "breakpoints": [{ "line": 5 }, { "line": 9 }] }),
+    );
+    let breakpoints = &messages[0]["body"]["breakpoints"];
+    assert_eq!(breakpoints[0], json!({ "verified": true, "line": 6, "instructionReference": "0x208" }));
+    assert_eq!(breakpoints[1]["verified"], false);
+
+    request(&mut session, "configurationDone", json!({}));
+    assert!(session.running());
+    let stopped = run_until_stopped(&mut session);
+    assert_eq!(stopped["body"]["reason"], "breakpoint");
+
+    let messages = request(&mut session, "stackTrace", json!({ "threadId": 1 }));
+    let frames = &messages[0]["body"]["stackFrames"];
+    assert_eq!(frames[0]["name"], "sub (0x208)");
+    assert_eq!(frames[0]["line"], 6);
+    assert_eq!(frames[0]["source"]["name"], "test.asm");
+    assert_eq!(frames[1]["name"], "start (0x202)");
+    assert_eq!(frames[1]["line"], 2);
+}
+#[test]
+fn test_instruction_breakpoint_and_variables() {
+    let mut session = launch("instruction_breakpoint", false);
+    request(&mut session, "setInstructionBreakpoints", json!({ "breakpoints": [{ "instructionReference": "0x204" }] }));
+    request(&mut session, "configurationDone", json!({}));
+    run_until_stopped(&mut session);
+
+    let messages = request(&mut session, "variables", json!({ "variablesReference": REGISTERS }));
+    let registers = messages[0]["body"]["variables"].as_array().unwrap().clone();
+    assert_eq!(registers[0], json!({ "name": "V0", "value": "0x05", "variablesReference": 0 }));
+    assert_eq!(registers[16]["value"], "0x300");
+    assert_eq!(registers[17]["value"], "0x204");
+
+    let messages = request(&mut session, "variables", json!({ "variablesReference": RAM }));
+    let rows = &messages[0]["body"]["variables"];
+    assert_eq!(rows.as_array().unwrap().len(), 4096 / RAM_ROW);
+    assert_eq!(rows[0x20]["name"], "0x200");
+    assert!(rows[0x20]["value"].as_str().unwrap().starts_with("60 05 22 08"));
+}
+#[test]
+fn test_stepping() {
+    let mut session = launch("stepping", true);
+    let messages = request(&mut session, "configurationDone", json!({}));
+    assert_eq!(messages[1]["body"]["reason"], "entry");
+    assert!(!session.running());
+
+    let pc = |session: &Session| session.processor().unwrap().pc();
+    request(&mut session, "stepIn", json!({ "threadId": 1 }));
+    assert_eq!(pc(&session), 0x202);
+
+    // Stepping over the CALL runs the whole subroutine.
+    request(&mut session, "next", json!({ "threadId": 1 }));
+    run_until_stopped(&mut session);
+    assert_eq!(pc(&session), 0x204);
+
+    request(&mut session, "stepIn", json!({ "threadId": 1 }));
+    request(&mut session, "stepIn", json!({ "threadId": 1 }));
+    request(&mut session, "stepIn", json!({ "threadId": 1 }));
+    assert_eq!(pc(&session), 0x208);
+    let messages = request(&mut session, "variables", json!({ "variablesReference": STACK }));
+    assert_eq!(messages[0]["body"]["variables"][0]["value"], "0x204");
+
+    request(&mut session, "stepOut", json!({ "threadId": 1 }));
+    run_until_stopped(&mut session);
+    assert_eq!(pc(&session), 0x204);
+}
+#[test]
+fn test_pause() {
+    let mut session = launch("pause", false);
+    request(&mut session, "configurationDone", json!({}));
+    session.run_frame([false; 16]);
+    let messages = request(&mut session, "pause", json!({ "threadId": 1 }));
+    assert_eq!(messages[1]["body"]["reason"], "pause");
+    assert!(!session.running());
+}
+#[test]
+fn test_memory_and_disassembly() {
+    let mut session = launch("memory", true);
+    let messages = request(&mut session, "readMemory", json!({ "memoryReference": "0x200", "count": 4 }));
+    assert_eq!(messages[0]["body"]["data"], "YAUiCA==");
+    let messages = request(&mut session, "readMemory", json!({ "memoryReference": "0xFFE", "count": 4 }));
+    assert_eq!(messages[0]["body"]["unreadableBytes"], 2);
+    let args = json!({ "memoryReference": "0x200", "offset": i64::MAX, "count": u64::MAX });
+    let messages = request(&mut session, "readMemory", args);
+    assert_eq!(messages[0]["body"]["data"], "");
+    let messages = request(&mut session, "readMemory", json!({ "memoryReference": "0x1", "count": u64::MAX }));
+    assert_eq!(messages[0]["body"]["unreadableBytes"], 1);
+
+    let messages = request(
+        &mut session,
+        "disassemble",
+        json!({ "memoryReference": "0x204", "instructionOffset": -1, "instructionCount": 2 }),
+    );
+    let instructions = &messages[0]["body"]["instructions"];
+    assert_eq!(instructions[0]["instruction"], "CALL 0x208");
+    assert_eq!(instructions[0]["symbol"], "start");
+    assert_eq!(instructions[1]["address"], "0x204");
+    assert_eq!(instructions[1]["line"], 3);
+
+    let args = json!({ "memoryReference": "0x200", "instructionOffset": i64::MAX, "instructionCount": i64::MAX });
+    let messages = request(&mut session, "disassemble", args);
+    let instructions = messages[0]["body"]["instructions"].as_array().unwrap();
+    assert_eq!(instructions.len(), 4096);
+    assert_eq!(instructions[0]["presentationHint"], "invalid");
+}
+#[test]
+fn test_errors() {
+    let mut session = Session::new();
+    let messages = request(&mut session, "stackTrace", json!({}));
+    assert_eq!(messages[0]["success"], false);
+    assert_eq!(messages[0]["message"], "no program has been launched");
+    let messages = request(&mut session, "launch", json!({ "program": "/no/such/rom.ch8" }));
+    assert_eq!(messages[0]["success"], false);
+    let messages = request(&mut session, "frobnicate", json!({}));
+    assert_eq!(messages[0]["message"], "unsupported request: frobnicate");
+    request(&mut session, "disconnect", json!({}));
+    assert!(session.finished());
+}
+#[test]
+fn test_message_framing() {
+    let mut output = Vec::new();
+    write_message(&mut output, &json!({ "seq": 1 })).unwrap();
+    write_message(&mut output, &json!({ "seq": 2 })).unwrap();
+    assert!(output.starts_with(b"Content-Length: 9\r\n\r\n{\"seq\":1}"));
+
+    let mut input = Cursor::new(output);
+    assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "seq": 1 })));
+    assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "seq": 2 })));
+    assert_eq!(read_message(&mut input).unwrap(), None);
+}
+#[test]
+fn test_base64() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+}
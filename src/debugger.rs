@@ -0,0 +1,120 @@
+use std::collections::BTreeSet;
+
+use processor::{MemoryAccess, Processor};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchKind {
+    Write,
+    Read,
+    Access,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub kind: WatchKind,
+    pub start: usize,
+    pub len: usize,
+}
+
+impl Watchpoint {
+    fn triggered_by(&self, access: &MemoryAccess) -> bool {
+        let kind_matches = match self.kind {
+            WatchKind::Write => access.write,
+            WatchKind::Read => !access.write,
+            WatchKind::Access => true,
+        };
+        kind_matches && access.overlaps(self.start, self.len)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    // About to run the instruction at a breakpoint.
+    Breakpoint(usize),
+    // The last instruction touched a watched range.
+    Watchpoint(Watchpoint),
+}
+
+// Runs a processor under the control of a debugger front end, stopping at
+// breakpoints and watchpoints. Breakpoints stop before the instruction at
+// their address runs; watchpoints stop just after the instruction that
+// touched the watched memory, the way hardware watchpoints do.
+pub struct Debugger {
+    pub processor: Processor,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: Vec<Watchpoint>,
+    // The breakpoint we last stopped at, which shouldn't stop us again
+    // when we carry on from it.
+    stopped_at: Option<usize>,
+}
+
+impl Debugger {
+    pub fn new(mut processor: Processor) -> Self {
+        // Watchpoints look at the instructions the processor reports.
+        processor.set_tracing(true);
+        Debugger {
+            processor,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            stopped_at: None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|&other| other != watchpoint);
+        self.watchpoints.len() != count
+    }
+
+    // Runs a single cycle, ignoring breakpoints. A cycle spent waiting for a
+    // key or for the display doesn't move the program counter.
+    pub fn step(&mut self, keypad: [bool; 16]) -> Option<StopReason> {
+        self.stopped_at = None;
+        let output = self.processor.tick(keypad);
+        let entry = output.trace?;
+        let access = MemoryAccess::of(entry.opcode, entry.before.i as usize)?;
+        self.watchpoints
+            .iter()
+            .find(|watchpoint| watchpoint.triggered_by(&access))
+            .map(|&watchpoint| StopReason::Watchpoint(watchpoint))
+    }
+
+    // Runs up to the given number of cycles, stopping early at a breakpoint
+    // or watchpoint.
+    pub fn run(&mut self, cycles: u32, keypad: [bool; 16]) -> Option<StopReason> {
+        for _ in 0..cycles {
+            let pc = self.processor.pc();
+            let resuming = self.stopped_at.take() == Some(pc);
+            if !resuming && self.breakpoints.contains(&pc) {
+                self.stopped_at = Some(pc);
+                return Some(StopReason::Breakpoint(pc));
+            }
+            if let Some(reason) = self.step(keypad) {
+                return Some(reason);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+#[path = "./debugger_test.rs"]
+mod debugger_test;
@@ -0,0 +1,60 @@
+use super::*;
+use processor::Processor;
+fn build_debugger() -> Debugger {
+    let mut processor = Processor::new();
+    processor.load(&[
+        0x60, 0x05, // LD V0, 5
+        0xA3, 0x00, // loop: LD I, 0x300
+        0xF0, 0x33, // LD B, V0
+        0xF2, 0x65, // LD V2, [I]
+        0x12, 0x02, // JP loop
+    ]);
+    Debugger::new(processor)
+}
+#[test]
+fn test_breakpoint() {
+    let mut debugger = build_debugger();
+    debugger.add_breakpoint(0x204);
+    assert_eq!(debugger.run(100, [false; 16]), Some(StopReason::Breakpoint(0x204)));
+    assert_eq!(debugger.processor.pc(), 0x204);
+
+    // Carrying on runs the instruction at the breakpoint and goes round
+    // the loop to it again.
+    assert_eq!(debugger.run(100, [false; 16]), Some(StopReason::Breakpoint(0x204)));
+    assert_eq!(debugger.processor.registers().v[2], 5);
+
+    assert!(debugger.remove_breakpoint(0x204));
+    assert_eq!(debugger.run(100, [false; 16]), None);
+}
+#[test]
+fn test_breakpoint_across_runs() {
+    let mut debugger = build_debugger();
+    debugger.add_breakpoint(0x204);
+    assert_eq!(debugger.run(2, [false; 16]), None);
+    assert_eq!(debugger.run(2, [false; 16]), Some(StopReason::Breakpoint(0x204)));
+}
+#[test]
+fn test_watchpoints() {
+    let mut debugger = build_debugger();
+    let write = Watchpoint { kind: WatchKind::Write, start: 0x302, len: 1 };
+    let read = Watchpoint { kind: WatchKind::Read, start: 0x300, len: 3 };
+    debugger.add_watchpoint(write);
+    debugger.add_watchpoint(read);
+
+    assert_eq!(debugger.run(100, [false; 16]), Some(StopReason::Watchpoint(write)));
+    assert_eq!(debugger.processor.pc(), 0x206);
+    assert_eq!(debugger.processor.ram()[0x302], 5);
+    assert_eq!(debugger.run(100, [false; 16]), Some(StopReason::Watchpoint(read)));
+    assert_eq!(debugger.processor.pc(), 0x208);
+
+    assert!(debugger.remove_watchpoint(write));
+    assert!(!debugger.remove_watchpoint(write));
+    assert_eq!(debugger.run(100, [false; 16]), Some(StopReason::Watchpoint(read)));
+}
+#[test]
+fn test_step() {
+    let mut debugger = build_debugger();
+    debugger.add_breakpoint(0x200);
+    assert_eq!(debugger.step([false; 16]), None);
+    assert_eq!(debugger.processor.pc(), 0x202);
+}
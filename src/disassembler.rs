@@ -0,0 +1,60 @@
+// Turns opcodes back into the mnemonics from Cowgod's CHIP-8 reference,
+// the same ones used in the comments throughout this crate. Bytes and
+// addresses are written in hex.
+pub fn disassemble(opcode: u16) -> String {
+    let nibbles = (
+        (opcode & 0xF000) >> 12,
+        (opcode & 0x0F00) >> 8,
+        (opcode & 0x00F0) >> 4,
+        opcode & 0x000F,
+    );
+    let nnn = opcode & 0x0FFF;
+    let kk = opcode & 0x00FF;
+    let x = nibbles.1;
+    let y = nibbles.2;
+    let n = nibbles.3;
+
+    match nibbles {
+        (0x00, 0x00, 0x0e, 0x00) => "CLS".to_string(),
+        (0x00, 0x00, 0x0e, 0x0e) => "RET".to_string(),
+        (0x01, _, _, _) => format!("JP 0x{:03X}", nnn),
+        (0x02, _, _, _) => format!("CALL 0x{:03X}", nnn),
+        (0x03, _, _, _) => format!("SE V{:X}, 0x{:02X}", x, kk),
+        (0x04, _, _, _) => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        (0x05, _, _, 0x00) => format!("SE V{:X}, V{:X}", x, y),
+        (0x06, _, _, _) => format!("LD V{:X}, 0x{:02X}", x, kk),
+        (0x07, _, _, _) => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        (0x08, _, _, 0x00) => format!("LD V{:X}, V{:X}", x, y),
+        (0x08, _, _, 0x01) => format!("OR V{:X}, V{:X}", x, y),
+        (0x08, _, _, 0x02) => format!("AND V{:X}, V{:X}", x, y),
+        (0x08, _, _, 0x03) => format!("XOR V{:X}, V{:X}", x, y),
+        (0x08, _, _, 0x04) => format!("ADD V{:X}, V{:X}", x, y),
+        (0x08, _, _, 0x05) => format!("SUB V{:X}, V{:X}", x, y),
+        (0x08, _, _, 0x06) => format!("SHR V{:X}, V{:X}", x, y),
+        (0x08, _, _, 0x07) => format!("SUBN V{:X}, V{:X}", x, y),
+        (0x08, _, _, 0x0e) => format!("SHL V{:X}, V{:X}", x, y),
+        (0x09, _, _, 0x00) => format!("SNE V{:X}, V{:X}", x, y),
+        (0x0a, _, _, _) => format!("LD I, 0x{:03X}", nnn),
+        (0x0b, _, _, _) => format!("JP V0, 0x{:03X}", nnn),
+        (0x0c, _, _, _) => format!("RND V{:X}, 0x{:02X}", x, kk),
+        (0x0d, _, _, _) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        (0x0e, _, 0x09, 0x0e) => format!("SKP V{:X}", x),
+        (0x0e, _, 0x0a, 0x01) => format!("SKNP V{:X}", x),
+        (0x0f, _, 0x00, 0x07) => format!("LD V{:X}, DT", x),
+        (0x0f, _, 0x00, 0x0a) => format!("LD V{:X}, K", x),
+        (0x0f, _, 0x01, 0x05) => format!("LD DT, V{:X}", x),
+        (0x0f, _, 0x01, 0x08) => format!("LD ST, V{:X}", x),
+        (0x0f, _, 0x01, 0x0e) => format!("ADD I, V{:X}", x),
+        (0x0f, _, 0x02, 0x09) => format!("LD F, V{:X}", x),
+        (0x0f, _, 0x03, 0x03) => format!("LD B, V{:X}", x),
+        (0x0f, _, 0x05, 0x05) => format!("LD [I], V{:X}", x),
+        (0x0f, _, 0x06, 0x05) => format!("LD V{:X}, [I]", x),
+        (0x0f, _, 0x07, 0x05) => format!("LD R, V{:X}", x),
+        (0x0f, _, 0x08, 0x05) => format!("LD V{:X}, R", x),
+        _ => format!("DW 0x{:04X}", opcode),
+    }
+}
+
+#[cfg(test)]
+#[path = "./disassembler_test.rs"]
+mod disassembler_test;
@@ -0,0 +1,18 @@
+use super::*;
+#[test]
+fn test_disassemble() {
+    assert_eq!(disassemble(0x00E0), "CLS");
+    assert_eq!(disassemble(0x1300), "JP 0x300");
+    assert_eq!(disassemble(0x6A0F), "LD VA, 0x0F");
+    assert_eq!(disassemble(0x8126), "SHR V1, V2");
+    assert_eq!(disassemble(0xB210), "JP V0, 0x210");
+    assert_eq!(disassemble(0xD125), "DRW V1, V2, 5");
+    assert_eq!(disassemble(0xF065), "LD V0, [I]");
+    assert_eq!(disassemble(0xF30A), "LD V3, K");
+}
+#[test]
+fn test_disassemble_unknown() {
+    assert_eq!(disassemble(0x0123), "DW 0x0123");
+    assert_eq!(disassemble(0x5121), "DW 0x5121");
+    assert_eq!(disassemble(0xFFFF), "DW 0xFFFF");
+}
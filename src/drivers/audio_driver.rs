@@ -1,12 +1,21 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use sdl2;
 use sdl2::audio::{AudioDevice, AudioCallback, AudioSpecDesired};
 
+use tone::{Tone, ToneSettings};
+
+// The audio device plays continuously. Beeping and muting just flip flags
+// that the callback reads, and the tone fades in and out on its own.
 pub struct AudioDriver {
-    device: AudioDevice<SquareWave>,
+    _device: AudioDevice<Beeper>,
+    beep: Arc<AtomicBool>,
+    muted: Arc<AtomicBool>,
 }
 
 impl AudioDriver {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+    pub fn new(sdl_context: &sdl2::Sdl, settings: ToneSettings) -> Self {
         let audio_subsystem = sdl_context.audio().unwrap();
 
         let desired_spec = AudioSpecDesired {
@@ -15,48 +24,60 @@ impl AudioDriver {
             samples: None, // default sample size
         };
 
+        let beep = Arc::new(AtomicBool::new(false));
+        let muted = Arc::new(AtomicBool::new(false));
+
         let device = audio_subsystem
             .open_playback(None, &desired_spec, |spec| {
                 // Show obtained AudioSpec
                 println!("{:?}", spec);
 
                 // initialize the audio callback
-                SquareWave {
-                    phase_inc: 240.0 / spec.freq as f32,
-                    phase: 0.0,
-                    volume: 0.25,
+                Beeper {
+                    tone: Tone::new(settings, spec.freq as u32),
+                    beep: beep.clone(),
+                    muted: muted.clone(),
                 }
             })
             .unwrap();
+        device.resume();
 
-        AudioDriver { device: device }
+        AudioDriver {
+            _device: device,
+            beep,
+            muted,
+        }
     }
 
     pub fn start_beep(&self) {
-        self.device.resume();
+        self.beep.store(true, Ordering::Relaxed);
     }
     pub fn stop_beep(&self) {
-        self.device.pause();
+        self.beep.store(false, Ordering::Relaxed);
+    }
+
+    // Returns whether audio is now muted.
+    pub fn toggle_mute(&self) -> bool {
+        !self.muted.fetch_xor(true, Ordering::Relaxed)
     }
 }
 
 
 
 
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+struct Beeper {
+    tone: Tone,
+    beep: Arc<AtomicBool>,
+    muted: Arc<AtomicBool>,
 }
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for Beeper {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
+        let gate = self.beep.load(Ordering::Relaxed) && !self.muted.load(Ordering::Relaxed);
         for x in out.iter_mut() {
-            *x = self.volume * if self.phase < 0.5 { 1.0 } else { -1.0 };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+            *x = self.tone.next_sample(gate);
         }
     }
 }
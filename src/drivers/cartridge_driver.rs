@@ -1,26 +1,63 @@
-use std::fs::File;
-use std::io::prelude::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chip8::patch;
 
 pub struct CartridgeDriver {
     pub rom: [u8; 3584],
     pub size: usize,
+    // The patch applied to the ROM as it was loaded, if any.
+    pub patch: Option<PathBuf>,
 }
 
 impl CartridgeDriver {
-    pub fn new(filename: &str) -> Self {
+    // Loads a ROM, applying the given IPS or BPS patch to it. Without one,
+    // a patch next to the ROM with the same name (game.ips or game.bps for
+    // game.ch8) is applied if there is one.
+    pub fn new(filename: &str, patch: Option<&str>) -> io::Result<Self> {
+        let mut data = read(Path::new(filename))?;
 
-        let mut f = File::open(filename).expect("file not found");
-        let mut buffer = [0u8; 3584];
+        let patch = patch.map(PathBuf::from).or_else(|| find_patch(Path::new(filename)));
+        if let Some(ref path) = patch {
+            data = patch::apply(&data, &read(path)?).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+            })?;
+        }
 
-        let bytes_read = if let Ok(bytes_read) = f.read(&mut buffer) {
-            bytes_read
-        } else {
-            0
-        };
+        // Anything that doesn't fit in the CHIP-8's memory is dropped.
+        let mut buffer = [0u8; 3584];
+        let size = data.len().min(buffer.len());
+        buffer[..size].copy_from_slice(&data[..size]);
 
-        CartridgeDriver {
+        Ok(CartridgeDriver {
             rom: buffer,
-            size: bytes_read,
+            size,
+            patch,
+        })
+    }
+
+    // A stable identifier for the ROM, used to key per-game files like
+    // saved flags. This is 64-bit FNV-1a, which is plenty to tell a few
+    // thousand tiny ROMs apart and won't change between Rust versions.
+    pub fn hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for &byte in &self.rom[..self.size] {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
         }
+        hash
     }
 }
+
+// Reads a file, with its name in any error.
+fn read(path: &Path) -> io::Result<Vec<u8>> {
+    fs::read(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+fn find_patch(rom: &Path) -> Option<PathBuf> {
+    ["ips", "bps"]
+        .iter()
+        .map(|extension| rom.with_extension(extension))
+        .find(|path| path.as_path() != rom && path.is_file())
+}
@@ -4,24 +4,66 @@ use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 
-use CHIP8_WIDTH;
-use CHIP8_HEIGHT;
+use super::text_font::{self, GLYPH_WIDTH, GLYPH_HEIGHT};
+use chip8::{CHIP8_WIDTH, CHIP8_HEIGHT};
+
+const SCREEN_HEIGHT: u32 = (CHIP8_HEIGHT as u32) * 20;
+
+// Indicator icons are 7x5 bitmaps drawn in the top right corner, each
+// bit becoming an INDICATOR_SCALE sized square.
+const INDICATOR_SCALE: u32 = 4;
+const INDICATOR_MARGIN: u32 = 8;
+// On-screen text is drawn with the 3x5 font, each font pixel TEXT_SCALE
+// window pixels across, on a black box so it's readable over the game.
+const TEXT_SCALE: u32 = 3;
+const TEXT_MARGIN: u32 = 8;
+const LINE_HEIGHT: u32 = (GLYPH_HEIGHT + 2) * TEXT_SCALE;
+const CHAR_WIDTH: u32 = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Indicator {
+    Paused,
+    FastForward,
+    Turbo,
+    SlowMotion,
+}
 
-const SCALE_FACTOR: u32 = 20;
-const SCREEN_WIDTH: u32 = (CHIP8_WIDTH as u32) * SCALE_FACTOR;
-const SCREEN_HEIGHT: u32 = (CHIP8_HEIGHT as u32) * SCALE_FACTOR;
+// Everything drawn on top of the CHIP-8 display.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Osd {
+    pub indicator: Option<Indicator>,
+    // Lines of text in the top left corner.
+    pub status: Vec<String>,
+    // A transient notification in the bottom left corner.
+    pub message: Option<String>,
+}
+
+impl Indicator {
+    fn bitmap(&self) -> [u8; 5] {
+        match *self {
+            Indicator::Paused => [0b0110110, 0b0110110, 0b0110110, 0b0110110, 0b0110110],
+            Indicator::FastForward => [0b1000100, 0b1100110, 0b1110111, 0b1100110, 0b1000100],
+            Indicator::Turbo => [0b1000101, 0b1100101, 0b1110101, 0b1100101, 0b1000101],
+            Indicator::SlowMotion => [0b1010000, 0b1011000, 0b1011100, 0b1011000, 0b1010000],
+        }
+    }
+}
 
 pub struct DisplayDriver {
     canvas: Canvas<Window>,
+    scale_factor: u32,
 }
 
 impl DisplayDriver {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+    // Taller displays are scaled down so the window keeps its height. The
+    // width is scaled down with it, so those windows are narrower.
+    pub fn new(sdl_context: &sdl2::Sdl, height: usize) -> Self {
+        let scale_factor = SCREEN_HEIGHT / height as u32;
         let video_subsys = sdl_context.video().unwrap();
         let window = video_subsys
             .window(
                 "rust-sdl2_gfx: draw line & FPSManager",
-                SCREEN_WIDTH,
+                CHIP8_WIDTH as u32 * scale_factor,
                 SCREEN_HEIGHT,
             )
             .position_centered()
@@ -35,22 +77,67 @@ impl DisplayDriver {
         canvas.clear();
         canvas.present();
 
-        DisplayDriver { canvas: canvas }
+        DisplayDriver {
+            canvas,
+            scale_factor,
+        }
     }
 
-    pub fn draw(&mut self, pixels: &[[u8; CHIP8_WIDTH]; CHIP8_HEIGHT]) {
+    pub fn draw(&mut self, pixels: &[[u8; CHIP8_WIDTH]], osd: &Osd) {
+        let scale = self.scale_factor;
         for (y, row) in pixels.iter().enumerate() {
             for (x, &col) in row.iter().enumerate() {
-                let x = (x as u32) * SCALE_FACTOR;
-                let y = (y as u32) * SCALE_FACTOR;
+                let x = (x as u32) * scale;
+                let y = (y as u32) * scale;
 
                 self.canvas.set_draw_color(color(col));
                 let _ = self.canvas
-                    .fill_rect(Rect::new(x as i32, y as i32, SCALE_FACTOR, SCALE_FACTOR));
+                    .fill_rect(Rect::new(x as i32, y as i32, scale, scale));
             }
         }
+        if let Some(indicator) = osd.indicator {
+            self.draw_indicator(indicator);
+        }
+        for (i, line) in osd.status.iter().enumerate() {
+            self.draw_text(TEXT_MARGIN, TEXT_MARGIN + (i as u32) * LINE_HEIGHT, line);
+        }
+        if let Some(ref message) = osd.message {
+            let (_, height) = self.canvas.output_size().unwrap();
+            self.draw_text(TEXT_MARGIN, height - TEXT_MARGIN - LINE_HEIGHT, message);
+        }
         self.canvas.present();
     }
+
+    fn draw_text(&mut self, left: u32, top: u32, text: &str) {
+        let width = (text.chars().count() as u32) * CHAR_WIDTH + TEXT_SCALE;
+        self.canvas.set_draw_color(pixels::Color::RGB(0, 0, 0));
+        let _ = self.canvas
+            .fill_rect(Rect::new(left as i32, top as i32, width, LINE_HEIGHT));
+
+        self.canvas.set_draw_color(pixels::Color::RGB(250, 250, 250));
+        text_font::draw(&mut self.canvas, left + TEXT_SCALE, top + TEXT_SCALE, TEXT_SCALE, text);
+    }
+
+    fn draw_indicator(&mut self, indicator: Indicator) {
+        let (width, _) = self.canvas.output_size().unwrap();
+        let left = width - INDICATOR_MARGIN - 7 * INDICATOR_SCALE;
+
+        self.canvas.set_draw_color(pixels::Color::RGB(250, 250, 0));
+        for (y, row) in indicator.bitmap().iter().enumerate() {
+            for x in 0..7 {
+                if row & (0b1000000 >> x) != 0 {
+                    let x = left + x * INDICATOR_SCALE;
+                    let y = INDICATOR_MARGIN + (y as u32) * INDICATOR_SCALE;
+                    let _ = self.canvas.fill_rect(Rect::new(
+                        x as i32,
+                        y as i32,
+                        INDICATOR_SCALE,
+                        INDICATOR_SCALE,
+                    ));
+                }
+            }
+        }
+    }
 }
 
 fn color(value: u8) -> pixels::Color {
@@ -1,32 +1,122 @@
 use sdl2;
-use sdl2::event::Event;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
 
+use inspector::InspectorKey;
+
+// Emulator controls, as opposed to CHIP-8 keypad keys.
+#[derive(Debug, PartialEq)]
+pub enum Hotkey {
+    Pause,
+    Reset,
+    FrameAdvance,
+    Turbo,
+    SlowMotion,
+    ToggleStats,
+    ToggleProfile,
+    ToggleMessages,
+    ToggleInspector,
+    ToggleCheats,
+    // Moving through and picking from the cheat menu. These come through
+    // whether or not it's open.
+    MenuUp,
+    MenuDown,
+    MenuSelect,
+    Mute,
+}
+
+pub struct InputState {
+    pub keypad: [bool; 16],
+    // Hotkeys pressed since the last poll.
+    pub hotkeys: Vec<Hotkey>,
+    // Fast-forward lasts as long as its key is held down.
+    pub fast_forward: bool,
+    // Keys pressed in the inspector window since the last poll.
+    pub inspector_keys: Vec<InspectorKey>,
+}
+
 pub struct InputDriver {
     events: sdl2::EventPump,
+    inspector_window: Option<u32>,
+    // While the inspector window has the keyboard, typing in it doesn't
+    // press CHIP-8 keys.
+    inspector_focused: bool,
 }
 
 impl InputDriver {
     pub fn new(sdl_context: &sdl2::Sdl) -> Self {
-        InputDriver { events: sdl_context.event_pump().unwrap() }
+        InputDriver {
+            events: sdl_context.event_pump().unwrap(),
+            inspector_window: None,
+            inspector_focused: false,
+        }
+    }
+
+    pub fn set_inspector_window(&mut self, window_id: u32) {
+        self.inspector_window = Some(window_id);
     }
 
 
-    pub fn poll(&mut self) -> Result<[bool; 16], ()> {
+    pub fn poll(&mut self) -> Result<InputState, ()> {
+        let mut hotkeys = Vec::new();
+        let mut inspector_keys = Vec::new();
 
         for event in self.events.poll_iter() {
-            if let Event::Quit { .. } = event {
-                return Err(());
-            };
+            match event {
+                Event::Quit { .. } => return Err(()),
+                // With two windows open, closing one doesn't quit.
+                Event::Window { window_id, win_event, .. } => {
+                    let inspector = Some(window_id) == self.inspector_window;
+                    match win_event {
+                        WindowEvent::Close if inspector => hotkeys.push(Hotkey::ToggleInspector),
+                        WindowEvent::Close => return Err(()),
+                        WindowEvent::FocusGained => self.inspector_focused = inspector,
+                        _ => {}
+                    }
+                }
+                Event::KeyDown { keycode: Some(keycode), window_id, .. }
+                    if Some(window_id) == self.inspector_window && inspector_key(keycode).is_some() =>
+                {
+                    inspector_keys.extend(inspector_key(keycode));
+                }
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
+                    let hotkey = match keycode {
+                        Keycode::P => Some(Hotkey::Pause),
+                        Keycode::Backspace => Some(Hotkey::Reset),
+                        Keycode::N => Some(Hotkey::FrameAdvance),
+                        Keycode::T => Some(Hotkey::Turbo),
+                        Keycode::O => Some(Hotkey::SlowMotion),
+                        Keycode::F1 => Some(Hotkey::ToggleStats),
+                        Keycode::F2 => Some(Hotkey::ToggleProfile),
+                        Keycode::F3 => Some(Hotkey::ToggleMessages),
+                        Keycode::F4 => Some(Hotkey::ToggleInspector),
+                        Keycode::F5 => Some(Hotkey::ToggleCheats),
+                        Keycode::Up => Some(Hotkey::MenuUp),
+                        Keycode::Down => Some(Hotkey::MenuDown),
+                        Keycode::Return => Some(Hotkey::MenuSelect),
+                        Keycode::M => Some(Hotkey::Mute),
+                        _ => None,
+                    };
+                    if let Some(hotkey) = hotkey {
+                        hotkeys.push(hotkey);
+                    }
+                }
+                _ => {}
+            }
         }
 
-        let keys: Vec<Keycode> = self.events
-            .keyboard_state()
-            .pressed_scancodes()
-            .filter_map(Keycode::from_scancode)
-            .collect();
+        let keys: Vec<Keycode> = if self.inspector_focused {
+            Vec::new()
+        } else {
+            self.events
+                .keyboard_state()
+                .pressed_scancodes()
+                .filter_map(Keycode::from_scancode)
+                .collect()
+        };
 
         let mut chip8_keys = [false; 16];
+        let mut fast_forward = false;
 
         for key in keys {
             let index = match key {
@@ -46,6 +136,10 @@ impl InputDriver {
                 Keycode::X => Some(0x0),
                 Keycode::C => Some(0xb),
                 Keycode::V => Some(0xf),
+                Keycode::Tab => {
+                    fast_forward = true;
+                    None
+                }
                 _ => None,
             };
 
@@ -54,6 +148,52 @@ impl InputDriver {
             }
         }
 
-        Ok(chip8_keys)
+        Ok(InputState {
+            keypad: chip8_keys,
+            hotkeys,
+            fast_forward,
+            inspector_keys,
+        })
+    }
+}
+
+// Arrows move the cursor, Tab switches between memory and registers, hex
+// digits type a new value and Escape abandons it, and [ and ] change the
+// height of the sprite viewer.
+fn inspector_key(keycode: Keycode) -> Option<InspectorKey> {
+    let digit = match keycode {
+        Keycode::Num0 => Some(0x0),
+        Keycode::Num1 => Some(0x1),
+        Keycode::Num2 => Some(0x2),
+        Keycode::Num3 => Some(0x3),
+        Keycode::Num4 => Some(0x4),
+        Keycode::Num5 => Some(0x5),
+        Keycode::Num6 => Some(0x6),
+        Keycode::Num7 => Some(0x7),
+        Keycode::Num8 => Some(0x8),
+        Keycode::Num9 => Some(0x9),
+        Keycode::A => Some(0xa),
+        Keycode::B => Some(0xb),
+        Keycode::C => Some(0xc),
+        Keycode::D => Some(0xd),
+        Keycode::E => Some(0xe),
+        Keycode::F => Some(0xf),
+        _ => None,
+    };
+    if let Some(digit) = digit {
+        return Some(InspectorKey::Digit(digit));
+    }
+    match keycode {
+        Keycode::Up => Some(InspectorKey::Up),
+        Keycode::Down => Some(InspectorKey::Down),
+        Keycode::Left => Some(InspectorKey::Left),
+        Keycode::Right => Some(InspectorKey::Right),
+        Keycode::PageUp => Some(InspectorKey::PageUp),
+        Keycode::PageDown => Some(InspectorKey::PageDown),
+        Keycode::Tab => Some(InspectorKey::SwitchFocus),
+        Keycode::Escape => Some(InspectorKey::Cancel),
+        Keycode::RightBracket => Some(InspectorKey::SpriteTaller),
+        Keycode::LeftBracket => Some(InspectorKey::SpriteShorter),
+        _ => None,
     }
 }
@@ -0,0 +1,228 @@
+use sdl2;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use super::text_font::{self, GLYPH_WIDTH, GLYPH_HEIGHT};
+use chip8::processor::Processor;
+use inspector::{self, Focus, Highlight, Inspector, REGISTER_COUNT, ROW_BYTES, WRITE_FADE};
+
+// Everything is laid out on a grid of character cells, drawn with the 3x5
+// font at TEXT_SCALE.
+const TEXT_SCALE: u32 = 2;
+const CELL_WIDTH: u32 = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+const CELL_HEIGHT: u32 = (GLYPH_HEIGHT + 2) * TEXT_SCALE;
+const MARGIN: u32 = 16;
+const COLUMNS: u32 = 110;
+// Rows of the hex dump.
+const DUMP_ROWS: usize = 36;
+// The second and third columns of the window.
+const REGISTERS_COLUMN: u32 = 56;
+const STACK_COLUMN: u32 = 86;
+// Sprite and font pixels are drawn as squares this big.
+const SPRITE_SCALE: u32 = 6;
+const FONT_SCALE: u32 = 4;
+
+const TEXT: Color = Color { r: 250, g: 250, b: 250, a: 0xff };
+const DIM: Color = Color { r: 120, g: 120, b: 120, a: 0xff };
+const FOCUSED: Color = Color { r: 250, g: 250, b: 0, a: 0xff };
+const PIXEL: Color = Color { r: 0, g: 250, b: 0, a: 0xff };
+
+// Draws the memory inspector in a window of its own.
+pub struct InspectorDriver {
+    canvas: Canvas<Window>,
+    font_base: usize,
+}
+
+impl InspectorDriver {
+    pub fn new(sdl_context: &sdl2::Sdl, font_base: usize) -> Self {
+        let video_subsys = sdl_context.video().unwrap();
+        let window = video_subsys
+            .window(
+                "CHIP-8 memory",
+                COLUMNS * CELL_WIDTH + 2 * MARGIN,
+                (DUMP_ROWS as u32 + 1) * CELL_HEIGHT + 2 * MARGIN,
+            )
+            .build()
+            .unwrap();
+        let canvas = window.into_canvas().build().unwrap();
+        InspectorDriver { canvas, font_base }
+    }
+
+    // Keys pressed in this window are meant for the inspector.
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    pub fn show(&mut self) {
+        self.canvas.window_mut().show();
+        self.canvas.window_mut().raise();
+    }
+
+    pub fn hide(&mut self) {
+        self.canvas.window_mut().hide();
+    }
+
+    pub fn draw(&mut self, inspector: &Inspector, processor: &Processor) {
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.draw_memory(inspector, processor);
+        self.draw_registers(inspector, processor);
+        self.draw_sprite(inspector, processor);
+        self.draw_stack(processor);
+        self.draw_font(processor);
+        self.canvas.present();
+    }
+
+    fn draw_memory(&mut self, inspector: &Inspector, processor: &Processor) {
+        let heading = if inspector.focus == Focus::Memory { FOCUSED } else { DIM };
+        self.text(0, 0, "MEMORY", heading);
+        let ram = processor.ram();
+        let first = inspector.first_address(DUMP_ROWS);
+        for row in 0..DUMP_ROWS {
+            let start = first + row * ROW_BYTES;
+            if start >= ram.len() {
+                break;
+            }
+            self.text(0, row as u32 + 1, &format!("{:03X}", start), DIM);
+            for (offset, &byte) in ram[start..].iter().take(ROW_BYTES).enumerate() {
+                let addr = start + offset;
+                let column = 5 + offset as u32 * 3;
+                let highlight = inspector.highlight(addr, processor);
+                let text = match (highlight, inspector.entry()) {
+                    (Highlight::Cursor, Some(entry)) => format!("{:<2}", entry),
+                    _ => format!("{:02X}", byte),
+                };
+                let foreground = match highlight {
+                    Highlight::Cursor => {
+                        self.fill(column, row as u32 + 1, 2, TEXT);
+                        Color::RGB(0, 0, 0)
+                    }
+                    Highlight::Pc => {
+                        self.fill(column, row as u32 + 1, 2, Color::RGB(180, 40, 40));
+                        TEXT
+                    }
+                    Highlight::I => {
+                        self.fill(column, row as u32 + 1, 2, Color::RGB(40, 80, 200));
+                        TEXT
+                    }
+                    Highlight::Stack => {
+                        self.fill(column, row as u32 + 1, 2, Color::RGB(130, 50, 160));
+                        TEXT
+                    }
+                    Highlight::Written(frames) => {
+                        let level = (160 * frames as u32 / WRITE_FADE as u32) as u8;
+                        self.fill(column, row as u32 + 1, 2, Color::RGB(level, level, 0));
+                        TEXT
+                    }
+                    Highlight::None => TEXT,
+                };
+                self.text(column, row as u32 + 1, &text, foreground);
+            }
+        }
+    }
+
+    // V0 to VF in two columns, then the other registers below them.
+    fn draw_registers(&mut self, inspector: &Inspector, processor: &Processor) {
+        let focused = inspector.focus == Focus::Registers;
+        self.text(REGISTERS_COLUMN, 0, "REGISTERS", if focused { FOCUSED } else { DIM });
+        for index in 0..REGISTER_COUNT {
+            let (column, row) = if index < 16 {
+                (REGISTERS_COLUMN + (index / 8) as u32 * 9, 1 + (index % 8) as u32)
+            } else {
+                (REGISTERS_COLUMN, 1 + index as u32 - 7)
+            };
+            let digits = inspector::register_digits(index);
+            let selected = focused && index == inspector.register;
+            let value = match inspector.entry() {
+                Some(ref entry) if selected => format!("{:<1$}", entry, digits),
+                _ => format!("{:01$X}", inspector::register(processor, index), digits),
+            };
+            self.text(column, row, &inspector::register_name(index), DIM);
+            if selected {
+                self.fill(column + 3, row, digits as u32, TEXT);
+            }
+            self.text(column + 3, row, &value, if selected { Color::RGB(0, 0, 0) } else { TEXT });
+        }
+    }
+
+    // The bytes at I drawn the way DXYN would draw them.
+    fn draw_sprite(&mut self, inspector: &Inspector, processor: &Processor) {
+        let i = processor.registers().i as usize;
+        self.text(REGISTERS_COLUMN, 16, &format!("SPRITE AT I, 8X{}", inspector.sprite_height), DIM);
+        let left = MARGIN + REGISTERS_COLUMN * CELL_WIDTH;
+        let top = MARGIN + 17 * CELL_HEIGHT;
+        let ram = processor.ram();
+        for row in 0..inspector.sprite_height {
+            let byte = ram.get(i + row).cloned().unwrap_or(0);
+            for bit in 0..8 {
+                let lit = byte & (0x80 >> bit) != 0;
+                self.canvas.set_draw_color(if lit { PIXEL } else { Color::RGB(30, 30, 30) });
+                let _ = self.canvas.fill_rect(Rect::new(
+                    (left + bit * SPRITE_SCALE) as i32,
+                    (top + row as u32 * SPRITE_SCALE) as i32,
+                    SPRITE_SCALE - 1,
+                    SPRITE_SCALE - 1,
+                ));
+            }
+        }
+    }
+
+    // Return addresses, outermost first.
+    fn draw_stack(&mut self, processor: &Processor) {
+        self.text(STACK_COLUMN, 0, "STACK", DIM);
+        let stack = processor.stack();
+        if stack.is_empty() {
+            self.text(STACK_COLUMN, 1, "EMPTY", DIM);
+        }
+        for (depth, &addr) in stack.iter().enumerate() {
+            self.text(STACK_COLUMN, 1 + depth as u32, &format!("{:X}  {:03X}", depth, addr), TEXT);
+        }
+    }
+
+    // The hex digit sprites as they are in memory, which a program could
+    // have changed.
+    fn draw_font(&mut self, processor: &Processor) {
+        self.text(STACK_COLUMN, 18, &format!("FONT AT {:03X}", self.font_base), DIM);
+        let left = MARGIN + STACK_COLUMN * CELL_WIDTH;
+        let top = MARGIN + 19 * CELL_HEIGHT;
+        let ram = processor.ram();
+        self.canvas.set_draw_color(PIXEL);
+        for digit in 0..16 {
+            let glyph_left = left + (digit % 8) * 6 * FONT_SCALE;
+            let glyph_top = top + (digit / 8) * 7 * FONT_SCALE;
+            for row in 0..5 {
+                let byte = ram[self.font_base + (digit * 5 + row) as usize];
+                for bit in 0..4 {
+                    if byte & (0x80 >> bit) != 0 {
+                        let _ = self.canvas.fill_rect(Rect::new(
+                            (glyph_left + bit * FONT_SCALE) as i32,
+                            (glyph_top + row * FONT_SCALE) as i32,
+                            FONT_SCALE,
+                            FONT_SCALE,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    fn text(&mut self, column: u32, row: u32, text: &str, color: Color) {
+        self.canvas.set_draw_color(color);
+        let left = MARGIN + column * CELL_WIDTH;
+        let top = MARGIN + row * CELL_HEIGHT + TEXT_SCALE;
+        text_font::draw(&mut self.canvas, left, top, TEXT_SCALE, text);
+    }
+
+    // Fills the cells behind some text.
+    fn fill(&mut self, column: u32, row: u32, width: u32, color: Color) {
+        self.canvas.set_draw_color(color);
+        let _ = self.canvas.fill_rect(Rect::new(
+            (MARGIN + column * CELL_WIDTH - TEXT_SCALE) as i32,
+            (MARGIN + row * CELL_HEIGHT) as i32,
+            width * CELL_WIDTH + TEXT_SCALE,
+            CELL_HEIGHT,
+        ));
+    }
+}
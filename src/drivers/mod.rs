@@ -2,8 +2,13 @@ mod display_driver;
 mod audio_driver;
 mod input_driver;
 mod cartridge_driver;
+mod storage_driver;
+mod inspector_driver;
+mod text_font;
 
-pub use self::display_driver::DisplayDriver;
+pub use self::display_driver::{DisplayDriver, Indicator, Osd};
 pub use self::audio_driver::AudioDriver;
-pub use self::input_driver::InputDriver;
+pub use self::input_driver::{InputDriver, Hotkey};
 pub use self::cartridge_driver::CartridgeDriver;
+pub use self::storage_driver::StorageDriver;
+pub use self::inspector_driver::InspectorDriver;
@@ -0,0 +1,70 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// Per-ROM files that should outlive a single run, kept under the user's
+// data directory in a folder named after the ROM's hash.
+pub struct StorageDriver {
+    dir: PathBuf,
+}
+
+impl StorageDriver {
+    pub fn new(rom_hash: u64) -> Self {
+        StorageDriver { dir: data_dir().join(format!("{:016x}", rom_hash)) }
+    }
+
+    pub fn load_flags(&self) -> Option<Vec<u8>> {
+        fs::read(self.dir.join("flags")).ok()
+    }
+
+    pub fn save_flags(&self, flags: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join("flags"), flags)
+    }
+
+    // Cheat lists are text files named after the list.
+    pub fn load_cheats(&self, name: &str) -> io::Result<String> {
+        fs::read_to_string(self.cheats_dir().join(format!("{}.txt", name)))
+    }
+
+    pub fn save_cheats(&self, name: &str, text: &str) -> io::Result<()> {
+        fs::create_dir_all(self.cheats_dir())?;
+        fs::write(self.cheats_dir().join(format!("{}.txt", name)), text)
+    }
+
+    // The names of the saved cheat lists, in order.
+    pub fn cheat_lists(&self) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(self.cheats_dir())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+            .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn cheats_dir(&self) -> PathBuf {
+        self.dir.join("cheats")
+    }
+}
+
+// $CHIP8_DATA_DIR if set, otherwise the platform's usual spot for
+// application data.
+fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("CHIP8_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+    let base = if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        PathBuf::from(dir)
+    } else if let Some(dir) = env::var_os("APPDATA") {
+        PathBuf::from(dir)
+    } else if let Some(home) = env::var_os("HOME") {
+        PathBuf::from(home).join(".local").join("share")
+    } else {
+        PathBuf::from(".")
+    };
+    base.join("chip8")
+}
@@ -0,0 +1,348 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use line_map::parse_address;
+use machine::MachineConfig;
+use processor::{Backend, Processor};
+use CHIP8_WIDTH;
+use CYCLES_PER_FRAME;
+
+// A number a game keeps somewhere a program can see it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Byte(usize),
+    // Big-endian, like everything else CHIP-8 stores.
+    Word(usize),
+    // Decimal digits one per byte, most significant first, the way FX33
+    // writes them. Most games keep their score like this.
+    Bcd(usize, usize),
+    Register(usize),
+}
+
+impl Value {
+    pub fn read(&self, processor: &Processor) -> i64 {
+        let ram = processor.ram();
+        let byte = |addr: usize| ram.get(addr).cloned().unwrap_or(0) as i64;
+        match *self {
+            Value::Byte(addr) => byte(addr),
+            Value::Word(addr) => byte(addr) << 8 | byte(addr + 1),
+            Value::Bcd(addr, digits) => (addr..addr + digits).fold(0, |total, addr| total * 10 + byte(addr)),
+            Value::Register(x) => processor.registers().v[x] as i64,
+        }
+    }
+
+    // Parses the fields after the keyword: `byte ADDR`, `word ADDR`,
+    // `bcd ADDR DIGITS` or `register VX`. Returns the value and the fields
+    // left over.
+    fn parse<'a>(fields: &'a [&'a str]) -> Option<(Value, &'a [&'a str])> {
+        match fields {
+            ["byte", addr, rest @ ..] => Some((Value::Byte(parse_address(addr)?), rest)),
+            ["word", addr, rest @ ..] => Some((Value::Word(parse_address(addr)?), rest)),
+            ["bcd", addr, digits, rest @ ..] => Some((Value::Bcd(parse_address(addr)?, digits.parse().ok()?), rest)),
+            ["register", name, rest @ ..] => {
+                let x = name.strip_prefix('v').or_else(|| name.strip_prefix('V'))?;
+                let x = usize::from_str_radix(x, 16).ok().filter(|&x| x < 16)?;
+                Some((Value::Register(x), rest))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Condition {
+    pub value: Value,
+    // The episode is over when comparing the value with the operand gives
+    // one of these orderings.
+    pub orderings: &'static [Ordering],
+    pub operand: i64,
+}
+
+impl Condition {
+    pub fn holds(&self, processor: &Processor) -> bool {
+        self.orderings.contains(&self.value.read(processor).cmp(&self.operand))
+    }
+}
+
+fn parse_comparison(op: &str) -> Option<&'static [Ordering]> {
+    match op {
+        "==" => Some(&[Ordering::Equal]),
+        "!=" => Some(&[Ordering::Less, Ordering::Greater]),
+        "<" => Some(&[Ordering::Less]),
+        "<=" => Some(&[Ordering::Less, Ordering::Equal]),
+        ">" => Some(&[Ordering::Greater]),
+        ">=" => Some(&[Ordering::Greater, Ordering::Equal]),
+        _ => None,
+    }
+}
+
+// How to play a particular game as an environment: how many frames each
+// step lasts, where the reward comes from and when an episode is over. Game
+// specs are text files with one setting per line:
+//
+//   frames 4
+//   reward bcd 0x2F0 3
+//   reward byte 0x2F8 100
+//   done byte 0x2F9 == 0
+//   max-steps 5000
+//
+// Each step's reward is how much each reward value went up during the
+// step, times its scale (1 if left out), added together. The episode is
+// done as soon as any done condition holds, or after max-steps steps.
+// Values are `byte ADDR`, `word ADDR`, `bcd ADDR DIGITS` or `register VX`,
+// and conditions compare them with ==, !=, <, <=, > or >=. Blank lines and
+// lines starting with # are ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameSpec {
+    pub frames: u32,
+    pub rewards: Vec<(Value, f64)>,
+    pub done: Vec<Condition>,
+    pub max_steps: Option<u64>,
+}
+
+impl Default for GameSpec {
+    fn default() -> Self {
+        GameSpec {
+            frames: 4,
+            rewards: Vec::new(),
+            done: Vec::new(),
+            max_steps: None,
+        }
+    }
+}
+
+impl GameSpec {
+    pub fn parse(text: &str) -> Result<GameSpec, String> {
+        let mut spec = GameSpec::default();
+        for (index, line) in text.lines().enumerate() {
+            let error = || format!("line {}: can't make sense of `{}`", index + 1, line.trim());
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [] => {}
+                [first, ..] if first.starts_with('#') => {}
+                ["frames", frames] => spec.frames = frames.parse().ok().filter(|&f| f > 0).ok_or_else(error)?,
+                ["max-steps", steps] => spec.max_steps = Some(steps.parse().map_err(|_| error())?),
+                ["reward", rest @ ..] => {
+                    let (value, rest) = Value::parse(rest).ok_or_else(error)?;
+                    let scale = match rest {
+                        [] => 1.0,
+                        [scale] => scale.parse().map_err(|_| error())?,
+                        _ => return Err(error()),
+                    };
+                    spec.rewards.push((value, scale));
+                }
+                ["done", rest @ ..] => {
+                    let (value, rest) = Value::parse(rest).ok_or_else(error)?;
+                    match rest {
+                        [op, operand] => spec.done.push(Condition {
+                            value,
+                            orderings: parse_comparison(op).ok_or_else(error)?,
+                            operand: operand.parse().map_err(|_| error())?,
+                        }),
+                        _ => return Err(error()),
+                    }
+                }
+                _ => return Err(error()),
+            }
+        }
+        Ok(spec)
+    }
+
+    pub fn load(path: &Path) -> Result<GameSpec, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        GameSpec::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+// What a step did.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub framebuffer: Vec<u8>,
+    pub reward: f64,
+    pub done: bool,
+}
+
+// A game as a reinforcement learning environment, in the style of Gym:
+// reset starts an episode, and step holds some keys down for a few frames
+// and says what came of it. Nothing is drawn or played, so it runs as fast
+// as the processor does.
+//
+// An episode is fully determined by the seed given to reset and the keys
+// pressed, and cloning an environment clones everything, so a clone and
+// the original go on to do the same thing.
+#[derive(Clone)]
+pub struct Env {
+    rom: Vec<u8>,
+    config: MachineConfig,
+    spec: GameSpec,
+    // Blocks unless set otherwise, since it's the fastest.
+    backend: Backend,
+    processor: Processor,
+    // The reward values as of the end of the last step.
+    values: Vec<i64>,
+    steps: u64,
+    done: bool,
+}
+
+impl Env {
+    // The environment starts out done; call reset before stepping.
+    pub fn new(rom: &[u8], config: MachineConfig, spec: GameSpec) -> Self {
+        let processor = Processor::with_config(config.clone());
+        Env {
+            rom: rom.to_vec(),
+            config,
+            spec,
+            backend: Backend::Blocks,
+            processor,
+            values: Vec::new(),
+            steps: 0,
+            done: true,
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+        self.processor.set_backend(backend);
+    }
+
+    pub fn spec(&self) -> &GameSpec {
+        &self.spec
+    }
+
+    pub fn processor(&self) -> &Processor {
+        &self.processor
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    // Starts a new episode from a freshly booted machine and returns the
+    // first framebuffer.
+    pub fn reset(&mut self, seed: u64) -> Vec<u8> {
+        self.processor = Processor::with_config(self.config.clone());
+        self.processor.set_backend(self.backend);
+        self.processor.seed_rng(seed);
+        self.processor.load(&self.rom);
+        self.values = self.read_rewards();
+        self.steps = 0;
+        self.done = false;
+        self.framebuffer()
+    }
+
+    // Runs the spec's number of frames with the keys held.
+    pub fn step(&mut self, keys: [bool; 16]) -> Step {
+        assert!(!self.done, "step called on a finished episode; reset first");
+        for _ in 0..self.spec.frames {
+            self.processor.run(CYCLES_PER_FRAME, keys);
+        }
+        self.steps += 1;
+
+        let values = self.read_rewards();
+        let reward = values
+            .iter()
+            .zip(&self.values)
+            .zip(&self.spec.rewards)
+            .map(|((&now, &before), &(_, scale))| (now - before) as f64 * scale)
+            .sum();
+        self.values = values;
+        self.done = self.spec.done.iter().any(|condition| condition.holds(&self.processor))
+            || self.spec.max_steps.is_some_and(|max| self.steps >= max);
+        Step {
+            framebuffer: self.framebuffer(),
+            reward,
+            done: self.done,
+        }
+    }
+
+    // The display a row at a time, one byte per pixel, 1 for lit.
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let vram = self.processor.vram();
+        let mut pixels = Vec::with_capacity(vram.len() * CHIP8_WIDTH);
+        for row in vram {
+            pixels.extend_from_slice(row);
+        }
+        pixels
+    }
+
+    fn read_rewards(&self) -> Vec<i64> {
+        self.spec.rewards.iter().map(|&(value, _)| value.read(&self.processor)).collect()
+    }
+}
+
+// Several environments stepped together, spread over threads. An
+// environment whose episode ends is reset straight away with the next seed,
+// and the step reports the first framebuffer of the new episode along with
+// the reward and done of the old one. Seeds are handed out in order, so a
+// run is as repeatable as a single environment's.
+#[derive(Clone)]
+pub struct VecEnv {
+    envs: Vec<Env>,
+    next_seed: u64,
+}
+
+impl VecEnv {
+    pub fn new(envs: Vec<Env>) -> Self {
+        VecEnv { envs, next_seed: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    pub fn envs(&self) -> &[Env] {
+        &self.envs
+    }
+
+    // Resets every environment, the first with the seed, the next with the
+    // seed plus one and so on.
+    pub fn reset(&mut self, seed: u64) -> Vec<Vec<u8>> {
+        self.next_seed = seed;
+        let mut framebuffers = Vec::with_capacity(self.envs.len());
+        for env in &mut self.envs {
+            framebuffers.push(env.reset(self.next_seed));
+            self.next_seed += 1;
+        }
+        framebuffers
+    }
+
+    // Steps each environment with its own keys.
+    pub fn step(&mut self, keys: &[[bool; 16]]) -> Vec<Step> {
+        assert_eq!(keys.len(), self.envs.len(), "one set of keys per environment");
+        // Platforms without threads, like wasm32, report no parallelism.
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let mut steps: Vec<Step> = if threads == 1 {
+            self.envs.iter_mut().zip(keys).map(|(env, &keys)| env.step(keys)).collect()
+        } else {
+            let chunk = self.envs.len().div_ceil(threads).max(1);
+            thread::scope(|scope| {
+                let handles: Vec<_> = self
+                    .envs
+                    .chunks_mut(chunk)
+                    .zip(keys.chunks(chunk))
+                    .map(|(envs, keys)| {
+                        scope.spawn(move || envs.iter_mut().zip(keys).map(|(env, &keys)| env.step(keys)).collect::<Vec<_>>())
+                    })
+                    .collect();
+                handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+            })
+        };
+        for (env, step) in self.envs.iter_mut().zip(&mut steps) {
+            if step.done {
+                step.framebuffer = env.reset(self.next_seed);
+                self.next_seed += 1;
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+#[path = "./env_test.rs"]
+mod env_test;
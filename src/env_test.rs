@@ -0,0 +1,175 @@
+use super::*;
+use machine::Quirks;
+// Scores a point every couple of frames while key 5 is held, keeping the
+// score as three BCD digits at 0x300.
+const SCORE_GAME: [u8; 26] = [
+    0x65, 0x00, // LD V5, 0
+    0xA3, 0x00, // LD I, 0x300
+    0xF5, 0x33, // LD B, V5
+    0x61, 0x05, // LD V1, 5
+    0xE1, 0xA1, // SKNP V1
+    0x75, 0x01, // ADD V5, 1
+    0xF5, 0x33, // LD B, V5
+    0x62, 0x08, // LD V2, 8
+    0xF2, 0x15, // LD DT, V2
+    0xF2, 0x07, // LD V2, DT
+    0x32, 0x00, // SE V2, 0
+    0x12, 0x12, // JP 0x212
+    0x12, 0x08, // JP 0x208
+];
+// Keeps writing random bytes to 0x300.
+const RANDOM_GAME: [u8; 8] = [
+    0xC0, 0xFF, // RND V0, 0xFF
+    0xA3, 0x00, // LD I, 0x300
+    0xF0, 0x55, // LD [I], V0
+    0x12, 0x00, // JP 0x200
+];
+
+fn keys(held: &[usize]) -> [bool; 16] {
+    let mut keys = [false; 16];
+    for &key in held {
+        keys[key] = true;
+    }
+    keys
+}
+fn env(rom: &[u8], spec: &str) -> Env {
+    Env::new(rom, MachineConfig::chip8(), GameSpec::parse(spec).unwrap())
+}
+#[test]
+fn test_parse() {
+    let spec = GameSpec::parse(
+        "# Scores\nframes 2\nreward bcd 0x2F0 3\nreward byte 2F8 -100\n\ndone register VF != 0\ndone word 0x300 >= 1000\nmax-steps 50\n",
+    )
+    .unwrap();
+    assert_eq!(spec.frames, 2);
+    assert_eq!(spec.rewards, vec![(Value::Bcd(0x2F0, 3), 1.0), (Value::Byte(0x2F8), -100.0)]);
+    assert_eq!(spec.done.len(), 2);
+    assert_eq!(spec.done[0].value, Value::Register(15));
+    assert_eq!(spec.done[0].orderings, &[Ordering::Less, Ordering::Greater]);
+    assert_eq!(spec.done[1].value, Value::Word(0x300));
+    assert_eq!(spec.done[1].operand, 1000);
+    assert_eq!(spec.max_steps, Some(50));
+
+    assert_eq!(GameSpec::parse("").unwrap(), GameSpec::default());
+    assert_eq!(
+        GameSpec::parse("frames 4\ndone byte 0x300 => 1"),
+        Err("line 2: can't make sense of `done byte 0x300 => 1`".to_string())
+    );
+    assert!(GameSpec::parse("reward register VG").is_err());
+    assert!(GameSpec::parse("frames 0").is_err());
+}
+#[test]
+fn test_values() {
+    let mut processor = Processor::new();
+    processor.ram_mut()[0x300..0x303].copy_from_slice(&[1, 2, 3]);
+    assert_eq!(Value::Byte(0x301).read(&processor), 2);
+    assert_eq!(Value::Word(0x300).read(&processor), 0x0102);
+    assert_eq!(Value::Bcd(0x300, 3).read(&processor), 123);
+    assert_eq!(Value::Register(0).read(&processor), 0);
+}
+#[test]
+fn test_rewards_and_done() {
+    let mut env = env(&SCORE_GAME, "reward bcd 0x300 3 0.5\ndone bcd 0x300 3 >= 10");
+    let framebuffer = env.reset(0);
+    assert_eq!(framebuffer, vec![0; 64 * 32]);
+
+    // Nothing happens without the key.
+    for _ in 0..5 {
+        assert_eq!(env.step(keys(&[])), Step { framebuffer: framebuffer.clone(), reward: 0.0, done: false });
+    }
+
+    let mut total = 0.0;
+    let mut steps = 0;
+    loop {
+        let step = env.step(keys(&[5]));
+        assert!(step.reward >= 0.0);
+        total += step.reward;
+        steps += 1;
+        if step.done {
+            break;
+        }
+        assert!(steps < 20, "the score should reach 10");
+    }
+    let score = Value::Bcd(0x300, 3).read(env.processor());
+    assert!(score >= 10);
+    assert_eq!(total, score as f64 * 0.5);
+    assert!(env.is_done());
+}
+#[test]
+fn test_max_steps() {
+    let mut env = env(&SCORE_GAME, "max-steps 3");
+    env.reset(0);
+    assert!(!env.step(keys(&[])).done);
+    assert!(!env.step(keys(&[])).done);
+    assert!(env.step(keys(&[])).done);
+    env.reset(0);
+    assert!(!env.step(keys(&[])).done);
+}
+#[test]
+fn test_seeds_are_repeatable() {
+    let run = |seed| {
+        let mut env = env(&RANDOM_GAME, "frames 1");
+        env.reset(seed);
+        (0..10)
+            .map(|_| {
+                env.step(keys(&[]));
+                env.processor().ram()[0x300]
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(run(7), run(7));
+    assert_ne!(run(7), run(8));
+}
+#[test]
+fn test_clone_is_deterministic() {
+    for backend in [Backend::Interpreter, Backend::DecodeCache, Backend::Blocks] {
+        for quirks in Quirks::all() {
+            let mut config = MachineConfig::chip8();
+            config.quirks = quirks;
+            let mut env = Env::new(&RANDOM_GAME, config, GameSpec::parse("reward byte 0x300").unwrap());
+            env.set_backend(backend);
+            env.reset(3);
+            for _ in 0..3 {
+                env.step(keys(&[1]));
+            }
+            let mut clone = env.clone();
+            for _ in 0..5 {
+                assert_eq!(clone.step(keys(&[2])), env.step(keys(&[2])));
+                assert_eq!(clone.processor().ram(), env.processor().ram());
+                assert_eq!(clone.processor().registers(), env.processor().registers());
+            }
+        }
+    }
+}
+#[test]
+fn test_vec_env_matches_single_envs() {
+    let spec = "frames 1\nreward byte 0x300\nmax-steps 3";
+    let mut vec_env = VecEnv::new(vec![env(&RANDOM_GAME, spec); 4]);
+    assert_eq!(vec_env.len(), 4);
+    vec_env.reset(100);
+
+    // Each environment gets seeds in order as its episodes end: all four
+    // end together every third step, so the second episodes get 104 to
+    // 107.
+    let mut singles: Vec<Env> = (0..4)
+        .map(|i| {
+            let mut env = env(&RANDOM_GAME, spec);
+            env.reset(100 + i);
+            env
+        })
+        .collect();
+    let mut next_seed = 104;
+    for _ in 0..7 {
+        let actions: Vec<[bool; 16]> = (0..4).map(|i| keys(&[i])).collect();
+        let steps = vec_env.step(&actions);
+        for (i, env) in singles.iter_mut().enumerate() {
+            let mut expected = env.step(actions[i]);
+            if expected.done {
+                expected.framebuffer = env.reset(next_seed);
+                next_seed += 1;
+            }
+            assert_eq!(steps[i], expected);
+            assert_eq!(vec_env.envs()[i].processor().ram(), env.processor().ram());
+        }
+    }
+}
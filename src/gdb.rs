@@ -0,0 +1,463 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::str;
+
+use debugger::{Debugger, StopReason, WatchKind, Watchpoint};
+use processor::Processor;
+use CYCLES_PER_FRAME;
+
+// GDB has no idea what a CHIP-8 is, so we describe the registers to it.
+// Register numbers follow the order here: V0-VF are 0-15, then I, PC, SP,
+// DT and ST. Multi-byte registers are little-endian.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" type="uint8"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+// Size in bytes of each register, by register number.
+const REGISTER_SIZES: [usize; 21] = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 1, 1, 1];
+
+// The byte GDB sends to interrupt a running program.
+const INTERRUPT: u8 = 0x03;
+
+enum Command {
+    Reply(String),
+    Resume { step: bool },
+    Detach,
+    Kill,
+}
+
+// A GDB remote serial protocol server for one debugging session. GDB (or
+// anything else that speaks the protocol) drives the processor: it can
+// read and write registers and RAM, set breakpoints and watchpoints, and
+// step or continue.
+pub struct GdbStub {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    no_ack: bool,
+    pub debugger: Debugger,
+}
+
+impl GdbStub {
+    pub fn new(stream: TcpStream, debugger: Debugger) -> Self {
+        // Packets are tiny and every one waits for a reply.
+        let _ = stream.set_nodelay(true);
+        GdbStub {
+            stream,
+            buffer: Vec::new(),
+            no_ack: false,
+            debugger,
+        }
+    }
+
+    // Serves requests until the client detaches, kills the program or
+    // hangs up. The program starts out stopped. While it runs, frame is
+    // called at the start of every frame with the processor, and returns
+    // the keys to hold down during that frame, or None to end the session.
+    pub fn run<F>(&mut self, mut frame: F) -> io::Result<()>
+    where
+        F: FnMut(&Processor) -> Option<[bool; 16]>,
+    {
+        while let Some(packet) = self.read_packet()? {
+            match self.handle(&packet) {
+                Command::Reply(reply) => self.send_packet(&reply)?,
+                Command::Resume { step } => match self.resume(step, &mut frame)? {
+                    Some(reply) => self.send_packet(&reply)?,
+                    None => break,
+                },
+                Command::Detach => {
+                    self.send_packet("OK")?;
+                    break;
+                }
+                Command::Kill => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, packet: &str) -> Command {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => "S05".to_string(),
+            "g" => self.read_registers(),
+            "G" => ok_or_error(self.write_registers(args)),
+            "p" => match parse_hex(args) {
+                Some(number) if number < REGISTER_SIZES.len() => self.read_register(number),
+                _ => "E01".to_string(),
+            },
+            "P" => ok_or_error(self.write_register(args)),
+            "m" => self.read_memory(args).unwrap_or_else(|| "E01".to_string()),
+            "M" => ok_or_error(self.write_memory(args)),
+            "Z" | "z" => match self.set_point(args, command == "Z") {
+                Some(reply) => reply,
+                None => "E01".to_string(),
+            },
+            "c" | "s" => {
+                if let Some(addr) = parse_hex(args) {
+                    self.debugger.processor.set_pc(addr);
+                }
+                return Command::Resume { step: command == "s" };
+            }
+            "D" => return Command::Detach,
+            "k" => return Command::Kill,
+            "H" | "T" => "OK".to_string(),
+            "q" => self.query(args),
+            "Q" if args == "StartNoAckMode" => {
+                // This packet itself has already been acknowledged.
+                self.no_ack = true;
+                "OK".to_string()
+            }
+            "v" => {
+                if args == "Cont?" {
+                    "vCont;c;C;s;S".to_string()
+                } else if let Some(actions) = args.strip_prefix("Cont;") {
+                    let step = actions.starts_with('s') || actions.starts_with('S');
+                    return Command::Resume { step };
+                } else if args.starts_with("Kill") {
+                    return Command::Kill;
+                } else {
+                    String::new()
+                }
+            }
+            _ => String::new(),
+        };
+        Command::Reply(reply)
+    }
+
+    fn query(&self, args: &str) -> String {
+        if args.starts_with("Supported") {
+            "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+;swbreak+;hwbreak+".to_string()
+        } else if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+            match parse_pair(range, ',') {
+                Some((offset, length)) => {
+                    let xml = TARGET_XML.as_bytes();
+                    let start = offset.min(xml.len());
+                    let end = (start + length).min(xml.len());
+                    let prefix = if end == xml.len() { "l" } else { "m" };
+                    format!("{}{}", prefix, String::from_utf8_lossy(&xml[start..end]))
+                }
+                None => "E01".to_string(),
+            }
+        } else {
+            match args {
+                "Attached" => "1".to_string(),
+                "C" => "QC1".to_string(),
+                "fThreadInfo" => "m1".to_string(),
+                "sThreadInfo" => "l".to_string(),
+                _ => String::new(),
+            }
+        }
+    }
+
+    fn register_values(&self) -> [usize; 21] {
+        let processor = &self.debugger.processor;
+        let registers = processor.registers();
+        let mut values = [0; 21];
+        for (value, &v) in values.iter_mut().zip(&registers.v) {
+            *value = v as usize;
+        }
+        values[16] = registers.i as usize;
+        values[17] = processor.pc();
+        values[18] = processor.sp();
+        values[19] = registers.delay_timer as usize;
+        values[20] = registers.sound_timer as usize;
+        values
+    }
+
+    fn set_register_values(&mut self, values: &[usize; 21]) {
+        let processor = &mut self.debugger.processor;
+        let mut registers = processor.registers();
+        for (v, &value) in registers.v.iter_mut().zip(values) {
+            *v = value as u8;
+        }
+        registers.i = values[16] as u16;
+        registers.delay_timer = values[19] as u8;
+        registers.sound_timer = values[20] as u8;
+        processor.set_registers(&registers);
+        processor.set_pc(values[17]);
+        processor.set_sp(values[18]);
+    }
+
+    fn read_registers(&self) -> String {
+        (0..REGISTER_SIZES.len())
+            .map(|number| self.read_register(number))
+            .collect()
+    }
+
+    fn read_register(&self, number: usize) -> String {
+        to_little_endian_hex(self.register_values()[number], REGISTER_SIZES[number])
+    }
+
+    fn write_registers(&mut self, hex: &str) -> Option<()> {
+        let mut values = [0; 21];
+        let mut at = 0;
+        for (value, &size) in values.iter_mut().zip(&REGISTER_SIZES) {
+            *value = from_little_endian_hex(hex.get(at..at + size * 2)?)?;
+            at += size * 2;
+        }
+        self.set_register_values(&values);
+        Some(())
+    }
+
+    fn write_register(&mut self, args: &str) -> Option<()> {
+        let mut parts = args.splitn(2, '=');
+        let number = parse_hex(parts.next()?)?;
+        let size = *REGISTER_SIZES.get(number)?;
+        let hex = parts.next()?;
+        if hex.len() != size * 2 {
+            return None;
+        }
+        let mut values = self.register_values();
+        values[number] = from_little_endian_hex(hex)?;
+        self.set_register_values(&values);
+        Some(())
+    }
+
+    // Reads as much of the range as lies inside RAM.
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let (addr, len) = parse_pair(args, ',')?;
+        let ram = self.debugger.processor.ram();
+        if addr >= ram.len() {
+            return None;
+        }
+        let end = addr.checked_add(len)?.min(ram.len());
+        Some(ram[addr..end].iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    fn write_memory(&mut self, args: &str) -> Option<()> {
+        let mut parts = args.splitn(2, ':');
+        let (addr, len) = parse_pair(parts.next()?, ',')?;
+        let bytes = decode_hex(parts.next()?)?;
+        let ram = self.debugger.processor.ram_mut();
+        let end = addr.checked_add(len)?;
+        if bytes.len() != len || end > ram.len() {
+            return None;
+        }
+        ram[addr..end].copy_from_slice(&bytes);
+        Some(())
+    }
+
+    // Z and z packets: TYPE,ADDR,KIND. For watchpoints KIND is the length
+    // of the watched range.
+    fn set_point(&mut self, args: &str, insert: bool) -> Option<String> {
+        let mut parts = args.split(',');
+        let kind = parts.next()?;
+        let addr = parse_hex(parts.next()?)?;
+        let len = parse_hex(parts.next()?)?.max(1);
+        let debugger = &mut self.debugger;
+        // Watched ranges must lie inside RAM.
+        let in_ram = addr.checked_add(len).is_some_and(|end| end <= debugger.processor.ram().len());
+        if ["2", "3", "4"].contains(&kind) && !in_ram {
+            return None;
+        }
+        let watch = |kind| Watchpoint { kind, start: addr, len };
+        match (kind, insert) {
+            ("0", true) | ("1", true) => debugger.add_breakpoint(addr),
+            ("0", false) | ("1", false) => {
+                debugger.remove_breakpoint(addr);
+            }
+            ("2", true) => debugger.add_watchpoint(watch(WatchKind::Write)),
+            ("3", true) => debugger.add_watchpoint(watch(WatchKind::Read)),
+            ("4", true) => debugger.add_watchpoint(watch(WatchKind::Access)),
+            ("2", false) => {
+                debugger.remove_watchpoint(watch(WatchKind::Write));
+            }
+            ("3", false) => {
+                debugger.remove_watchpoint(watch(WatchKind::Read));
+            }
+            ("4", false) => {
+                debugger.remove_watchpoint(watch(WatchKind::Access));
+            }
+            _ => return Some(String::new()),
+        }
+        Some("OK".to_string())
+    }
+
+    // Runs the program until it stops, and returns the stop reply. Returns
+    // None if the session should end instead.
+    fn resume<F>(&mut self, step: bool, frame: &mut F) -> io::Result<Option<String>>
+    where
+        F: FnMut(&Processor) -> Option<[bool; 16]>,
+    {
+        if step {
+            let reason = self.debugger.step([false; 16]);
+            return Ok(Some(stop_reply(reason)));
+        }
+        loop {
+            let keypad = match frame(&self.debugger.processor) {
+                Some(keypad) => keypad,
+                None => return Ok(None),
+            };
+            if let Some(reason) = self.debugger.run(CYCLES_PER_FRAME, keypad) {
+                return Ok(Some(stop_reply(Some(reason))));
+            }
+            match self.poll_interrupt()? {
+                Some(true) => return Ok(Some("S02".to_string())),
+                Some(false) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    // Checks for an interrupt without waiting. Returns None if the client
+    // has hung up.
+    fn poll_interrupt(&mut self) -> io::Result<Option<bool>> {
+        let mut bytes = [0; 64];
+        self.stream.set_nonblocking(true)?;
+        let result = self.stream.read(&mut bytes);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Ok(None),
+            Ok(count) => {
+                let interrupted = bytes[..count].contains(&INTERRUPT);
+                self.buffer
+                    .extend(bytes[..count].iter().filter(|&&byte| byte != INTERRUPT));
+                Ok(Some(interrupted))
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(Some(false)),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.buffer.is_empty() {
+            let mut bytes = [0; 1024];
+            let count = self.stream.read(&mut bytes)?;
+            if count == 0 {
+                return Ok(None);
+            }
+            self.buffer.extend_from_slice(&bytes[..count]);
+        }
+        Ok(Some(self.buffer.remove(0)))
+    }
+
+    // Reads the next packet, acknowledging it unless acks are off. Acks
+    // from the client and stray interrupts are skipped.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            loop {
+                match self.read_byte()? {
+                    Some(b'$') => break,
+                    Some(_) => continue,
+                    None => return Ok(None),
+                }
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+            let mut checksum = Vec::new();
+            for _ in 0..2 {
+                match self.read_byte()? {
+                    Some(byte) => checksum.push(byte),
+                    None => return Ok(None),
+                }
+            }
+
+            let valid = String::from_utf8(checksum)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(&hex, 16).ok())
+                == Some(checksum_of(&data));
+            if self.no_ack {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            if valid {
+                self.stream.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()
+    }
+}
+
+fn stop_reply(reason: Option<StopReason>) -> String {
+    match reason {
+        None => "S05".to_string(),
+        Some(StopReason::Breakpoint(_)) => "T05swbreak:;".to_string(),
+        Some(StopReason::Watchpoint(watchpoint)) => {
+            let kind = match watchpoint.kind {
+                WatchKind::Write => "watch",
+                WatchKind::Read => "rwatch",
+                WatchKind::Access => "awatch",
+            };
+            format!("T05{}:{:x};", kind, watchpoint.start)
+        }
+    }
+}
+
+fn ok_or_error(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".to_string(),
+        None => "E01".to_string(),
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+fn parse_pair(text: &str, separator: char) -> Option<(usize, usize)> {
+    let mut parts = text.splitn(2, separator);
+    Some((parse_hex(parts.next()?)?, parse_hex(parts.next()?)?))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = str::from_utf8(pair).ok().filter(|pair| pair.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+fn to_little_endian_hex(value: usize, size: usize) -> String {
+    (0..size).map(|byte| format!("{:02x}", (value >> (8 * byte)) & 0xFF)).collect()
+}
+
+fn from_little_endian_hex(hex: &str) -> Option<usize> {
+    let bytes = decode_hex(hex)?;
+    Some(bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as usize))
+}
+
+#[cfg(test)]
+#[path = "./gdb_test.rs"]
+mod gdb_test;
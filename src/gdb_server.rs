@@ -0,0 +1,20 @@
+use std::io;
+use std::net::TcpListener;
+
+use chip8::debugger::Debugger;
+use chip8::gdb::GdbStub;
+use chip8::processor::Processor;
+
+// Waits for a debugger to connect on the local port, then hands the
+// processor over to it until it disconnects. frame is called once per
+// frame while the program runs; see GdbStub::run.
+pub fn serve<F>(port: u16, processor: Processor, frame: F) -> io::Result<()>
+where
+    F: FnMut(&Processor) -> Option<[bool; 16]>,
+{
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("Waiting for GDB on 127.0.0.1:{}", port);
+    let (stream, addr) = listener.accept()?;
+    println!("GDB connected from {}", addr);
+    GdbStub::new(stream, Debugger::new(processor)).run(frame)
+}
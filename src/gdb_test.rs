@@ -0,0 +1,146 @@
+use super::*;
+use std::net::TcpListener;
+use std::thread;
+// A scripted client: sends a packet and returns the reply, checking the
+// acknowledgements on the way.
+struct Client {
+    stream: TcpStream,
+}
+impl Client {
+    fn send(&mut self, data: &str) -> String {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes()).unwrap();
+        let mut ack = [0];
+        self.stream.read_exact(&mut ack).unwrap();
+        assert_eq!(ack[0], b'+');
+        self.read_reply()
+    }
+    fn read_reply(&mut self) -> String {
+        let mut reply = Vec::new();
+        let mut byte = [0];
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            reply.push(byte[0]);
+        }
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        self.stream.write_all(b"+").unwrap();
+        assert_eq!(reply[0], b'$');
+        String::from_utf8(reply[1..].to_vec()).unwrap()
+    }
+}
+// Starts a stub for the program on a local port. The stub thread returns
+// the debugger when the session ends.
+fn start(program: &[u8]) -> (Client, thread::JoinHandle<Debugger>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut processor = Processor::new();
+    processor.load(program);
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut stub = GdbStub::new(stream, Debugger::new(processor));
+        stub.run(|_| Some([false; 16])).unwrap();
+        stub.debugger
+    });
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_nodelay(true).unwrap();
+    let client = Client { stream };
+    (client, server)
+}
+const PROGRAM: [u8; 10] = [
+    0x60, 0x05, // LD V0, 5
+    0xA3, 0x00, // loop: LD I, 0x300
+    0xF0, 0x33, // LD B, V0
+    0xF2, 0x65, // LD V2, [I]
+    0x12, 0x02, // JP loop
+];
+#[test]
+fn test_registers() {
+    let (mut client, server) = start(&PROGRAM);
+    assert!(client.send("qSupported:swbreak+").contains("qXfer:features:read+"));
+    assert_eq!(client.send("?"), "S05");
+    // V0-VF, then I, PC, SP, DT and ST.
+    assert_eq!(client.send("g"), format!("{}0000000200{}", "00".repeat(16), "0000"));
+    assert_eq!(client.send("s"), "S05");
+    assert_eq!(client.send("p0"), "05");
+    assert_eq!(client.send("p11"), "0202");
+    assert_eq!(client.send("P10=3412"), "OK");
+    assert_eq!(client.send("p10"), "3412");
+    assert_eq!(client.send("p15"), "E01");
+    assert_eq!(client.send("D"), "OK");
+    let debugger = server.join().unwrap();
+    assert_eq!(debugger.processor.registers().i, 0x1234);
+}
+#[test]
+fn test_memory() {
+    let (mut client, server) = start(&PROGRAM);
+    assert_eq!(client.send("m200,4"), "6005a300");
+    assert_eq!(client.send("M300,2:abcd"), "OK");
+    assert_eq!(client.send("m300,2"), "abcd");
+    assert_eq!(client.send("mffe,4"), "0000");
+    assert_eq!(client.send("m1000,1"), "E01");
+    assert_eq!(client.send("M1000,1:00"), "E01");
+    assert_eq!(client.send("m1,ffffffffffffffff"), "E01");
+    assert_eq!(client.send("M10,ffffffffffffffff:00"), "E01");
+    client.send("D");
+    server.join().unwrap();
+}
+#[test]
+fn test_breakpoints_and_watchpoints() {
+    let (mut client, server) = start(&PROGRAM);
+    assert_eq!(client.send("Z0,206,2"), "OK");
+    assert_eq!(client.send("c"), "T05swbreak:;");
+    assert_eq!(client.send("p11"), "0602");
+    assert_eq!(client.send("z0,206,2"), "OK");
+
+    assert_eq!(client.send("Z2,301,1"), "OK");
+    assert_eq!(client.send("c"), "T05watch:301;");
+    assert_eq!(client.send("p11"), "0602");
+    assert_eq!(client.send("z2,301,1"), "OK");
+
+    assert_eq!(client.send("Z2,ffffffffffffffff,2"), "E01");
+    assert_eq!(client.send("Z4,300,ffffffffffffffff"), "E01");
+    assert_eq!(client.send("Z3,ffe,4"), "E01");
+    assert_eq!(client.send("Z3,300,3"), "OK");
+    assert_eq!(client.send("vCont;c"), "T05rwatch:300;");
+    assert_eq!(client.send("p11"), "0802");
+    client.send("D");
+    server.join().unwrap();
+}
+#[test]
+fn test_interrupt() {
+    let (mut client, server) = start(&PROGRAM);
+    client.stream.write_all(b"$c#63").unwrap();
+    let mut ack = [0];
+    client.stream.read_exact(&mut ack).unwrap();
+    client.stream.write_all(&[INTERRUPT]).unwrap();
+    assert_eq!(client.read_reply(), "S02");
+    client.send("D");
+    server.join().unwrap();
+}
+#[test]
+fn test_target_description() {
+    let (mut client, server) = start(&PROGRAM);
+    let first = client.send("qXfer:features:read:target.xml:0,40");
+    assert!(first.starts_with("m<?xml"));
+    assert_eq!(first.len(), 1 + 0x40);
+    let rest = client.send("qXfer:features:read:target.xml:40,10000");
+    assert!(rest.starts_with('l'));
+    assert!(rest.ends_with("</target>\n"));
+    client.send("D");
+    server.join().unwrap();
+}
+#[test]
+fn test_no_ack_mode() {
+    let (mut client, server) = start(&PROGRAM);
+    assert_eq!(client.send("QStartNoAckMode"), "OK");
+    client.stream.write_all(b"$?#3f").unwrap();
+    let mut reply = [0; 7];
+    client.stream.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"$S05#b8");
+    client.stream.write_all(b"$k#6b").unwrap();
+    server.join().unwrap();
+}
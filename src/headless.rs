@@ -0,0 +1,152 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use drivers::CartridgeDriver;
+use options::Options;
+use chip8::profiler::Profiler;
+use chip8::remote::Server;
+use wav::AudioRecorder;
+use gdb_server;
+use netplay_connection;
+use remote_frontend::Emulator;
+use chip8::netplay;
+use chip8::CYCLES_PER_FRAME;
+use boot;
+
+// Runs a ROM for a fixed number of frames (or under GDB) with no window,
+// no sound device and nothing pressed on the keypad. Saved flags are
+// neither loaded nor written, and CXKK is seeded with 0 unless --seed is
+// given, so every run of the same ROM behaves identically. Under remote
+// control it runs in real time, until --frames or for good, and the keys
+// are the remote's.
+pub fn run(options: &Options) -> io::Result<()> {
+    let patch = options.patch.as_deref();
+    let mut cartridge_driver = CartridgeDriver::new(&options.cartridge_filename, patch)?;
+    let mut processor = boot(options, &cartridge_driver, None);
+
+    if let Some(port) = options.gdb {
+        return gdb_server::serve(port, processor, |_| Some([false; 16]));
+    }
+
+    let mut recorder = match options.audio_out {
+        Some(ref path) => Some(AudioRecorder::create(
+            path,
+            options.sample_rate,
+            options.tone,
+            CYCLES_PER_FRAME * 60,
+        )?),
+        None => None,
+    };
+    let mut trace = match options.trace {
+        Some(ref path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+    let mut profiler = options.profile.as_ref().map(|_| {
+        Profiler::new(options.config.load_address, cartridge_driver.size)
+    });
+    let tracing = trace.is_some() || profiler.is_some();
+    processor.set_tracing(tracing);
+
+    // Nothing is pressed here, but the other player's keys still count.
+    let mut netplay = netplay_connection::connect(options, cartridge_driver.hash())?;
+    if let Some(ref session) = netplay {
+        processor.seed_rng(session.seed());
+    }
+
+    let mut remote = match options.remote {
+        Some(ref address) => Some(Server::bind(address)?),
+        None => None,
+    };
+    if let Some(ref server) = remote {
+        println!("Remote control on {}", server.address());
+    }
+    let mut paused = false;
+    let frame_duration = Duration::new(0, 1_000_000_000 / 60);
+    let mut next_frame = Instant::now();
+
+    let mut frame = 0;
+    while options.frames.is_none_or(|frames| frame < frames) {
+        frame += 1;
+        if let Some(ref mut server) = remote {
+            server.poll(&mut Emulator {
+                options,
+                processor: &mut processor,
+                paused: &mut paused,
+                cartridge_driver: &mut cartridge_driver,
+                storage_driver: None,
+                tracing,
+            });
+        }
+        let keypad = match netplay {
+            Some(ref mut session) => session.exchange([false; 16])?,
+            None => [false; 16],
+        };
+        // Nothing needs to see individual cycles, so run the whole frame at
+        // once.
+        if recorder.is_none() && !tracing && remote.is_none() {
+            processor.run(CYCLES_PER_FRAME, keypad);
+        } else {
+            let keypad = match remote {
+                Some(ref server) => server.remote.keypad(keypad),
+                None => keypad,
+            };
+            let cycles = if paused { 0 } else { CYCLES_PER_FRAME };
+            let mut ran = 0;
+            let mut beep = false;
+            for _ in 0..cycles {
+                if let Some(ref mut server) = remote {
+                    if server.remote.at_breakpoint(&processor) {
+                        paused = true;
+                        break;
+                    }
+                }
+                ran += 1;
+                let output = processor.tick(keypad);
+                beep = output.beep;
+                if let Some(ref mut recorder) = recorder {
+                    recorder.cycle(output.beep)?;
+                }
+                if let Some(entry) = output.trace {
+                    if let Some(ref mut trace) = trace {
+                        writeln!(trace, "{}", entry)?;
+                    }
+                    if let Some(ref mut profiler) = profiler {
+                        profiler.record(&entry);
+                    }
+                }
+            }
+            if let Some(ref mut server) = remote {
+                server.remote.frame_done(ran, beep);
+            }
+        }
+        if let Some(ref mut session) = netplay {
+            session.frame_done(netplay::state_hash(&processor))?;
+        }
+
+        if remote.is_some() {
+            next_frame += frame_duration;
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                next_frame = now;
+            }
+        }
+    }
+    if let Some(session) = netplay {
+        session.close()?;
+    }
+
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
+    if let Some(mut trace) = trace {
+        trace.flush()?;
+    }
+    if let (Some(profiler), Some(path)) = (profiler, options.profile.as_ref()) {
+        fs::write(path, profiler.summary())?;
+    }
+    Ok(())
+}
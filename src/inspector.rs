@@ -0,0 +1,242 @@
+use chip8::processor::Processor;
+
+// Bytes per row of the hex dump.
+pub const ROW_BYTES: usize = 16;
+// How many frames a written byte stays highlighted.
+pub const WRITE_FADE: u8 = 30;
+// Sprites are at most 15 rows tall.
+const MAX_SPRITE_HEIGHT: usize = 15;
+
+// The registers in the order they're listed: V0 to VF, then I, PC, SP, DT
+// and ST.
+pub const REGISTER_COUNT: usize = 21;
+
+// Keys the inspector window handles itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InspectorKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    // Switches between editing memory and registers.
+    SwitchFocus,
+    Digit(u8),
+    Cancel,
+    SpriteTaller,
+    SpriteShorter,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Focus {
+    Memory,
+    Registers,
+}
+
+// How a byte in the hex dump stands out, most important first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Highlight {
+    Cursor,
+    Pc,
+    I,
+    // An address on the stack, that a RET will return to.
+    Stack,
+    // Written this many frames before it fades completely.
+    Written(u8),
+    None,
+}
+
+// The state of the memory inspector: where the cursor is, what's being
+// typed, and which bytes were written recently. The window itself is drawn
+// by InspectorDriver. Memory and registers can only be edited while the
+// game is paused, so values don't change under the cursor.
+pub struct Inspector {
+    pub visible: bool,
+    pub focus: Focus,
+    // The byte selected in the hex dump.
+    pub cursor: usize,
+    // The register selected, as an index into the list of registers.
+    pub register: usize,
+    pub sprite_height: usize,
+    // Digits typed so far and how many there are.
+    entry: Option<(usize, usize)>,
+    ram: Vec<u8>,
+    // Frames left for each byte's written highlight.
+    written: Vec<u8>,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Inspector {
+            visible: false,
+            focus: Focus::Memory,
+            cursor: 0x200,
+            register: 0,
+            sprite_height: MAX_SPRITE_HEIGHT,
+            entry: None,
+            ram: Vec::new(),
+            written: Vec::new(),
+        }
+    }
+
+    // Call once per frame to notice which bytes were written since the
+    // last one. The first call just takes a snapshot.
+    pub fn observe(&mut self, ram: &[u8]) {
+        if self.ram.len() != ram.len() {
+            self.ram = ram.to_vec();
+            self.written = vec![0; ram.len()];
+            return;
+        }
+        for (addr, &byte) in ram.iter().enumerate() {
+            if byte != self.ram[addr] {
+                self.ram[addr] = byte;
+                self.written[addr] = WRITE_FADE;
+            } else if self.written[addr] > 0 {
+                self.written[addr] -= 1;
+            }
+        }
+    }
+
+    // Acts on a key pressed in the inspector window. Returns a message to
+    // show the user, if any.
+    pub fn handle(&mut self, key: InspectorKey, processor: &mut Processor, paused: bool) -> Option<String> {
+        let ram_size = processor.ram().len();
+        let move_cursor = |cursor: usize, by: isize| {
+            (cursor as isize + by).max(0).min(ram_size as isize - 1) as usize
+        };
+        let page = (ROW_BYTES * 16) as isize;
+        match (key, self.focus) {
+            (InspectorKey::Digit(digit), _) => return self.enter_digit(digit, processor, paused),
+            (InspectorKey::Cancel, _) => self.entry = None,
+            (InspectorKey::SwitchFocus, Focus::Memory) => self.focus = Focus::Registers,
+            (InspectorKey::SwitchFocus, Focus::Registers) => self.focus = Focus::Memory,
+            (InspectorKey::SpriteTaller, _) => self.sprite_height = (self.sprite_height + 1).min(MAX_SPRITE_HEIGHT),
+            (InspectorKey::SpriteShorter, _) => self.sprite_height = (self.sprite_height - 1).max(1),
+            (InspectorKey::Up, Focus::Registers) => self.register = self.register.saturating_sub(1),
+            (InspectorKey::Down, Focus::Registers) => self.register = (self.register + 1).min(REGISTER_COUNT - 1),
+            (InspectorKey::Left, Focus::Memory) => self.cursor = move_cursor(self.cursor, -1),
+            (InspectorKey::Right, Focus::Memory) => self.cursor = move_cursor(self.cursor, 1),
+            (InspectorKey::Up, Focus::Memory) => self.cursor = move_cursor(self.cursor, -(ROW_BYTES as isize)),
+            (InspectorKey::Down, Focus::Memory) => self.cursor = move_cursor(self.cursor, ROW_BYTES as isize),
+            (InspectorKey::PageUp, Focus::Memory) => self.cursor = move_cursor(self.cursor, -page),
+            (InspectorKey::PageDown, Focus::Memory) => self.cursor = move_cursor(self.cursor, page),
+            _ => return None,
+        }
+        // Moving elsewhere abandons a half-typed value.
+        if key != InspectorKey::SpriteTaller && key != InspectorKey::SpriteShorter {
+            self.entry = None;
+        }
+        None
+    }
+
+    fn enter_digit(&mut self, digit: u8, processor: &mut Processor, paused: bool) -> Option<String> {
+        if !paused {
+            return Some("Pause to edit".to_string());
+        }
+        let (value, count) = self.entry.unwrap_or((0, 0));
+        let value = value << 4 | digit as usize;
+        let digits = match self.focus {
+            Focus::Memory => 2,
+            Focus::Registers => register_digits(self.register),
+        };
+        if count + 1 < digits {
+            self.entry = Some((value, count + 1));
+            return None;
+        }
+        self.entry = None;
+        match self.focus {
+            Focus::Memory => {
+                let cursor = self.cursor;
+                processor.ram_mut()[cursor] = value as u8;
+                // Edits aren't the program writing memory.
+                if cursor < self.ram.len() {
+                    self.ram[cursor] = value as u8;
+                }
+                self.cursor = (cursor + 1).min(processor.ram().len() - 1);
+            }
+            Focus::Registers => set_register(processor, self.register, value),
+        }
+        None
+    }
+
+    // The digits typed so far, for showing in place of the value being
+    // edited.
+    pub fn entry(&self) -> Option<String> {
+        self.entry.map(|(value, count)| format!("{:01$X}", value, count))
+    }
+
+    // The first address shown when the hex dump has room for the given
+    // number of rows. The dump moves a page at a time to keep the cursor
+    // in view.
+    pub fn first_address(&self, rows: usize) -> usize {
+        let page = rows * ROW_BYTES;
+        self.cursor / page * page
+    }
+
+    pub fn highlight(&self, addr: usize, processor: &Processor) -> Highlight {
+        let pc = processor.pc();
+        if self.focus == Focus::Memory && addr == self.cursor {
+            Highlight::Cursor
+        } else if addr == pc || addr == pc + 1 {
+            Highlight::Pc
+        } else if addr == processor.registers().i as usize {
+            Highlight::I
+        } else if processor.stack().iter().any(|&ret| addr == ret || addr == ret + 1) {
+            Highlight::Stack
+        } else {
+            match self.written.get(addr) {
+                Some(&frames) if frames > 0 => Highlight::Written(frames),
+                _ => Highlight::None,
+            }
+        }
+    }
+}
+
+pub fn register_name(index: usize) -> String {
+    match index {
+        0..=15 => format!("V{:X}", index),
+        16 => "I".to_string(),
+        17 => "PC".to_string(),
+        18 => "SP".to_string(),
+        19 => "DT".to_string(),
+        _ => "ST".to_string(),
+    }
+}
+
+// How many hex digits the register is shown and edited with.
+pub fn register_digits(index: usize) -> usize {
+    match index {
+        16 | 17 => 3,
+        _ => 2,
+    }
+}
+
+pub fn register(processor: &Processor, index: usize) -> usize {
+    let registers = processor.registers();
+    match index {
+        0..=15 => registers.v[index] as usize,
+        16 => registers.i as usize,
+        17 => processor.pc(),
+        18 => processor.sp(),
+        19 => registers.delay_timer as usize,
+        _ => registers.sound_timer as usize,
+    }
+}
+
+fn set_register(processor: &mut Processor, index: usize, value: usize) {
+    let mut registers = processor.registers();
+    match index {
+        0..=15 => registers.v[index] = value as u8,
+        16 => registers.i = value as u16,
+        17 => processor.set_pc(value),
+        18 => processor.set_sp(value),
+        19 => registers.delay_timer = value as u8,
+        _ => registers.sound_timer = value as u8,
+    }
+    processor.set_registers(&registers);
+}
+
+#[cfg(test)]
+#[path = "./inspector_test.rs"]
+mod inspector_test;
@@ -0,0 +1,130 @@
+use super::*;
+fn paused_edit(inspector: &mut Inspector, processor: &mut Processor, digits: &[u8]) {
+    for &digit in digits {
+        assert_eq!(inspector.handle(InspectorKey::Digit(digit), processor, true), None);
+    }
+}
+#[test]
+fn test_cursor_movement() {
+    let mut processor = Processor::new();
+    let mut inspector = Inspector::new();
+    inspector.handle(InspectorKey::Right, &mut processor, false);
+    inspector.handle(InspectorKey::Down, &mut processor, false);
+    assert_eq!(inspector.cursor, 0x211);
+    inspector.handle(InspectorKey::PageUp, &mut processor, false);
+    inspector.handle(InspectorKey::PageUp, &mut processor, false);
+    inspector.handle(InspectorKey::PageUp, &mut processor, false);
+    assert_eq!(inspector.cursor, 0);
+    inspector.handle(InspectorKey::Left, &mut processor, false);
+    assert_eq!(inspector.cursor, 0);
+    for _ in 0..20 {
+        inspector.handle(InspectorKey::PageDown, &mut processor, false);
+    }
+    assert_eq!(inspector.cursor, 0xFFF);
+    assert_eq!(inspector.first_address(36), 0xFC0);
+}
+#[test]
+fn test_edit_memory() {
+    let mut processor = Processor::new();
+    let mut inspector = Inspector::new();
+    inspector.cursor = 0x300;
+    inspector.observe(processor.ram());
+    assert_eq!(
+        inspector.handle(InspectorKey::Digit(0xA), &mut processor, false),
+        Some("Pause to edit".to_string())
+    );
+
+    paused_edit(&mut inspector, &mut processor, &[0xA]);
+    assert_eq!(inspector.entry(), Some("A".to_string()));
+    paused_edit(&mut inspector, &mut processor, &[0x5]);
+    assert_eq!(inspector.entry(), None);
+    assert_eq!(processor.ram()[0x300], 0xA5);
+    assert_eq!(inspector.cursor, 0x301);
+
+    // Our own edits don't count as the program writing.
+    inspector.observe(processor.ram());
+    assert_eq!(inspector.highlight(0x300, &processor), Highlight::None);
+}
+#[test]
+fn test_cancel_edit() {
+    let mut processor = Processor::new();
+    let mut inspector = Inspector::new();
+    paused_edit(&mut inspector, &mut processor, &[0x1]);
+    inspector.handle(InspectorKey::Cancel, &mut processor, true);
+    assert_eq!(inspector.entry(), None);
+    paused_edit(&mut inspector, &mut processor, &[0x1]);
+    inspector.handle(InspectorKey::Down, &mut processor, true);
+    assert_eq!(inspector.entry(), None);
+    assert_eq!(processor.ram()[0x200], 0);
+}
+#[test]
+fn test_edit_registers() {
+    let mut processor = Processor::new();
+    let mut inspector = Inspector::new();
+    inspector.handle(InspectorKey::SwitchFocus, &mut processor, true);
+    assert_eq!(inspector.focus, Focus::Registers);
+    inspector.handle(InspectorKey::Down, &mut processor, true);
+    paused_edit(&mut inspector, &mut processor, &[0x4, 0x2]);
+    assert_eq!(processor.registers().v[1], 0x42);
+
+    for _ in 0..15 {
+        inspector.handle(InspectorKey::Down, &mut processor, true);
+    }
+    assert_eq!(register_name(inspector.register), "I");
+    paused_edit(&mut inspector, &mut processor, &[0x3, 0x0, 0x0]);
+    assert_eq!(processor.registers().i, 0x300);
+
+    inspector.handle(InspectorKey::Down, &mut processor, true);
+    paused_edit(&mut inspector, &mut processor, &[0x2, 0x4, 0x6]);
+    assert_eq!(processor.pc(), 0x246);
+    assert_eq!(register(&processor, 17), 0x246);
+
+    for _ in 0..10 {
+        inspector.handle(InspectorKey::Down, &mut processor, true);
+    }
+    assert_eq!(register_name(inspector.register), "ST");
+}
+#[test]
+fn test_written_bytes_fade() {
+    let mut processor = Processor::new();
+    let mut inspector = Inspector::new();
+    inspector.observe(processor.ram());
+    processor.ram_mut()[0x300] = 1;
+    inspector.observe(processor.ram());
+    assert_eq!(inspector.highlight(0x300, &processor), Highlight::Written(WRITE_FADE));
+    for _ in 0..WRITE_FADE {
+        inspector.observe(processor.ram());
+    }
+    assert_eq!(inspector.highlight(0x300, &processor), Highlight::None);
+}
+#[test]
+fn test_highlights() {
+    let mut processor = Processor::new();
+    // CALL 0x300, then LD I, 0x208 there.
+    processor.load(&[0x23, 0x00]);
+    processor.ram_mut()[0x300] = 0xA2;
+    processor.ram_mut()[0x301] = 0x08;
+    processor.tick([false; 16]);
+    processor.tick([false; 16]);
+
+    let mut inspector = Inspector::new();
+    assert_eq!(inspector.highlight(0x200, &processor), Highlight::Cursor);
+    assert_eq!(inspector.highlight(0x202, &processor), Highlight::Stack);
+    assert_eq!(inspector.highlight(0x203, &processor), Highlight::Stack);
+    assert_eq!(inspector.highlight(0x208, &processor), Highlight::I);
+    assert_eq!(inspector.highlight(0x302, &processor), Highlight::Pc);
+    assert_eq!(inspector.highlight(0x303, &processor), Highlight::Pc);
+    inspector.focus = Focus::Registers;
+    assert_eq!(inspector.highlight(0x200, &processor), Highlight::None);
+}
+#[test]
+fn test_sprite_height() {
+    let mut processor = Processor::new();
+    let mut inspector = Inspector::new();
+    inspector.handle(InspectorKey::SpriteTaller, &mut processor, false);
+    assert_eq!(inspector.sprite_height, 15);
+    for _ in 0..20 {
+        inspector.handle(InspectorKey::SpriteShorter, &mut processor, false);
+    }
+    assert_eq!(inspector.sprite_height, 1);
+}
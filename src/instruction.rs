@@ -0,0 +1,104 @@
+// A decoded opcode, with its operands pulled out so the processor doesn't
+// have to pick the opcode apart every time it runs it. x and y are
+// register numbers, kk a byte, n a nibble and nnn an address.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    Cls,
+    Ret,
+    Jp(u16),
+    Call(u16),
+    SeByte(u8, u8),
+    SneByte(u8, u8),
+    SeReg(u8, u8),
+    LdByte(u8, u8),
+    AddByte(u8, u8),
+    LdReg(u8, u8),
+    Or(u8, u8),
+    And(u8, u8),
+    Xor(u8, u8),
+    AddReg(u8, u8),
+    Sub(u8, u8),
+    Shr(u8, u8),
+    Subn(u8, u8),
+    Shl(u8, u8),
+    SneReg(u8, u8),
+    LdI(u16),
+    JpV0(u8, u16),
+    Rnd(u8, u8),
+    Drw(u8, u8, u8),
+    Skp(u8),
+    Sknp(u8),
+    LdFromDelay(u8),
+    LdKey(u8),
+    LdDelay(u8),
+    LdSound(u8),
+    AddI(u8),
+    LdFont(u8),
+    LdBcd(u8),
+    Store(u8),
+    Load(u8),
+    StoreFlags(u8),
+    LoadFlags(u8),
+    // Anything we don't recognise, which runs as a no-op.
+    Unknown,
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Instruction {
+        let nibbles = (
+            (opcode & 0xF000) >> 12,
+            (opcode & 0x0F00) >> 8,
+            (opcode & 0x00F0) >> 4,
+            opcode & 0x000F,
+        );
+        let nnn = opcode & 0x0FFF;
+        let kk = (opcode & 0x00FF) as u8;
+        let x = nibbles.1 as u8;
+        let y = nibbles.2 as u8;
+        let n = nibbles.3 as u8;
+
+        match nibbles {
+            (0x00, 0x00, 0x0e, 0x00) => Instruction::Cls,
+            (0x00, 0x00, 0x0e, 0x0e) => Instruction::Ret,
+            (0x01, _, _, _) => Instruction::Jp(nnn),
+            (0x02, _, _, _) => Instruction::Call(nnn),
+            (0x03, _, _, _) => Instruction::SeByte(x, kk),
+            (0x04, _, _, _) => Instruction::SneByte(x, kk),
+            (0x05, _, _, 0x00) => Instruction::SeReg(x, y),
+            (0x06, _, _, _) => Instruction::LdByte(x, kk),
+            (0x07, _, _, _) => Instruction::AddByte(x, kk),
+            (0x08, _, _, 0x00) => Instruction::LdReg(x, y),
+            (0x08, _, _, 0x01) => Instruction::Or(x, y),
+            (0x08, _, _, 0x02) => Instruction::And(x, y),
+            (0x08, _, _, 0x03) => Instruction::Xor(x, y),
+            (0x08, _, _, 0x04) => Instruction::AddReg(x, y),
+            (0x08, _, _, 0x05) => Instruction::Sub(x, y),
+            (0x08, _, _, 0x06) => Instruction::Shr(x, y),
+            (0x08, _, _, 0x07) => Instruction::Subn(x, y),
+            (0x08, _, _, 0x0e) => Instruction::Shl(x, y),
+            (0x09, _, _, 0x00) => Instruction::SneReg(x, y),
+            (0x0a, _, _, _) => Instruction::LdI(nnn),
+            (0x0b, _, _, _) => Instruction::JpV0(x, nnn),
+            (0x0c, _, _, _) => Instruction::Rnd(x, kk),
+            (0x0d, _, _, _) => Instruction::Drw(x, y, n),
+            (0x0e, _, 0x09, 0x0e) => Instruction::Skp(x),
+            (0x0e, _, 0x0a, 0x01) => Instruction::Sknp(x),
+            (0x0f, _, 0x00, 0x07) => Instruction::LdFromDelay(x),
+            (0x0f, _, 0x00, 0x0a) => Instruction::LdKey(x),
+            (0x0f, _, 0x01, 0x05) => Instruction::LdDelay(x),
+            (0x0f, _, 0x01, 0x08) => Instruction::LdSound(x),
+            (0x0f, _, 0x01, 0x0e) => Instruction::AddI(x),
+            (0x0f, _, 0x02, 0x09) => Instruction::LdFont(x),
+            (0x0f, _, 0x03, 0x03) => Instruction::LdBcd(x),
+            (0x0f, _, 0x05, 0x05) => Instruction::Store(x),
+            (0x0f, _, 0x06, 0x05) => Instruction::Load(x),
+            (0x0f, _, 0x07, 0x05) => Instruction::StoreFlags(x),
+            (0x0f, _, 0x08, 0x05) => Instruction::LoadFlags(x),
+            _ => Instruction::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./instruction_test.rs"]
+mod instruction_test;
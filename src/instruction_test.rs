@@ -0,0 +1,20 @@
+use super::*;
+#[test]
+fn test_decode_operands() {
+    assert_eq!(Instruction::decode(0x00E0), Instruction::Cls);
+    assert_eq!(Instruction::decode(0x00EE), Instruction::Ret);
+    assert_eq!(Instruction::decode(0x1ABC), Instruction::Jp(0xABC));
+    assert_eq!(Instruction::decode(0x3A42), Instruction::SeByte(0xA, 0x42));
+    assert_eq!(Instruction::decode(0x8AB4), Instruction::AddReg(0xA, 0xB));
+    assert_eq!(Instruction::decode(0xB123), Instruction::JpV0(0x1, 0x123));
+    assert_eq!(Instruction::decode(0xD12F), Instruction::Drw(0x1, 0x2, 0xF));
+    assert_eq!(Instruction::decode(0xF755), Instruction::Store(0x7));
+}
+#[test]
+fn test_decode_unknown() {
+    assert_eq!(Instruction::decode(0x0123), Instruction::Unknown);
+    assert_eq!(Instruction::decode(0x5121), Instruction::Unknown);
+    assert_eq!(Instruction::decode(0x800F), Instruction::Unknown);
+    assert_eq!(Instruction::decode(0xE1FF), Instruction::Unknown);
+    assert_eq!(Instruction::decode(0xF1FF), Instruction::Unknown);
+}
@@ -0,0 +1,52 @@
+// The processor core, and everything it needs, builds without std for
+// microcontrollers. It doesn't need a heap either. The rest of the library
+// is for desktop hosts.
+#![cfg_attr(not(feature = "std"), no_std)]
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(all(feature = "std", any(test, not(target_arch = "wasm32"))))]
+extern crate rand;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate serde_json;
+pub mod processor;
+pub mod instruction;
+pub mod machine;
+pub mod random;
+pub mod trace;
+mod font;
+#[cfg(feature = "std")]
+pub mod recompiler;
+#[cfg(feature = "std")]
+pub mod selftest;
+#[cfg(feature = "std")]
+pub mod disassembler;
+#[cfg(feature = "std")]
+pub mod profiler;
+#[cfg(feature = "std")]
+pub mod debugger;
+#[cfg(feature = "std")]
+pub mod gdb;
+#[cfg(feature = "std")]
+pub mod line_map;
+#[cfg(feature = "std")]
+pub mod dap;
+#[cfg(feature = "std")]
+pub mod env;
+#[cfg(feature = "std")]
+pub mod cheats;
+#[cfg(feature = "std")]
+pub mod patch;
+#[cfg(feature = "std")]
+pub mod netplay;
+#[cfg(feature = "std")]
+pub mod remote;
+
+pub const CHIP8_WIDTH: usize = 64;
+pub const CHIP8_HEIGHT: usize = 32;
+pub const CHIP8_RAM: usize = 4096;
+// Tallest display we support (the 64x64 hires and two-page modes).
+pub const VRAM_HEIGHT: usize = 64;
+// The display refreshes at 60Hz. At normal speed we run this many cycles
+// each frame, which is roughly 500 instructions per second.
+pub const CYCLES_PER_FRAME: u32 = 8;
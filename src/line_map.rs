@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Ties ROM addresses back to assembler source lines and labels, so a
+// debugger can show where it is and set breakpoints by line. Line maps are
+// text files with one entry per line:
+//
+//   line 0x200 game.asm 12
+//   symbol 0x256 draw_digit
+//
+// A line entry says the instruction at the address was assembled from that
+// line of that file. A symbol entry names the address. Source paths are
+// relative to the map. Blank lines and lines starting with # are ignored.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineMap {
+    base: PathBuf,
+    lines: Vec<(usize, String, usize)>,
+    symbols: Vec<(usize, String)>,
+}
+
+impl LineMap {
+    pub fn parse(text: &str) -> Result<LineMap, String> {
+        let mut map = LineMap::default();
+        for (index, line) in text.lines().enumerate() {
+            let error = || format!("line {}: expected `line ADDR FILE LINE` or `symbol ADDR NAME`", index + 1);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [] => {}
+                [first, ..] if first.starts_with('#') => {}
+                ["line", addr, file, line] => {
+                    let addr = parse_address(addr).ok_or_else(error)?;
+                    let line = line.parse().map_err(|_| error())?;
+                    map.lines.push((addr, file.to_string(), line));
+                }
+                ["symbol", addr, name] => {
+                    let addr = parse_address(addr).ok_or_else(error)?;
+                    map.symbols.push((addr, name.to_string()));
+                }
+                _ => return Err(error()),
+            }
+        }
+        map.symbols.sort();
+        Ok(map)
+    }
+
+    pub fn load(path: &Path) -> Result<LineMap, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut map = LineMap::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        map.base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(map)
+    }
+
+    // Where a source file named in the map lives.
+    pub fn source_path(&self, file: &str) -> PathBuf {
+        self.base.join(file)
+    }
+
+    // The address of the first instruction on the line, or on the next line
+    // with code if that one has none. Returns the line actually used too.
+    // Editors send full paths, so any path ending in a file from the map
+    // matches it.
+    pub fn address(&self, path: &str, line: usize) -> Option<(usize, usize)> {
+        self.lines
+            .iter()
+            .filter(|&&(_, ref file, source_line)| {
+                source_line >= line && Path::new(path).ends_with(file)
+            })
+            .min_by_key(|&&(addr, _, source_line)| (source_line, addr))
+            .map(|&(addr, _, source_line)| (addr, source_line))
+    }
+
+    pub fn location(&self, addr: usize) -> Option<(&str, usize)> {
+        self.lines
+            .iter()
+            .find(|&&(line_addr, _, _)| line_addr == addr)
+            .map(|&(_, ref file, line)| (file.as_str(), line))
+    }
+
+    // The nearest symbol at or before the address.
+    pub fn symbol(&self, addr: usize) -> Option<&str> {
+        self.symbols
+            .iter()
+            .rev()
+            .find(|&&(symbol_addr, _)| symbol_addr <= addr)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+pub fn parse_address(text: &str) -> Option<usize> {
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    usize::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+#[path = "./line_map_test.rs"]
+mod line_map_test;
@@ -0,0 +1,35 @@
+use super::*;
+const MAP: &str = "# digits.asm
+line 0x200 digits.asm 4
+line 0x202 digits.asm 5
+line 0x204 digits.asm 7
+line 0x206 lib/draw.asm 2
+symbol 0x200 start
+symbol 0x206 draw
+";
+#[test]
+fn test_address() {
+    let map = LineMap::parse(MAP).unwrap();
+    assert_eq!(map.address("/home/me/game/digits.asm", 5), Some((0x202, 5)));
+    assert_eq!(map.address("/home/me/game/digits.asm", 6), Some((0x204, 7)));
+    assert_eq!(map.address("/home/me/game/digits.asm", 8), None);
+    assert_eq!(map.address("/home/me/game/lib/draw.asm", 1), Some((0x206, 2)));
+    assert_eq!(map.address("/home/me/game/draw.asm", 1), None);
+}
+#[test]
+fn test_location_and_symbol() {
+    let map = LineMap::parse(MAP).unwrap();
+    assert_eq!(map.location(0x204), Some(("digits.asm", 7)));
+    assert_eq!(map.location(0x208), None);
+    assert_eq!(map.symbol(0x204), Some("start"));
+    assert_eq!(map.symbol(0x300), Some("draw"));
+    assert_eq!(map.symbol(0x100), None);
+}
+#[test]
+fn test_parse_errors() {
+    assert_eq!(
+        LineMap::parse("line 0x200 digits.asm\n"),
+        Err("line 1: expected `line ADDR FILE LINE` or `symbol ADDR NAME`".to_string())
+    );
+    assert!(LineMap::parse("symbol zz start\n").is_err());
+}
@@ -0,0 +1,199 @@
+use CHIP8_HEIGHT;
+use CHIP8_RAM;
+use VRAM_HEIGHT;
+
+// Behaviours that differ between CHIP-8 interpreters. Games are written
+// against one of them, so the wrong set of quirks can break a game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    pub name: &'static str,
+    // 8XY1, 8XY2 and 8XY3 set VF to 0.
+    pub vf_reset: bool,
+    // FX55 and FX65 leave I pointing just past the last register.
+    pub memory_increment: bool,
+    // 8XY6 and 8XYE shift VX in place instead of shifting VY into VX.
+    pub shifting: bool,
+    // BNNN jumps to NNN plus VX (where X is the first digit of NNN)
+    // instead of NNN plus V0.
+    pub jumping: bool,
+    // DXYN clips sprites at the edges of the screen instead of wrapping
+    // them around. The starting position wraps either way.
+    pub clipping: bool,
+    // DXYN waits for the next vertical blank before carrying on, so at most
+    // one sprite is drawn per frame.
+    pub display_wait: bool,
+    // On a tall display DXYN sets VF to the number of sprite rows that
+    // collided (or were clipped off the bottom) instead of just 1.
+    pub row_collisions: bool,
+}
+
+impl Quirks {
+    // The original COSMAC VIP interpreter.
+    pub fn vip() -> Self {
+        Quirks {
+            name: "vip",
+            vf_reset: true,
+            memory_increment: true,
+            shifting: false,
+            jumping: false,
+            clipping: true,
+            display_wait: true,
+            row_collisions: false,
+        }
+    }
+
+    // SUPER-CHIP 1.1 on the HP-48.
+    pub fn schip() -> Self {
+        Quirks {
+            name: "schip",
+            vf_reset: false,
+            memory_increment: false,
+            shifting: true,
+            jumping: true,
+            clipping: true,
+            display_wait: false,
+            row_collisions: true,
+        }
+    }
+
+    pub fn xochip() -> Self {
+        Quirks {
+            name: "xochip",
+            vf_reset: false,
+            memory_increment: true,
+            shifting: false,
+            jumping: false,
+            clipping: false,
+            display_wait: false,
+            row_collisions: false,
+        }
+    }
+
+    // What this emulator has always done, and still does by default.
+    pub fn legacy() -> Self {
+        Quirks {
+            name: "legacy",
+            vf_reset: false,
+            memory_increment: false,
+            shifting: true,
+            jumping: false,
+            clipping: false,
+            display_wait: false,
+            row_collisions: false,
+        }
+    }
+
+    pub fn all() -> [Self; 4] {
+        [Quirks::legacy(), Quirks::vip(), Quirks::schip(), Quirks::xochip()]
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Quirks::all().iter().find(|quirks| quirks.name == name).cloned()
+    }
+}
+
+// A range of memory that belongs to the interpreter rather than the
+// program. Cartridges are never loaded into a reserved region.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Region {
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.start && addr < self.end
+    }
+}
+
+// Describes the memory layout and display of the machine being emulated.
+// Different CHIP-8 hosts put programs (and the interpreter itself) in
+// different places, so the processor reads all of that from here instead
+// of hard-coding the COSMAC VIP layout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MachineConfig {
+    pub name: &'static str,
+    pub ram_size: usize,
+    // Where the cartridge is copied to.
+    pub load_address: usize,
+    // Where the program counter starts. This is usually the same as the
+    // load address, but hires ROMs ship with a patch for the original
+    // interpreter in front of the program which we don't need to run.
+    pub entry_point: usize,
+    pub font_base: usize,
+    pub reserved: &'static [Region],
+    pub display_height: usize,
+    // SCHIP has 8 persistent flag registers, XO-CHIP has 16.
+    pub flag_registers: usize,
+    pub quirks: Quirks,
+}
+
+impl MachineConfig {
+    // The COSMAC VIP: 4K of RAM, programs start at 0x200.
+    pub fn chip8() -> Self {
+        MachineConfig {
+            name: "chip8",
+            ram_size: CHIP8_RAM,
+            load_address: 0x200,
+            entry_point: 0x200,
+            font_base: 0,
+            reserved: &[Region { start: 0x000, end: 0x200 }],
+            display_height: CHIP8_HEIGHT,
+            flag_registers: 8,
+            quirks: Quirks::legacy(),
+        }
+    }
+
+    // The ETI-660 keeps its interpreter in the first 0x600 bytes.
+    pub fn eti660() -> Self {
+        MachineConfig {
+            name: "eti660",
+            load_address: 0x600,
+            entry_point: 0x600,
+            reserved: &[Region { start: 0x000, end: 0x600 }],
+            ..MachineConfig::chip8()
+        }
+    }
+
+    // "CHIP-8 hires" ROMs are 64x64. They start with a jump to a patch for
+    // the VIP interpreter at 0x260, and the program itself begins at 0x2C0.
+    // We support the tall display natively, so skip straight to the program.
+    pub fn hires() -> Self {
+        MachineConfig {
+            name: "hires",
+            entry_point: 0x2C0,
+            display_height: VRAM_HEIGHT,
+            ..MachineConfig::chip8()
+        }
+    }
+
+    // The two-page display mode: a 64x64 screen for ordinary ROMs that
+    // start at 0x200.
+    pub fn two_page() -> Self {
+        MachineConfig {
+            name: "two-page",
+            display_height: VRAM_HEIGHT,
+            ..MachineConfig::chip8()
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "chip8" => Some(MachineConfig::chip8()),
+            "eti660" => Some(MachineConfig::eti660()),
+            "hires" => Some(MachineConfig::hires()),
+            "two-page" => Some(MachineConfig::two_page()),
+            _ => None,
+        }
+    }
+
+    pub fn is_reserved(&self, addr: usize) -> bool {
+        self.reserved.iter().any(|region| region.contains(addr))
+    }
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        MachineConfig::chip8()
+    }
+}
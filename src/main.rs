@@ -1,52 +1,631 @@
-extern crate rand;
+extern crate chip8;
 extern crate sdl2;
+extern crate rand;
 mod drivers;
-mod processor;
-mod font;
+mod options;
+mod controls;
+mod overlay;
+mod tone;
+mod wav;
+mod headless;
+mod gdb_server;
+mod inspector;
+mod cheat_menu;
+mod cheat_prompt;
+mod netplay_connection;
+mod remote_frontend;
 
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::env;
+use std::process;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
 
-use drivers::{DisplayDriver, AudioDriver, InputDriver, CartridgeDriver};
-use processor::Processor;
-
-const CHIP8_WIDTH: usize = 64;
-const CHIP8_HEIGHT: usize = 32;
-const CHIP8_RAM: usize = 4096;
+use chip8::processor::Processor;
+use chip8::machine::Quirks;
+use chip8::selftest;
+use chip8::patch;
+use chip8::netplay;
+use chip8::remote::Server;
+use chip8::cheats::{CheatList, Cheats, Command};
+use chip8::dap;
+use chip8::profiler::Profiler;
+use chip8::trace::{self, Comparison, TraceRecord};
+use chip8::CYCLES_PER_FRAME;
+use drivers::{DisplayDriver, AudioDriver, InputDriver, CartridgeDriver, StorageDriver, InspectorDriver, Hotkey, Osd};
+use options::{Options, USAGE};
+use controls::Controls;
+use overlay::Overlay;
+use inspector::Inspector;
+use cheat_menu::CheatMenu;
+use cheat_prompt::CheatPrompt;
+use remote_frontend::Emulator;
+use wav::AudioRecorder;
 
 
 
 fn main() {
-    let sleep_duration = Duration::from_millis(2);
+    let frame_duration = Duration::new(0, 1_000_000_000 / 60);
 
-    let sdl_context = sdl2::init().unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("selftest") {
+        process::exit(if run_selftest() { 0 } else { 1 });
+    }
+    if args.first().map(String::as_str) == Some("trace-diff") {
+        process::exit(if run_trace_diff(&args[1..]) { 0 } else { 1 });
+    }
+    if args.first().map(String::as_str) == Some("make-patch") {
+        process::exit(if run_make_patch(&args[1..]) { 0 } else { 1 });
+    }
+    if args.first().map(String::as_str) == Some("dap") {
+        process::exit(if run_dap(&args[1..], frame_duration) { 0 } else { 1 });
+    }
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(1);
+        }
+    };
+
+    if options.headless {
+        if let Err(e) = headless::run(&options) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
 
-    let args: Vec<String> = env::args().collect();
-    let cartridge_filename = &args[1];
+    let sdl_context = sdl2::init().unwrap();
 
-    let cartridge_driver = CartridgeDriver::new(cartridge_filename);
-    let audio_driver = AudioDriver::new(&sdl_context);
-    let mut display_driver = DisplayDriver::new(&sdl_context);
+    let patch = options.patch.as_deref();
+    let mut cartridge_driver = match CartridgeDriver::new(&options.cartridge_filename, patch) {
+        Ok(cartridge_driver) => cartridge_driver,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    let audio_driver = AudioDriver::new(&sdl_context, options.tone);
+    let mut display_driver = DisplayDriver::new(&sdl_context, options.config.display_height);
     let mut input_driver = InputDriver::new(&sdl_context);
-    let mut processor = Processor::new();
+    let mut storage_driver = StorageDriver::new(cartridge_driver.hash());
 
-    processor.load(&cartridge_driver.rom);
+    // Both players have to start from the same machine, so netplay leaves
+    // saved flags and cheats alone.
+    let mut netplay = match netplay_connection::connect(&options, cartridge_driver.hash()) {
+        Ok(netplay) => netplay,
+        Err(e) => {
+            eprintln!("Couldn't start netplay: {}", e);
+            process::exit(1);
+        }
+    };
+    let storage = if netplay.is_some() { None } else { Some(&storage_driver) };
+    let mut processor = boot(&options, &cartridge_driver, storage);
+    if let Some(ref session) = netplay {
+        processor.seed_rng(session.seed());
+    }
+    if let Some(port) = options.gdb {
+        // The window only shows the display while the program runs.
+        let mut next_frame = Instant::now();
+        let result = gdb_server::serve(port, processor, |processor| {
+            let input = input_driver.poll().ok()?;
+            display_driver.draw(processor.vram(), &Osd::default());
+            next_frame += frame_duration;
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                next_frame = now;
+            }
+            Some(input.keypad)
+        });
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    let mut controls = Controls::new(
+        options.fast_forward_speed,
+        options.turbo_speed,
+        options.slow_motion_speed,
+    );
+    let mut next_frame = Instant::now();
+    let profile = format!("{} {}", options.config.name, options.config.quirks.name);
+    let mut overlay = Overlay::new(&profile, next_frame);
+    let mut last_osd = None;
+    let mut recorder = options.audio_out.as_ref().map(|path| {
+        AudioRecorder::create(path, options.sample_rate, options.tone, CYCLES_PER_FRAME * 60)
+            .expect("couldn't create audio output file")
+    });
+    let mut trace = options.trace.as_ref().map(|path| {
+        BufWriter::new(File::create(path).expect("couldn't create trace file"))
+    });
+    let mut profiler = options.profile.as_ref().map(|_| {
+        Profiler::new(options.config.load_address, cartridge_driver.size)
+    });
+    let tracing = trace.is_some() || profiler.is_some();
+    processor.set_tracing(tracing);
+    let mut inspector = Inspector::new();
+    // The inspector window opens the first time it's asked for.
+    let mut inspector_driver: Option<InspectorDriver> = None;
+    if let Some(ref path) = cartridge_driver.patch {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        overlay.message(&format!("Patched with {}", name), next_frame);
+    }
+    let mut cheats = Cheats::new();
+    // Cheats saved as the default list come back the next time the ROM
+    // runs, the way flags do.
+    let saved_cheats = storage.and_then(|storage_driver| storage_driver.load_cheats("default").ok());
+    if let Some(text) = saved_cheats {
+        match CheatList::parse(&text) {
+            Ok(list) => {
+                overlay.message(&format!("Loaded {} cheats", list.cheats.len()), next_frame);
+                cheats.list = list;
+            }
+            Err(e) => println!("Couldn't load cheats: {}", e),
+        }
+    }
+    let mut cheat_menu = CheatMenu::new();
+    let cheat_prompt = if options.cheat_prompt { Some(CheatPrompt::new()) } else { None };
+    let mut remote = match options.remote.as_ref().map(|address| Server::bind(address)) {
+        Some(Ok(server)) => {
+            println!("Remote control on {}", server.address());
+            Some(server)
+        }
+        Some(Err(e)) => {
+            eprintln!("Couldn't start remote control: {}", e);
+            process::exit(1);
+        }
+        None => None,
+    };
+    let mut frames = 0;
+
+    while let Ok(input) = input_driver.poll() {
+        let now = Instant::now();
+        for hotkey in &input.hotkeys {
+            // Both players' games have to run at the same speed.
+            if netplay.is_some() && changes_speed(hotkey) {
+                overlay.message("Not during netplay", now);
+                continue;
+            }
+            controls.handle(hotkey);
+            match *hotkey {
+                Hotkey::ToggleStats => overlay.show_stats = !overlay.show_stats,
+                Hotkey::ToggleProfile => overlay.show_profile = !overlay.show_profile,
+                Hotkey::ToggleMessages => overlay.show_messages = !overlay.show_messages,
+                Hotkey::ToggleInspector => {
+                    inspector.visible = !inspector.visible;
+                    let driver = inspector_driver.get_or_insert_with(|| {
+                        InspectorDriver::new(&sdl_context, options.config.font_base)
+                    });
+                    input_driver.set_inspector_window(driver.window_id());
+                    if inspector.visible {
+                        driver.show();
+                    } else {
+                        driver.hide();
+                    }
+                }
+                Hotkey::Mute => {
+                    let muted = audio_driver.toggle_mute();
+                    overlay.message(if muted { "Muted" } else { "Unmuted" }, now);
+                }
+                _ => {}
+            }
+            if let Some(message) = hotkey_message(hotkey, &controls) {
+                overlay.message(&message, now);
+            }
+            if let Some(message) = cheat_menu.handle(hotkey, &mut cheats.list) {
+                overlay.message(&message, now);
+            }
+        }
+        controls.fast_forward = input.fast_forward && netplay.is_none();
+        for &key in &input.inspector_keys {
+            if let Some(message) = inspector.handle(key, &mut processor, controls.paused) {
+                overlay.message(&message, now);
+            }
+        }
+
+        if let Some(ref mut server) = remote {
+            let hash = cartridge_driver.hash();
+            server.poll(&mut Emulator {
+                options: &options,
+                processor: &mut processor,
+                paused: &mut controls.paused,
+                cartridge_driver: &mut cartridge_driver,
+                storage_driver: Some(&mut storage_driver),
+                tracing,
+            });
+            // Cheats are for the ROM they were made for.
+            if cartridge_driver.hash() != hash {
+                cheats = Cheats::new();
+                overlay.message("Loaded a new ROM", now);
+            }
+        }
+        let storage = if netplay.is_some() { None } else { Some(&storage_driver) };
 
-    while let Ok(keypad) = input_driver.poll() {
+        let mut vram_changed = false;
+        if controls.take_reset() {
+            processor = boot(&options, &cartridge_driver, storage);
+            processor.set_tracing(tracing);
+            if let Some(ref mut profiler) = profiler {
+                profiler.restart();
+            }
+            vram_changed = true;
+        }
+
+        if let Some(ref prompt) = cheat_prompt {
+            for line in prompt.poll() {
+                if line.trim().is_empty() {
+                    prompt.reply("");
+                    continue;
+                }
+                match run_cheat_command(&line, &mut cheats, &mut processor, &storage_driver) {
+                    Ok(reply) => prompt.reply(&reply),
+                    Err(e) => prompt.reply(&format!("error: {}", e)),
+                }
+            }
+        }
+        cheats.list.apply(&mut processor);
 
-        let output = processor.tick(keypad);
+        let keypad = match netplay.as_mut().map(|session| session.exchange(input.keypad)) {
+            Some(Ok(keypad)) => keypad,
+            Some(Err(e)) => {
+                eprintln!("Netplay: {}", e);
+                break;
+            }
+            None => input.keypad,
+        };
+        let mut beep = false;
+        let mut flags_changed = false;
+        let keypad = match remote {
+            Some(ref server) => server.remote.keypad(keypad),
+            None => keypad,
+        };
+        let cycles = controls.cycles_this_frame(CYCLES_PER_FRAME);
+        let mut ran = 0;
+        for _ in 0..cycles {
+            if let Some(ref mut server) = remote {
+                if server.remote.at_breakpoint(&processor) {
+                    controls.paused = true;
+                    overlay.message(&format!("Breakpoint at {:03X}", processor.pc()), now);
+                    break;
+                }
+            }
+            ran += 1;
+            let output = processor.tick(keypad);
+            if let Some(ref mut recorder) = recorder {
+                recorder.cycle(output.beep).expect("couldn't write audio output file");
+            }
+            if let Some(entry) = output.trace {
+                if let Some(ref mut trace) = trace {
+                    writeln!(trace, "{}", entry).expect("couldn't write trace file");
+                }
+                if let Some(ref mut profiler) = profiler {
+                    profiler.record(&entry);
+                }
+            }
+            beep = output.beep;
+            vram_changed |= output.vram_changed;
+            flags_changed |= output.flags_changed;
+        }
+
+        if let Some(ref mut session) = netplay {
+            if let Err(e) = session.frame_done(netplay::state_hash(&processor)) {
+                eprintln!("Netplay: {}", e);
+                break;
+            }
+        }
+        if let Some(ref mut server) = remote {
+            server.remote.frame_done(ran, beep);
+        }
+
+        if let (true, Some(storage_driver)) = (flags_changed, storage) {
+            match storage_driver.save_flags(processor.flags()) {
+                Ok(()) => overlay.message("Flags saved", now),
+                Err(e) => println!("Couldn't save flags: {}", e),
+            }
+        }
 
-        if output.vram_changed {
-            display_driver.draw(output.vram);
+        inspector.observe(processor.ram());
+        if let (true, Some(ref mut driver)) = (inspector.visible, inspector_driver.as_mut()) {
+            driver.draw(&inspector, &processor);
         }
 
-        if output.beep {
+        overlay.frame(cycles, now);
+        let mut osd = overlay.osd(now);
+        osd.indicator = controls.indicator();
+        osd.status.extend(cheat_menu.lines(&cheats.list));
+        if vram_changed || last_osd.as_ref() != Some(&osd) {
+            display_driver.draw(processor.vram(), &osd);
+            last_osd = Some(osd);
+        }
+
+        if beep {
             audio_driver.start_beep();
         } else {
             audio_driver.stop_beep();
         }
 
-        thread::sleep(sleep_duration);
+        frames += 1;
+        if Some(frames) == options.frames {
+            break;
+        }
+
+        next_frame += frame_duration;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+    }
+
+    if let Some(session) = netplay {
+        let _ = session.close();
+    }
+    if let Some(recorder) = recorder {
+        recorder.finish().expect("couldn't write audio output file");
+    }
+    if let Some(mut trace) = trace {
+        trace.flush().expect("couldn't write trace file");
+    }
+    if let (Some(profiler), Some(path)) = (profiler, options.profile.as_ref()) {
+        fs::write(path, profiler.summary()).expect("couldn't write profile");
+    }
+}
+
+// Runs the built-in conformance tests against every quirk profile and
+// prints a table of the results. Returns true if everything passed.
+fn run_selftest() -> bool {
+    let profiles = Quirks::all();
+    let mut failures = Vec::new();
+
+    let mut header = format!("{:<22}", "");
+    for quirks in &profiles {
+        header += &format!("{:<8}", quirks.name);
+    }
+    println!("{}", header.trim_end());
+
+    for case in selftest::cases() {
+        let mut row = format!("{:<22}", case.name);
+        for &quirks in &profiles {
+            let outcome = selftest::run_case(&case, quirks);
+            row += &format!("{:<8}", if outcome.passed() { "ok" } else { "FAIL" });
+            if !outcome.passed() {
+                failures.push(format!(
+                    "{} with {} quirks: expected {}, got {}",
+                    case.name,
+                    quirks.name,
+                    outcome.expected,
+                    outcome.got
+                ));
+            }
+        }
+        println!("{}", row.trim_end());
+    }
+
+    println!();
+    if failures.is_empty() {
+        println!("All tests passed.");
+    } else {
+        for failure in &failures {
+            println!("{}", failure);
+        }
+    }
+    failures.is_empty()
+}
+
+// Compares two trace files and reports where they first disagree. Returns
+// true if they agree.
+fn run_trace_diff(args: &[String]) -> bool {
+    if args.len() != 2 {
+        eprintln!("{}", USAGE);
+        return false;
+    }
+    let read = |path: &String| {
+        fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| trace::parse(&text))
+            .map_err(|e| format!("{}: {}", path, e))
+    };
+    let (a, b) = match (read(&args[0]), read(&args[1])) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+
+    match trace::compare(&a, &b) {
+        Comparison::Match { compared, skipped_a, skipped_b } => {
+            println!("The traces agree for {} instructions.", compared);
+            for &(path, skipped, records) in &[(&args[0], skipped_a, &a), (&args[1], skipped_b, &b)] {
+                let extra = records.len() - skipped - compared;
+                if skipped > 0 {
+                    println!("Skipped {} instructions at the start of {}.", skipped, path);
+                }
+                if extra > 0 {
+                    println!("{} carries on for another {} instructions.", path, extra);
+                }
+            }
+            true
+        }
+        Comparison::Diverged(divergence) => {
+            println!("The traces diverge after {} instructions:", divergence.index);
+            for difference in &divergence.differences {
+                println!("  {}", difference);
+            }
+            for &(path, at, records) in &[(&args[0], divergence.a, &a), (&args[1], divergence.b, &b)] {
+                if let Some(at) = at {
+                    println!();
+                    println!("{}, line {}:", path, records[at].line_number);
+                    print_records(&records[at.saturating_sub(3)..=at]);
+                }
+            }
+            false
+        }
+    }
+}
+
+// Writes a patch that turns one ROM into another, as IPS or BPS depending
+// on the patch's extension. Returns true if it was written.
+fn run_make_patch(args: &[String]) -> bool {
+    let (original, modified, path) = match args {
+        [original, modified, path] => (original, modified, path),
+        _ => {
+            eprintln!("{}", USAGE);
+            return false;
+        }
+    };
+    let extension = Path::new(path).extension().and_then(|extension| extension.to_str());
+    let format = match extension.and_then(patch::Format::from_extension) {
+        Some(format) => format,
+        None => {
+            eprintln!("{}: patches must end in .ips or .bps", path);
+            return false;
+        }
+    };
+    let read = |path: &String| fs::read(path).map_err(|e| format!("{}: {}", path, e));
+    let (original, modified) = match (read(original), read(modified)) {
+        (Ok(original), Ok(modified)) => (original, modified),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    let patch = patch::make(&original, &modified, format);
+    if let Err(e) = fs::write(path, &patch) {
+        eprintln!("{}: {}", path, e);
+        return false;
+    }
+    println!("Wrote a {} byte patch to {}.", patch.len(), path);
+    true
+}
+
+// Serves the Debug Adapter Protocol on stdin and stdout, so nothing else
+// may print to stdout. The window opens once a program is launched, since
+// only then do we know how tall the display is. Returns true if the session
+// ended cleanly.
+fn run_dap(args: &[String], frame_duration: Duration) -> bool {
+    let headless = match args {
+        [] => false,
+        [flag] if flag == "--headless" => true,
+        _ => {
+            eprintln!("{}", USAGE);
+            return false;
+        }
+    };
+    let mut drivers = None;
+    let mut next_frame = Instant::now();
+    let result = dap::serve(BufReader::new(io::stdin()), io::stdout(), |processor| {
+        let keypad = if headless {
+            [false; 16]
+        } else {
+            let (_, display_driver, input_driver) = drivers.get_or_insert_with(|| {
+                let sdl_context = sdl2::init().unwrap();
+                let display_driver = DisplayDriver::new(&sdl_context, processor.vram().len());
+                let input_driver = InputDriver::new(&sdl_context);
+                (sdl_context, display_driver, input_driver)
+            });
+            let input = input_driver.poll().ok()?;
+            display_driver.draw(processor.vram(), &Osd::default());
+            input.keypad
+        };
+        next_frame += frame_duration;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+        Some(keypad)
+    });
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        return false;
+    }
+    true
+}
+
+fn print_records(records: &[TraceRecord]) {
+    for record in records {
+        println!("  {}", record.line);
+    }
+}
+
+// Runs a line typed at the cheat prompt. Cheat lists are saved per ROM.
+fn run_cheat_command(
+    line: &str,
+    cheats: &mut Cheats,
+    processor: &mut Processor,
+    storage_driver: &StorageDriver,
+) -> Result<String, String> {
+    match Command::parse(line)? {
+        Command::Save(name) => {
+            storage_driver
+                .save_cheats(&name, &cheats.list.to_string())
+                .map_err(|e| format!("couldn't save {}: {}", name, e))?;
+            Ok(format!("Saved {} cheats as {}", cheats.list.cheats.len(), name))
+        }
+        Command::Load(name) => {
+            let text = storage_driver
+                .load_cheats(&name)
+                .map_err(|e| format!("couldn't load {}: {}", name, e))?;
+            cheats.list = CheatList::parse(&text).map_err(|e| format!("{}: {}", name, e))?;
+            Ok(format!("Loaded {} cheats from {}", cheats.list.cheats.len(), name))
+        }
+        Command::Lists => {
+            let lists = storage_driver.cheat_lists();
+            if lists.is_empty() {
+                Ok("No saved lists".to_string())
+            } else {
+                Ok(lists.join("\n"))
+            }
+        }
+        command => cheats.run(command, processor),
+    }
+}
+
+// Whether a hotkey changes how fast the game runs, which netplay can't
+// allow.
+fn changes_speed(hotkey: &Hotkey) -> bool {
+    matches!(
+        *hotkey,
+        Hotkey::Pause | Hotkey::Reset | Hotkey::FrameAdvance | Hotkey::Turbo | Hotkey::SlowMotion
+    )
+}
+
+// The notification shown when a hotkey is pressed, if any.
+fn hotkey_message(hotkey: &Hotkey, controls: &Controls) -> Option<String> {
+    let on_off = |on| if on { "on" } else { "off" };
+    match *hotkey {
+        Hotkey::Pause => Some(if controls.paused { "Paused" } else { "Resumed" }.to_string()),
+        Hotkey::Reset => Some("Reset".to_string()),
+        Hotkey::Turbo => Some(format!("Turbo {}", on_off(controls.turbo))),
+        Hotkey::SlowMotion => Some(format!("Slow motion {}", on_off(controls.slow_motion))),
+        _ => None,
+    }
+}
+
+// A fresh processor with the cartridge loaded and any saved flags restored.
+// Used at startup and for soft resets.
+fn boot(
+    options: &Options,
+    cartridge_driver: &CartridgeDriver,
+    storage_driver: Option<&StorageDriver>,
+) -> Processor {
+    let mut processor = Processor::with_config(options.config.clone());
+    processor.set_backend(options.backend);
+    if let Some(seed) = options.seed {
+        processor.seed_rng(seed);
+    }
+    processor.load(&cartridge_driver.rom);
+    if let Some(flags) = storage_driver.and_then(StorageDriver::load_flags) {
+        processor.set_flags(&flags);
     }
+    processor
 }
@@ -0,0 +1,305 @@
+// Lockstep netplay: two emulators, each running the whole game, connected
+// over TCP. Games for two players share the one keypad, so each player
+// owns some of its keys, and every frame each side sends the other its
+// keys. A frame only runs once both sides' keys for it are in, so both
+// games see exactly the same input and stay in step.
+//
+// Keys pressed now are used a few frames later (the input delay), which
+// gives them time to cross the network before they're needed: with enough
+// delay, neither side ever waits. Every so often both sides also send a
+// hash of their whole machine, so if the games drift apart anyway (a
+// different ROM or quirks, say) it's caught instead of playing on.
+//
+// Every message is 13 bytes: a type, a frame number and a value.
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+
+use processor::Processor;
+
+const MAGIC: &[u8] = b"CH8N";
+const VERSION: u8 = 1;
+// How often, in frames, the two sides compare their machines.
+pub const HASH_INTERVAL: u32 = 60;
+// A second of delay is already unplayable.
+pub const MAX_INPUT_DELAY: u32 = 60;
+
+const INPUT: u8 = 1;
+const HASH: u8 = 2;
+
+// The keys each player gets unless told otherwise: the left half of the
+// keypad for the host and the right half for the other player. Pong, for
+// one, uses 1 and 4 for the left paddle and C and D for the right.
+pub const HOST_KEYS: u16 =
+    1 << 0x1 | 1 << 0x2 | 1 << 0x4 | 1 << 0x5 | 1 << 0x7 | 1 << 0x8 | 1 << 0xA | 1 << 0x0;
+pub const GUEST_KEYS: u16 = !HOST_KEYS;
+
+// What each side brings to the session.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    // Whether this side is the host, whose input delay and seed both sides
+    // use.
+    pub host: bool,
+    // The keys this side owns, a bit per key.
+    pub keys: u16,
+    pub input_delay: u32,
+    // Which ROM is running, so both sides are sure it's the same one.
+    pub rom_hash: u64,
+    // The seed for CXKK's random numbers, which have to match too.
+    pub seed: u64,
+}
+
+// Parses a list of hex keys, like "1,4" or "14" for keys 1 and 4, into a
+// bit per key.
+pub fn parse_keys(text: &str) -> Result<u16, String> {
+    let mut keys = 0;
+    for c in text.chars().filter(|&c| c != ',') {
+        let key = c.to_digit(16).ok_or_else(|| format!("not a key: {}", c))?;
+        keys |= 1 << key;
+    }
+    if keys == 0 {
+        return Err("no keys given".to_string());
+    }
+    Ok(keys)
+}
+
+fn to_bits(keypad: [bool; 16]) -> u16 {
+    keypad
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (key, &pressed)| if pressed { bits | 1 << key } else { bits })
+}
+
+fn from_bits(bits: u16) -> [bool; 16] {
+    let mut keypad = [false; 16];
+    for (key, pressed) in keypad.iter_mut().enumerate() {
+        *pressed = bits & 1 << key != 0;
+    }
+    keypad
+}
+
+// A hash of everything about the machine that affects how the game goes
+// on: the same 64-bit FNV-1a as ROM hashes, over a save state.
+pub fn state_hash(processor: &Processor) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in processor.save_state() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn big_endian(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |number, &byte| number << 8 | byte as u64)
+}
+
+// Makes a connection dropping say what happened.
+fn left(e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof
+        | io::ErrorKind::BrokenPipe
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted => io::Error::new(e.kind(), "the other side left"),
+        _ => e,
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct Session {
+    stream: TcpStream,
+    keys: u16,
+    remote_keys: u16,
+    input_delay: u32,
+    seed: u64,
+    // The frame about to run.
+    frame: u32,
+    // Keys for frames that haven't run yet.
+    local_inputs: HashMap<u32, u16>,
+    remote_inputs: HashMap<u32, u16>,
+    // Hashes waiting for the other side's to compare with.
+    local_hashes: HashMap<u32, u64>,
+    remote_hashes: HashMap<u32, u64>,
+}
+
+impl Session {
+    // Introduces both sides over a connected stream and agrees on how the
+    // session goes: same ROM, no key owned by both, and the host's input
+    // delay and seed.
+    pub fn new(mut stream: TcpStream, config: Config) -> io::Result<Self> {
+        // Messages are tiny, and every frame waits for one.
+        stream.set_nodelay(true)?;
+
+        let mut hello = MAGIC.to_vec();
+        hello.push(VERSION);
+        hello.push(config.host as u8);
+        hello.extend_from_slice(&config.keys.to_be_bytes());
+        hello.extend_from_slice(&config.input_delay.to_be_bytes());
+        hello.extend_from_slice(&config.rom_hash.to_be_bytes());
+        hello.extend_from_slice(&config.seed.to_be_bytes());
+        stream.write_all(&hello)?;
+
+        let mut reply = vec![0; hello.len()];
+        stream.read_exact(&mut reply)?;
+        if &reply[..4] != MAGIC {
+            return Err(invalid("the other side isn't a CHIP-8 netplay session".to_string()));
+        }
+        if reply[4] != VERSION {
+            let message = format!("the other side speaks netplay version {}, not {}", reply[4], VERSION);
+            return Err(invalid(message));
+        }
+        let host = reply[5] != 0;
+        let remote_keys = u16::from_be_bytes([reply[6], reply[7]]);
+        let input_delay = big_endian(&reply[8..12]) as u32;
+        let rom_hash = big_endian(&reply[12..20]);
+        let seed = big_endian(&reply[20..28]);
+
+        if host == config.host {
+            let role = if host { "host" } else { "join" };
+            return Err(invalid(format!("both sides want to {}", role)));
+        }
+        if rom_hash != config.rom_hash {
+            return Err(invalid("the other side is running a different ROM".to_string()));
+        }
+        let shared = config.keys & remote_keys;
+        if shared != 0 {
+            let keys: Vec<String> = (0..16)
+                .filter(|key| shared & 1 << key != 0)
+                .map(|key| format!("{:X}", key))
+                .collect();
+            return Err(invalid(format!("both sides own keys {}", keys.join(","))));
+        }
+
+        let (input_delay, seed) = if config.host {
+            (config.input_delay, config.seed)
+        } else {
+            (input_delay, seed)
+        };
+        if input_delay > MAX_INPUT_DELAY {
+            return Err(invalid(format!(
+                "an input delay of {} frames is over the limit of {}",
+                input_delay, MAX_INPUT_DELAY
+            )));
+        }
+        // Nobody has pressed anything yet for the frames before the first
+        // keys sent can arrive.
+        let inputs: HashMap<u32, u16> = (0..input_delay).map(|frame| (frame, 0)).collect();
+        Ok(Session {
+            stream,
+            keys: config.keys,
+            remote_keys,
+            input_delay,
+            seed,
+            frame: 0,
+            local_inputs: inputs.clone(),
+            remote_inputs: inputs,
+            local_hashes: HashMap::new(),
+            remote_hashes: HashMap::new(),
+        })
+    }
+
+    // The seed both sides use for random numbers.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn input_delay(&self) -> u32 {
+        self.input_delay
+    }
+
+    // The frame about to run, counting from 0.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    // Sends the keys held down now, to be used input_delay frames from
+    // now, and waits for the other side's keys for this frame. Returns the
+    // keypad to run this frame with. Keys this side doesn't own are
+    // ignored.
+    pub fn exchange(&mut self, keypad: [bool; 16]) -> io::Result<[bool; 16]> {
+        let later = self.frame + self.input_delay;
+        let keys = to_bits(keypad) & self.keys;
+        self.local_inputs.insert(later, keys);
+        self.send(INPUT, later, keys as u64)?;
+
+        while !self.remote_inputs.contains_key(&self.frame) {
+            self.receive()?;
+        }
+        let local = self.local_inputs.remove(&self.frame).unwrap_or(0);
+        let remote = self.remote_inputs.remove(&self.frame).unwrap_or(0) & self.remote_keys;
+        Ok(from_bits(local | remote))
+    }
+
+    // Call after running each frame with the machine's state_hash. Every
+    // HASH_INTERVAL frames it's sent to the other side to compare, and
+    // Err says the games have drifted apart.
+    pub fn frame_done(&mut self, hash: u64) -> io::Result<()> {
+        let frame = self.frame;
+        self.frame += 1;
+        if !frame.is_multiple_of(HASH_INTERVAL) {
+            return Ok(());
+        }
+        self.send(HASH, frame, hash)?;
+        self.local_hashes.insert(frame, hash);
+        self.compare_hashes(frame)
+    }
+
+    // Ends the session, after checking any hashes still on their way from
+    // the other side. Leaving without this looks to the other side like
+    // the connection dropped.
+    pub fn close(mut self) -> io::Result<()> {
+        self.stream.shutdown(Shutdown::Write)?;
+        loop {
+            match self.receive() {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn send(&mut self, kind: u8, frame: u32, value: u64) -> io::Result<()> {
+        let mut message = vec![kind];
+        message.extend_from_slice(&frame.to_be_bytes());
+        message.extend_from_slice(&value.to_be_bytes());
+        self.stream.write_all(&message).map_err(left)
+    }
+
+    fn receive(&mut self) -> io::Result<()> {
+        let mut message = [0; 13];
+        self.stream.read_exact(&mut message).map_err(left)?;
+        let frame = big_endian(&message[1..5]) as u32;
+        let value = big_endian(&message[5..]);
+        match message[0] {
+            INPUT => {
+                self.remote_inputs.insert(frame, value as u16);
+                Ok(())
+            }
+            HASH => {
+                self.remote_hashes.insert(frame, value);
+                self.compare_hashes(frame)
+            }
+            kind => Err(invalid(format!("unknown netplay message {}", kind))),
+        }
+    }
+
+    fn compare_hashes(&mut self, frame: u32) -> io::Result<()> {
+        match (self.local_hashes.get(&frame), self.remote_hashes.get(&frame)) {
+            (Some(local), Some(remote)) if local != remote => {
+                Err(invalid(format!("the games drifted apart (desync) by frame {}", frame)))
+            }
+            (Some(_), Some(_)) => {
+                self.local_hashes.remove(&frame);
+                self.remote_hashes.remove(&frame);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./netplay_test.rs"]
+mod netplay_test;
@@ -0,0 +1,34 @@
+use std::io;
+use std::net::{TcpListener, TcpStream};
+
+use chip8::netplay::{self, Config, Session};
+use options::Options;
+use rand;
+
+// Sets up the netplay session asked for with --host or --join, if any.
+// Hosting waits for the other player to connect.
+pub fn connect(options: &Options, rom_hash: u64) -> io::Result<Option<Session>> {
+    let (stream, host) = if let Some(port) = options.host {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        println!("Waiting for the other player on port {}", port);
+        let (stream, addr) = listener.accept()?;
+        println!("{} joined", addr);
+        (stream, true)
+    } else if let Some(ref addr) = options.join {
+        let stream = TcpStream::connect(addr.as_str())?;
+        println!("Joined {}", addr);
+        (stream, false)
+    } else {
+        return Ok(None);
+    };
+
+    let default_keys = if host { netplay::HOST_KEYS } else { netplay::GUEST_KEYS };
+    let config = Config {
+        host,
+        keys: options.netplay_keys.unwrap_or(default_keys),
+        input_delay: options.input_delay,
+        rom_hash,
+        seed: options.seed.unwrap_or_else(rand::random),
+    };
+    Session::new(stream, config).map(Some)
+}
@@ -0,0 +1,152 @@
+use super::*;
+use std::net::TcpListener;
+use std::thread;
+
+use machine::MachineConfig;
+
+fn config(host: bool) -> Config {
+    Config {
+        host,
+        keys: if host { HOST_KEYS } else { GUEST_KEYS },
+        input_delay: 2,
+        rom_hash: 1,
+        seed: if host { 7 } else { 8 },
+    }
+}
+
+// Connects a host and a guest over localhost, and runs each with its own
+// function on its own thread.
+fn connect<T, H, G>(host: Config, guest: Config, run_host: H, run_guest: G) -> (T, T)
+where
+    T: Send + 'static,
+    H: FnOnce(io::Result<Session>) -> T + Send + 'static,
+    G: FnOnce(io::Result<Session>) -> T + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let guest = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        run_guest(Session::new(stream, guest))
+    });
+    let (stream, _) = listener.accept().unwrap();
+    let host = run_host(Session::new(stream, host));
+    (host, guest.join().unwrap())
+}
+
+#[test]
+fn test_parse_keys() {
+    assert_eq!(parse_keys("1,4"), Ok(0b10010));
+    assert_eq!(parse_keys("cd"), Ok(0x3000));
+    assert_eq!(parse_keys("1,g"), Err("not a key: g".to_string()));
+    assert_eq!(parse_keys(""), Err("no keys given".to_string()));
+    assert_eq!(HOST_KEYS & GUEST_KEYS, 0);
+    assert_eq!(HOST_KEYS | GUEST_KEYS, 0xFFFF);
+}
+
+#[test]
+fn test_handshake() {
+    let (host, guest) = connect(
+        config(true),
+        config(false),
+        |session| session.map(|s| (s.seed(), s.input_delay())).unwrap(),
+        |session| session.map(|s| (s.seed(), s.input_delay())).unwrap(),
+    );
+    // Both use the host's seed and delay.
+    assert_eq!(host, (7, 2));
+    assert_eq!(guest, (7, 2));
+}
+
+#[test]
+fn test_handshake_errors() {
+    let error = |session: io::Result<Session>| session.err().map(|e| e.to_string());
+
+    let mut guest = config(false);
+    guest.rom_hash = 2;
+    let (host, _) = connect(config(true), guest, error, error);
+    assert_eq!(host, Some("the other side is running a different ROM".to_string()));
+
+    let mut guest = config(false);
+    guest.keys = parse_keys("1,4,c").unwrap();
+    let (host, _) = connect(config(true), guest, error, error);
+    assert_eq!(host, Some("both sides own keys 1,4".to_string()));
+
+    let (host, _) = connect(config(true), config(true), error, error);
+    assert_eq!(host, Some("both sides want to host".to_string()));
+
+    let mut host = config(true);
+    host.input_delay = u32::MAX;
+    let (_, guest) = connect(host, config(false), error, error);
+    assert_eq!(guest, Some(format!("an input delay of {} frames is over the limit of 60", u32::MAX)));
+}
+
+#[test]
+fn test_exchange() {
+    // The host holds 1 and tries to press C, which isn't its key; the
+    // guest holds C.
+    let run = |pressed: usize| {
+        move |session: io::Result<Session>| {
+            let mut session = session.unwrap();
+            let mut keypads = Vec::new();
+            for _ in 0..5 {
+                let mut keypad = [false; 16];
+                keypad[pressed] = true;
+                keypad[0xC] = true;
+                keypads.push(session.exchange(keypad).unwrap());
+                session.frame_done(0).unwrap();
+            }
+            session.close().unwrap();
+            keypads
+        }
+    };
+    let (host, guest) = connect(config(true), config(false), run(0x1), run(0x3));
+    assert_eq!(host, guest);
+
+    // Keys pressed on frame 0 arrive on frame 2.
+    assert_eq!(host[1], [false; 16]);
+    let pressed: Vec<usize> = (0..16).filter(|&key| host[2][key]).collect();
+    assert_eq!(pressed, vec![0x1, 0x3, 0xC]);
+}
+
+// Runs a ROM in lockstep on both sides, changing the machine on one side
+// at the given frame to make it drift.
+fn play(drift_at: Option<u32>) -> impl FnOnce(io::Result<Session>) -> io::Result<u64> + Send {
+    move |session| {
+        let mut session = session?;
+        let mut processor = Processor::with_config(MachineConfig::chip8());
+        processor.seed_rng(session.seed());
+        processor.load(include_bytes!("../tests/roms/keypad.ch8"));
+        for frame in 0..HASH_INTERVAL * 3 {
+            let mut keypad = [false; 16];
+            keypad[(frame / 10 % 16) as usize] = true;
+            let keypad = session.exchange(keypad)?;
+            processor.run(::CYCLES_PER_FRAME, keypad);
+            if Some(frame) == drift_at {
+                processor.write_ram(0xF00, 1);
+            }
+            session.frame_done(state_hash(&processor))?;
+        }
+        session.close()?;
+        Ok(state_hash(&processor))
+    }
+}
+
+#[test]
+fn test_lockstep() {
+    let (host, guest) = connect(config(true), config(false), play(None), play(None));
+    assert_eq!(host.unwrap(), guest.unwrap());
+}
+
+#[test]
+fn test_desync() {
+    let (host, guest) = connect(config(true), config(false), play(Some(30)), play(None));
+    let errors: Vec<String> = vec![host, guest]
+        .into_iter()
+        .filter_map(|result| result.err())
+        .map(|e| e.to_string())
+        .collect();
+    assert!(
+        errors.contains(&"the games drifted apart (desync) by frame 60".to_string()),
+        "{:?}",
+        errors
+    );
+}
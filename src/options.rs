@@ -0,0 +1,269 @@
+use chip8::machine::{MachineConfig, Quirks};
+use chip8::processor::Backend;
+use chip8::netplay;
+use tone::{ToneSettings, Waveform};
+
+pub const USAGE: &str = "usage: chip8 [options] ROM
+       chip8 selftest
+       chip8 trace-diff TRACE TRACE
+       chip8 make-patch ORIGINAL MODIFIED PATCH
+       chip8 dap [--headless]
+
+options:
+  --machine NAME        chip8 (default), eti660, hires or two-page
+  --quirks NAME         legacy (default), vip, schip or xochip
+  --rpl-flags N         number of persistent user flags, 8 or 16
+  --fast-forward X      speed while holding Tab (default 4)
+  --turbo X             speed when turbo is toggled with T (default 2)
+  --slow-motion X       speed when slow motion is toggled with O (default 0.25)
+  --tone HZ             beep frequency (default 240)
+  --volume X            beep volume from 0 to 1 (default 0.25)
+  --waveform NAME       square (default), sine or triangle
+  --audio-out FILE      also render the beep to a WAV file
+  --sample-rate HZ      sample rate for --audio-out (default 44100)
+  --headless            run without a window or sound; needs --frames, --gdb
+                        or --remote
+  --frames N            stop after N frames (60 per second)
+  --trace FILE          log every instruction executed to FILE
+  --profile FILE        write an execution profile and coverage map to FILE
+  --gdb PORT            wait for GDB to connect on PORT and let it drive
+  --backend NAME        cached (default), interpreter or blocks
+  --seed N              seed for CXKK's random numbers, to get the same ones
+                        every run (default: a new seed each run, or 0 with
+                        --headless)
+  --cheats              take cheat commands in the terminal while running
+  --patch FILE          apply an IPS or BPS patch to the ROM (default: ROM.ips
+                        or ROM.bps next to the ROM, if there is one)
+  --host PORT           host a two-player netplay session on PORT
+  --join HOST:PORT      join a netplay session
+  --netplay-keys KEYS   keys this player owns, in hex, e.g. 1,4 (default: the
+                        left half of the keypad to host, the right to join)
+  --input-delay N       frames before a key press counts in netplay, giving
+                        it time to arrive, up to 60 (default 2; the host's
+                        is used)
+  --remote ADDR         take JSON-RPC commands on a localhost port, or on a
+                        Unix socket if ADDR is a path";
+
+pub struct Options {
+    pub config: MachineConfig,
+    pub cartridge_filename: String,
+    pub fast_forward_speed: f32,
+    pub turbo_speed: f32,
+    pub slow_motion_speed: f32,
+    pub tone: ToneSettings,
+    pub audio_out: Option<String>,
+    pub sample_rate: u32,
+    pub headless: bool,
+    pub frames: Option<u32>,
+    pub trace: Option<String>,
+    pub profile: Option<String>,
+    pub gdb: Option<u16>,
+    pub backend: Backend,
+    pub seed: Option<u64>,
+    pub cheat_prompt: bool,
+    pub patch: Option<String>,
+    pub host: Option<u16>,
+    pub join: Option<String>,
+    pub netplay_keys: Option<u16>,
+    pub input_delay: u32,
+    pub remote: Option<String>,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut config = MachineConfig::chip8();
+        let mut cartridge_filename = None;
+        let mut fast_forward_speed = 4.0;
+        let mut turbo_speed = 2.0;
+        let mut slow_motion_speed = 0.25;
+        let mut tone = ToneSettings::default();
+        let mut audio_out = None;
+        let mut sample_rate = 44100;
+        let mut headless = false;
+        let mut frames = None;
+        let mut trace = None;
+        let mut profile = None;
+        let mut gdb = None;
+        let mut backend = Backend::DecodeCache;
+        let mut seed = None;
+        let mut cheat_prompt = false;
+        let mut patch = None;
+        let mut host = None;
+        let mut join = None;
+        let mut netplay_keys = None;
+        let mut input_delay = 2;
+        let mut remote = None;
+        // These change the machine, whichever order they come in.
+        let mut quirks = None;
+        let mut flag_registers = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--machine" => {
+                    let name = value(&mut args, arg)?;
+                    config = MachineConfig::from_name(name)
+                        .ok_or_else(|| format!("unknown machine: {}", name))?;
+                }
+                "--quirks" => {
+                    let name = value(&mut args, arg)?;
+                    quirks = Some(Quirks::from_name(name).ok_or_else(|| format!("unknown quirks: {}", name))?);
+                }
+                "--rpl-flags" => {
+                    flag_registers = match value(&mut args, arg)?.as_str() {
+                        "8" => Some(8),
+                        "16" => Some(16),
+                        _ => return Err("--rpl-flags takes 8 or 16".to_string()),
+                    };
+                }
+                "--fast-forward" => fast_forward_speed = speed(&mut args, arg)?,
+                "--turbo" => turbo_speed = speed(&mut args, arg)?,
+                "--slow-motion" => slow_motion_speed = speed(&mut args, arg)?,
+                "--tone" => tone.frequency = speed(&mut args, arg)?,
+                "--volume" => {
+                    tone.volume = match value(&mut args, arg)?.parse::<f32>() {
+                        Ok(volume) if (0.0..=1.0).contains(&volume) => volume,
+                        _ => return Err("--volume takes a number from 0 to 1".to_string()),
+                    };
+                }
+                "--waveform" => {
+                    let name = value(&mut args, arg)?;
+                    tone.waveform = Waveform::from_name(name)
+                        .ok_or_else(|| format!("unknown waveform: {}", name))?;
+                }
+                "--audio-out" => audio_out = Some(value(&mut args, arg)?.clone()),
+                "--sample-rate" => sample_rate = count(&mut args, arg)?,
+                "--headless" => headless = true,
+                "--frames" => frames = Some(count(&mut args, arg)?),
+                "--trace" => trace = Some(value(&mut args, arg)?.clone()),
+                "--profile" => profile = Some(value(&mut args, arg)?.clone()),
+                "--gdb" => {
+                    gdb = match value(&mut args, arg)?.parse::<u16>() {
+                        Ok(port) if port > 0 => Some(port),
+                        _ => return Err("--gdb takes a port number".to_string()),
+                    };
+                }
+                "--backend" => {
+                    let name = value(&mut args, arg)?;
+                    backend = Backend::from_name(name)
+                        .ok_or_else(|| format!("unknown backend: {}", name))?;
+                }
+                "--seed" => {
+                    seed = match value(&mut args, arg)?.parse::<u64>() {
+                        Ok(seed) => Some(seed),
+                        _ => return Err("--seed takes a whole number".to_string()),
+                    };
+                }
+                "--cheats" => cheat_prompt = true,
+                "--patch" => patch = Some(value(&mut args, arg)?.clone()),
+                "--host" => {
+                    host = match value(&mut args, arg)?.parse::<u16>() {
+                        Ok(port) if port > 0 => Some(port),
+                        _ => return Err("--host takes a port number".to_string()),
+                    };
+                }
+                "--join" => join = Some(value(&mut args, arg)?.clone()),
+                "--netplay-keys" => {
+                    let keys = netplay::parse_keys(value(&mut args, arg)?)
+                        .map_err(|e| format!("--netplay-keys: {}", e))?;
+                    netplay_keys = Some(keys);
+                }
+                "--input-delay" => {
+                    input_delay = value(&mut args, arg)?
+                        .parse()
+                        .ok()
+                        .filter(|&delay| delay <= netplay::MAX_INPUT_DELAY)
+                        .ok_or_else(|| {
+                            format!("--input-delay takes a whole number of frames, up to {}", netplay::MAX_INPUT_DELAY)
+                        })?;
+                }
+                "--remote" => remote = Some(value(&mut args, arg)?.clone()),
+                _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+                _ => cartridge_filename = Some(arg.clone()),
+            }
+        }
+
+        if let Some(quirks) = quirks {
+            config.quirks = quirks;
+        }
+        if let Some(flag_registers) = flag_registers {
+            config.flag_registers = flag_registers;
+        }
+
+        if headless && frames.is_none() && gdb.is_none() && remote.is_none() {
+            return Err("--headless needs --frames, --gdb or --remote".to_string());
+        }
+        // Headless runs are for checking things, so they come out the same
+        // every time.
+        if headless && seed.is_none() {
+            seed = Some(0);
+        }
+        if host.is_some() && join.is_some() {
+            return Err("--host and --join can't be used together".to_string());
+        }
+        if (host.is_some() || join.is_some()) && gdb.is_some() {
+            return Err("--gdb can't be used with netplay".to_string());
+        }
+        if (host.is_some() || join.is_some()) && cheat_prompt {
+            return Err("--cheats can't be used with netplay".to_string());
+        }
+        if (host.is_some() || join.is_some()) && remote.is_some() {
+            return Err("--remote can't be used with netplay".to_string());
+        }
+        if gdb.is_some() && remote.is_some() {
+            return Err("--gdb and --remote can't be used together".to_string());
+        }
+
+        Ok(Options {
+            config,
+            cartridge_filename: cartridge_filename.ok_or("no ROM given")?,
+            fast_forward_speed,
+            turbo_speed,
+            slow_motion_speed,
+            tone,
+            audio_out,
+            sample_rate,
+            headless,
+            frames,
+            trace,
+            profile,
+            gdb,
+            backend,
+            seed,
+            cheat_prompt,
+            patch,
+            host,
+            join,
+            netplay_keys,
+            input_delay,
+            remote,
+        })
+    }
+}
+
+fn value<'a, I>(args: &mut I, name: &str) -> Result<&'a String, String>
+where
+    I: Iterator<Item = &'a String>,
+{
+    args.next().ok_or_else(|| format!("{} needs a value", name))
+}
+
+fn count<'a, I>(args: &mut I, name: &str) -> Result<u32, String>
+where
+    I: Iterator<Item = &'a String>,
+{
+    match value(args, name)?.parse::<u32>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("{} takes a positive whole number", name)),
+    }
+}
+
+fn speed<'a, I>(args: &mut I, name: &str) -> Result<f32, String>
+where
+    I: Iterator<Item = &'a String>,
+{
+    match value(args, name)?.parse::<f32>() {
+        Ok(speed) if speed > 0.0 => Ok(speed),
+        _ => Err(format!("{} takes a positive number", name)),
+    }
+}
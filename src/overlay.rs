@@ -0,0 +1,88 @@
+use std::time::{Duration, Instant};
+
+use drivers::Osd;
+
+// How long a notification stays on screen.
+const MESSAGE_DURATION_MS: u64 = 2000;
+
+// Decides what text goes on top of the game: notifications, the frame
+// and instruction rate, and the name of the machine profile. Each of them
+// can be switched off with a hotkey.
+pub struct Overlay {
+    pub show_messages: bool,
+    pub show_stats: bool,
+    pub show_profile: bool,
+    profile: String,
+    message: Option<(String, Instant)>,
+    // Counters for the second currently being measured, and the rates
+    // measured over the last complete second.
+    sample_start: Instant,
+    frames: u32,
+    cycles: u32,
+    fps: u32,
+    ips: u32,
+}
+
+impl Overlay {
+    pub fn new(profile: &str, now: Instant) -> Self {
+        Overlay {
+            show_messages: true,
+            show_stats: false,
+            show_profile: false,
+            profile: profile.to_string(),
+            message: None,
+            sample_start: now,
+            frames: 0,
+            cycles: 0,
+            fps: 0,
+            ips: 0,
+        }
+    }
+
+    pub fn message(&mut self, text: &str, now: Instant) {
+        self.message = Some((text.to_string(), now));
+    }
+
+    // Call once per displayed frame with the number of cycles it ran.
+    pub fn frame(&mut self, cycles: u32, now: Instant) {
+        self.frames += 1;
+        self.cycles += cycles;
+
+        let elapsed = now - self.sample_start;
+        if elapsed >= Duration::from_secs(1) {
+            let seconds = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1e9;
+            self.fps = (self.frames as f32 / seconds).round() as u32;
+            self.ips = (self.cycles as f32 / seconds).round() as u32;
+            self.sample_start = now;
+            self.frames = 0;
+            self.cycles = 0;
+        }
+    }
+
+    pub fn osd(&self, now: Instant) -> Osd {
+        let mut status = Vec::new();
+        if self.show_stats {
+            status.push(format!("{} FPS", self.fps));
+            status.push(format!("{} IPS", self.ips));
+        }
+        if self.show_profile {
+            status.push(self.profile.clone());
+        }
+
+        let message = match self.message {
+            Some((ref text, shown)) if self.show_messages &&
+                now - shown < Duration::from_millis(MESSAGE_DURATION_MS) => Some(text.clone()),
+            _ => None,
+        };
+
+        Osd {
+            indicator: None,
+            status,
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./overlay_test.rs"]
+mod overlay_test;
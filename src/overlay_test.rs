@@ -0,0 +1,42 @@
+use super::*;
+#[test]
+fn test_nothing_shown_by_default() {
+    let now = Instant::now();
+    let overlay = Overlay::new("chip8", now);
+    assert_eq!(overlay.osd(now), Osd::default());
+}
+#[test]
+fn test_message_expires() {
+    let now = Instant::now();
+    let mut overlay = Overlay::new("chip8", now);
+    overlay.message("Flags saved", now);
+    assert_eq!(overlay.osd(now).message, Some("Flags saved".to_string()));
+
+    let later = now + Duration::from_millis(MESSAGE_DURATION_MS);
+    assert_eq!(overlay.osd(later).message, None);
+}
+#[test]
+fn test_messages_can_be_hidden() {
+    let now = Instant::now();
+    let mut overlay = Overlay::new("chip8", now);
+    overlay.show_messages = false;
+    overlay.message("Reset", now);
+    assert_eq!(overlay.osd(now).message, None);
+}
+#[test]
+fn test_stats() {
+    let now = Instant::now();
+    let mut overlay = Overlay::new("chip8", now);
+    overlay.show_stats = true;
+    for i in 1..61 {
+        overlay.frame(8, now + Duration::from_millis(1000 * i / 60));
+    }
+    assert_eq!(overlay.osd(now).status, ["60 FPS", "480 IPS"]);
+}
+#[test]
+fn test_profile() {
+    let now = Instant::now();
+    let mut overlay = Overlay::new("eti660", now);
+    overlay.show_profile = true;
+    assert_eq!(overlay.osd(now).status, ["eti660"]);
+}
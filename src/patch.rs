@@ -0,0 +1,335 @@
+// Soft-patching ROMs with IPS and BPS patches, and making patches by
+// diffing two ROMs. Fixes for old ROMs are passed around as patches rather
+// than as patched ROMs, and applied as the ROM is loaded.
+//
+// IPS is the simplest: a list of offsets and bytes to write there. BPS
+// records CRC-32s of the original ROM, the patched ROM and the patch
+// itself, so a patch for a different ROM (or a corrupted one) is caught
+// rather than producing a broken game.
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+// IPS records have 16-bit lengths.
+const IPS_MAX_RECORD: usize = 0xFFFF;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Ips,
+    Bps,
+}
+
+impl Format {
+    // Patches are told apart by their first few bytes.
+    pub fn detect(patch: &[u8]) -> Option<Format> {
+        if patch.starts_with(IPS_MAGIC) {
+            Some(Format::Ips)
+        } else if patch.starts_with(BPS_MAGIC) {
+            Some(Format::Bps)
+        } else {
+            None
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ips" => Some(Format::Ips),
+            "bps" => Some(Format::Bps),
+            _ => None,
+        }
+    }
+}
+
+// Applies an IPS or BPS patch to a ROM, returning the patched ROM.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    match Format::detect(patch) {
+        Some(Format::Ips) => apply_ips(rom, patch),
+        Some(Format::Bps) => apply_bps(rom, patch),
+        None => Err("not an IPS or BPS patch".to_string()),
+    }
+}
+
+// Makes a patch that turns original into modified.
+pub fn make(original: &[u8], modified: &[u8], format: Format) -> Vec<u8> {
+    match format {
+        Format::Ips => make_ips(original, modified),
+        Format::Bps => make_bps(original, modified),
+    }
+}
+
+// Reads a patch a field at a time.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() - self.offset < len {
+            return Err("the patch ends too soon".to_string());
+        }
+        let bytes = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // A big-endian number of the given size, as IPS uses.
+    fn number(&mut self, len: usize) -> Result<usize, String> {
+        Ok(self.bytes(len)?.iter().fold(0, |number, &byte| number << 8 | byte as usize))
+    }
+
+    // BPS's variable-length numbers: seven bits at a time, least
+    // significant first, with the top bit set on the last byte.
+    fn varint(&mut self) -> Result<usize, String> {
+        let mut number: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.byte()?;
+            number = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|bits| number.checked_add(bits))
+                .ok_or("a number in the patch is too big")?;
+            if byte & 0x80 != 0 {
+                return Ok(number);
+            }
+            shift = shift.checked_shl(7).ok_or("a number in the patch is too big")?;
+            number = number.checked_add(shift).ok_or("a number in the patch is too big")?;
+        }
+    }
+}
+
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = Reader { data: patch, offset: 0 };
+    if reader.bytes(IPS_MAGIC.len())? != IPS_MAGIC {
+        return Err("not an IPS patch".to_string());
+    }
+    let mut patched = rom.to_vec();
+    loop {
+        if reader.bytes(IPS_EOF.len())? == IPS_EOF {
+            break;
+        }
+        reader.offset -= IPS_EOF.len();
+        let offset = reader.number(3)?;
+        let bytes = match reader.number(2)? {
+            // A run of one byte repeated.
+            0 => {
+                let count = reader.number(2)?;
+                vec![reader.byte()?; count]
+            }
+            len => reader.bytes(len)?.to_vec(),
+        };
+        if patched.len() < offset + bytes.len() {
+            patched.resize(offset + bytes.len(), 0);
+        }
+        patched[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+    // Some patches shrink the ROM, with its new size after the end marker.
+    if reader.offset + 3 <= patch.len() {
+        let len = reader.number(3)?;
+        patched.truncate(len);
+    }
+    Ok(patched)
+}
+
+pub fn make_ips(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    let mut patch = IPS_MAGIC.to_vec();
+    let mut offset = 0;
+    while offset < modified.len() {
+        if original.get(offset) == Some(&modified[offset]) {
+            offset += 1;
+            continue;
+        }
+        let start = offset;
+        while offset < modified.len()
+            && offset - start < IPS_MAX_RECORD
+            && original.get(offset) != Some(&modified[offset])
+        {
+            offset += 1;
+        }
+        // An offset that reads as "EOF" would end the patch early, so a
+        // record there starts a byte sooner. ROMs are never that big.
+        let start = if start == 0x454F46 { start - 1 } else { start };
+        patch.extend_from_slice(&[(start >> 16) as u8, (start >> 8) as u8, start as u8]);
+        patch.extend_from_slice(&[((offset - start) >> 8) as u8, (offset - start) as u8]);
+        patch.extend_from_slice(&modified[start..offset]);
+    }
+    patch.extend_from_slice(IPS_EOF);
+    if modified.len() < original.len() {
+        let len = modified.len();
+        patch.extend_from_slice(&[(len >> 16) as u8, (len >> 8) as u8, len as u8]);
+    }
+    patch
+}
+
+// BPS actions, in the low two bits of each one's first number.
+const SOURCE_READ: usize = 0;
+const TARGET_READ: usize = 1;
+const SOURCE_COPY: usize = 2;
+const TARGET_COPY: usize = 3;
+
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < BPS_MAGIC.len() + 12 || !patch.starts_with(BPS_MAGIC) {
+        return Err("not a BPS patch".to_string());
+    }
+    let footer = patch.len() - 12;
+    let checksum = |at: usize| {
+        let bytes = &patch[footer + at..footer + at + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    };
+    if crc32(&patch[..footer + 8]) != checksum(8) {
+        return Err("the patch is corrupt: its checksum doesn't match".to_string());
+    }
+    if crc32(rom) != checksum(0) {
+        return Err("the patch is for a different ROM: the ROM's checksum doesn't match".to_string());
+    }
+
+    let mut reader = Reader {
+        data: &patch[..footer],
+        offset: BPS_MAGIC.len(),
+    };
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(format!("the patch is for a {} byte ROM, not {} bytes", source_size, rom.len()));
+    }
+
+    // The size comes from the patch, so it isn't trusted to allocate with.
+    let mut target = Vec::new();
+    let mut source_offset: isize = 0;
+    let mut target_offset: isize = 0;
+    let out_of_range = || "the patch copies from outside the ROM".to_string();
+    while reader.offset < footer {
+        let action = reader.varint()?;
+        let len = (action >> 2) + 1;
+        if target.len() + len > target_size {
+            return Err("the patch writes past the end of the ROM".to_string());
+        }
+        match action & 3 {
+            SOURCE_READ => {
+                let start = target.len();
+                target.extend_from_slice(slice(rom, start, len).ok_or_else(out_of_range)?);
+            }
+            TARGET_READ => target.extend_from_slice(reader.bytes(len)?),
+            SOURCE_COPY => {
+                let relative = signed(reader.varint()?);
+                source_offset = source_offset.checked_add(relative).ok_or_else(out_of_range)?;
+                let start = usize_offset(source_offset).ok_or_else(out_of_range)?;
+                target.extend_from_slice(slice(rom, start, len).ok_or_else(out_of_range)?);
+                source_offset += len as isize;
+            }
+            TARGET_COPY => {
+                let relative = signed(reader.varint()?);
+                target_offset = target_offset.checked_add(relative).ok_or_else(out_of_range)?;
+                let start = usize_offset(target_offset).ok_or_else(out_of_range)?;
+                if start >= target.len() {
+                    return Err(out_of_range());
+                }
+                let end = start.checked_add(len).ok_or_else(out_of_range)?;
+                // The copy can overlap what it's writing, to repeat a run
+                // of bytes, so it goes a byte at a time.
+                for i in start..end {
+                    let byte = target[i];
+                    target.push(byte);
+                }
+                target_offset += len as isize;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if target.len() != target_size {
+        return Err("the patch ends before the patched ROM does".to_string());
+    }
+    if crc32(&target) != checksum(4) {
+        return Err("the patched ROM's checksum doesn't match".to_string());
+    }
+    Ok(target)
+}
+
+fn signed(number: usize) -> isize {
+    let magnitude = (number >> 1) as isize;
+    if number & 1 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+// The len bytes at start, if they're all there.
+fn slice(data: &[u8], start: usize, len: usize) -> Option<&[u8]> {
+    data.get(start..start.checked_add(len)?)
+}
+
+fn usize_offset(offset: isize) -> Option<usize> {
+    if offset < 0 {
+        None
+    } else {
+        Some(offset as usize)
+    }
+}
+
+// A simple BPS patch: bytes that are the same as in the original are read
+// from it, and everything else is stored in the patch. ROMs are small
+// enough that looking for moved blocks isn't worth it.
+pub fn make_bps(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    let mut patch = BPS_MAGIC.to_vec();
+    write_varint(&mut patch, original.len());
+    write_varint(&mut patch, modified.len());
+    write_varint(&mut patch, 0);
+
+    let mut offset = 0;
+    while offset < modified.len() {
+        let same = |offset: usize| original.get(offset) == Some(&modified[offset]);
+        let start = offset;
+        let matching = same(start);
+        while offset < modified.len() && same(offset) == matching {
+            offset += 1;
+        }
+        let action = if matching { SOURCE_READ } else { TARGET_READ };
+        write_varint(&mut patch, (offset - start - 1) << 2 | action);
+        if !matching {
+            patch.extend_from_slice(&modified[start..offset]);
+        }
+    }
+
+    patch.extend_from_slice(&crc32(original).to_le_bytes());
+    patch.extend_from_slice(&crc32(modified).to_le_bytes());
+    let checksum = crc32(&patch);
+    patch.extend_from_slice(&checksum.to_le_bytes());
+    patch
+}
+
+fn write_varint(patch: &mut Vec<u8>, mut number: usize) {
+    loop {
+        let bits = (number & 0x7F) as u8;
+        number >>= 7;
+        if number == 0 {
+            patch.push(0x80 | bits);
+            return;
+        }
+        patch.push(bits);
+        number -= 1;
+    }
+}
+
+// The CRC-32 used by zip and PNG, which is what BPS uses.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+#[path = "./patch_test.rs"]
+mod patch_test;
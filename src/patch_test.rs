@@ -0,0 +1,175 @@
+use super::*;
+
+fn roms() -> Vec<(Vec<u8>, Vec<u8>)> {
+    let original: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    let mut changed = original.clone();
+    changed[0] = 0xFF;
+    changed[10..14].copy_from_slice(&[1, 2, 3, 4]);
+    changed[199] = 0;
+    let mut longer = original.clone();
+    longer.extend_from_slice(&[0x12, 0x00]);
+    let shorter = original[..150].to_vec();
+    vec![
+        (original.clone(), original.clone()),
+        (original.clone(), changed),
+        (original.clone(), longer),
+        (original.clone(), shorter),
+        (Vec::new(), original),
+    ]
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn test_ips_round_trip() {
+    for (original, modified) in roms() {
+        let patch = make_ips(&original, &modified);
+        assert_eq!(Format::detect(&patch), Some(Format::Ips));
+        assert_eq!(apply(&original, &patch), Ok(modified));
+    }
+}
+
+#[test]
+fn test_bps_round_trip() {
+    for (original, modified) in roms() {
+        let patch = make_bps(&original, &modified);
+        assert_eq!(Format::detect(&patch), Some(Format::Bps));
+        assert_eq!(apply(&original, &patch), Ok(modified));
+    }
+}
+
+#[test]
+fn test_ips_run_and_truncate() {
+    let mut patch = b"PATCH".to_vec();
+    // Four 0xAA bytes at 2.
+    patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x04, 0xAA]);
+    // Two bytes at 8, past the end of the ROM.
+    patch.extend_from_slice(&[0x00, 0x00, 0x08, 0x00, 0x02, 0x01, 0x02]);
+    patch.extend_from_slice(b"EOF");
+    assert_eq!(
+        apply_ips(&[0; 4], &patch),
+        Ok(vec![0, 0, 0xAA, 0xAA, 0xAA, 0xAA, 0, 0, 1, 2])
+    );
+
+    patch.extend_from_slice(&[0x00, 0x00, 0x03]);
+    assert_eq!(apply_ips(&[0; 4], &patch), Ok(vec![0, 0, 0xAA]));
+}
+
+#[test]
+fn test_bad_ips() {
+    assert_eq!(apply(&[0; 4], b"PATCH\x00\x00\x01\x00"), Err("the patch ends too soon".to_string()));
+    assert_eq!(apply(&[0; 4], b"PATCHES"), Err("the patch ends too soon".to_string()));
+    assert_eq!(apply(&[0; 4], b"hello"), Err("not an IPS or BPS patch".to_string()));
+}
+
+// A BPS patch with the given actions, with checksums filled in.
+fn build_bps(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+    build_bps_sized(source, target, target.len(), actions)
+}
+
+// The same, but claiming the target is target_size bytes.
+fn build_bps_sized(source: &[u8], target: &[u8], target_size: usize, actions: &[u8]) -> Vec<u8> {
+    let mut patch = b"BPS1".to_vec();
+    write_varint(&mut patch, source.len());
+    write_varint(&mut patch, target_size);
+    write_varint(&mut patch, 0);
+    patch.extend_from_slice(actions);
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    let checksum = crc32(&patch);
+    patch.extend_from_slice(&checksum.to_le_bytes());
+    patch
+}
+
+#[test]
+fn test_bps_copies() {
+    let source = [1, 2, 3, 4];
+    let target = [3, 4, 9, 9, 9, 9, 1, 2];
+    let mut actions = Vec::new();
+    // Copy two bytes from the source at 2.
+    write_varint(&mut actions, (2 - 1) << 2 | SOURCE_COPY);
+    write_varint(&mut actions, 2 << 1);
+    // Write a 9, then copy it forwards three times.
+    write_varint(&mut actions, TARGET_READ);
+    actions.push(9);
+    write_varint(&mut actions, (3 - 1) << 2 | TARGET_COPY);
+    write_varint(&mut actions, 2 << 1);
+    // Go back to the start of the source.
+    write_varint(&mut actions, (2 - 1) << 2 | SOURCE_COPY);
+    write_varint(&mut actions, 4 << 1 | 1);
+
+    let patch = build_bps(&source, &target, &actions);
+    assert_eq!(apply_bps(&source, &patch), Ok(target.to_vec()));
+}
+
+#[test]
+fn test_bps_checksums() {
+    let original: Vec<u8> = (0..64).collect();
+    let mut modified = original.clone();
+    modified[5] = 0;
+    let patch = make_bps(&original, &modified);
+
+    let mut other = original.clone();
+    other[0] = 0xFF;
+    assert_eq!(
+        apply(&other, &patch),
+        Err("the patch is for a different ROM: the ROM's checksum doesn't match".to_string())
+    );
+
+    let mut corrupt = patch.clone();
+    corrupt[8] ^= 1;
+    assert_eq!(
+        apply(&original, &corrupt),
+        Err("the patch is corrupt: its checksum doesn't match".to_string())
+    );
+
+    // A patch whose target checksum is wrong, but is otherwise fine.
+    let mut actions = Vec::new();
+    write_varint(&mut actions, (64 - 1) << 2 | SOURCE_READ);
+    let patch = build_bps(&original, &modified, &actions);
+    assert_eq!(
+        apply(&original, &patch),
+        Err("the patched ROM's checksum doesn't match".to_string())
+    );
+}
+
+#[test]
+fn test_bps_out_of_range() {
+    let mut actions = Vec::new();
+    write_varint(&mut actions, (4 - 1) << 2 | SOURCE_COPY);
+    write_varint(&mut actions, 2 << 1);
+    let patch = build_bps(&[0; 4], &[0; 4], &actions);
+    assert_eq!(apply(&[0; 4], &patch), Err("the patch copies from outside the ROM".to_string()));
+}
+
+#[test]
+fn test_bps_huge_numbers() {
+    // A target too big to allocate is an error, not a crash.
+    let mut actions = Vec::new();
+    write_varint(&mut actions, (4 - 1) << 2 | SOURCE_READ);
+    let patch = build_bps_sized(&[0; 4], &[0; 4], usize::MAX, &actions);
+    assert_eq!(apply(&[0; 4], &patch), Err("the patch ends before the patched ROM does".to_string()));
+
+    // Offsets that overflow, after one copy has moved them off zero.
+    for &action in &[SOURCE_COPY, TARGET_COPY] {
+        let mut actions = Vec::new();
+        write_varint(&mut actions, (2 - 1) << 2 | SOURCE_READ);
+        write_varint(&mut actions, action);
+        write_varint(&mut actions, 1 << 1);
+        write_varint(&mut actions, action);
+        write_varint(&mut actions, (isize::MAX as usize) << 1);
+        let patch = build_bps(&[0; 4], &[0; 4], &actions);
+        assert_eq!(apply(&[0; 4], &patch), Err("the patch copies from outside the ROM".to_string()));
+    }
+}
+
+#[test]
+fn test_format_from_extension() {
+    assert_eq!(Format::from_extension("ips"), Some(Format::Ips));
+    assert_eq!(Format::from_extension("BPS"), Some(Format::Bps));
+    assert_eq!(Format::from_extension("ch8"), None);
+}
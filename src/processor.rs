@@ -1,17 +1,98 @@
-use rand;
-use rand::Rng;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use rand::{self, Rng};
 use font::FONT_SET;
+use instruction::Instruction;
+#[cfg(feature = "std")]
+use recompiler::BlockCache;
+use machine::MachineConfig;
+use random::{Random, XorShift};
+use trace::{Registers, TraceEntry};
 
-use CHIP8_HEIGHT;
 use CHIP8_WIDTH;
+use CHIP8_HEIGHT;
 use CHIP8_RAM;
+use VRAM_HEIGHT;
+use CYCLES_PER_FRAME;
 
 const OPCODE_SIZE: usize = 2;
 
-pub struct OutputState<'a> {
-    pub vram: &'a [[u8; CHIP8_WIDTH]; CHIP8_HEIGHT],
+// Save states start with this, then a version number.
+#[cfg(feature = "std")]
+const STATE_MAGIC: &[u8] = b"CH8S";
+#[cfg(feature = "std")]
+const STATE_VERSION: u8 = 1;
+
+// How the processor runs instructions. They all behave identically, cycle
+// for cycle; they differ only in speed. The caches need a heap, so without
+// the std feature every backend interprets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    // Decodes every instruction as it runs.
+    Interpreter,
+    // Decodes each instruction once and remembers it until the memory it
+    // came from is written.
+    DecodeCache,
+    // Like DecodeCache for tick, but run translates whole basic blocks and
+    // runs them without going back to the top of the loop for each cycle.
+    Blocks,
+}
+
+impl Backend {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "interpreter" => Some(Backend::Interpreter),
+            "cached" => Some(Backend::DecodeCache),
+            "blocks" => Some(Backend::Blocks),
+            _ => None,
+        }
+    }
+}
+
+pub struct OutputState {
     pub vram_changed: bool,
+    pub flags_changed: bool,
     pub beep: bool,
+    // The instruction run this tick, when tracing is on.
+    pub trace: Option<TraceEntry>,
+}
+
+// Memory an instruction reads or writes as data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryAccess {
+    pub start: usize,
+    pub len: usize,
+    pub write: bool,
+}
+
+impl MemoryAccess {
+    // What the instruction touches when it runs with I set to i.
+    pub fn of(opcode: u16, i: usize) -> Option<MemoryAccess> {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let (len, write) = match opcode & 0xF0FF {
+            0xF033 => (3, true),
+            0xF055 => (x + 1, true),
+            0xF065 => (x + 1, false),
+            _ if opcode & 0xF000 == 0xD000 => ((opcode & 0x000F) as usize, false),
+            _ => return None,
+        };
+        Some(MemoryAccess { start: i, len, write })
+    }
+
+    pub fn overlaps(&self, start: usize, len: usize) -> bool {
+        self.start < start.saturating_add(len) && start < self.start.saturating_add(self.len)
+    }
+}
+
+// A seed that's different every run. wasm32 has no thread_rng, and no_std
+// builds have no rand at all, so there every processor starts with the same
+// seed unless the host reseeds it.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+fn fresh_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+#[cfg(not(all(feature = "std", not(target_arch = "wasm32"))))]
+fn fresh_seed() -> u64 {
+    0
 }
 
 enum ProgramCounter {
@@ -30,8 +111,11 @@ impl ProgramCounter {
     }
 }
 
-pub struct Processor {
-    vram: [[u8; CHIP8_WIDTH]; CHIP8_HEIGHT],
+// The CHIP-8 itself. R is where CXKK gets random numbers from.
+#[derive(Clone)]
+pub struct Processor<R = XorShift> {
+    config: MachineConfig,
+    vram: [[u8; CHIP8_WIDTH]; VRAM_HEIGHT],
     vram_changed: bool,
     ram: [u8; CHIP8_RAM],
     stack: [usize; 16],
@@ -44,47 +128,317 @@ pub struct Processor {
     keypad: [bool; 16],
     keypad_waiting: bool,
     keypad_register: usize,
+    flags: [u8; 16],
+    flags_changed: bool,
+    // Cycles run since the last vertical blank. Every CYCLES_PER_FRAME
+    // cycles is one 60Hz frame.
+    frame_cycles: u32,
+    vblank_waiting: bool,
+    tracing: bool,
+    // Whether the host counts the timers down itself with tick_timers,
+    // rather than running doing it every cycle.
+    manual_timers: bool,
+    rng: R,
+    backend: Backend,
+    // Instructions already decoded, by address, and translated blocks.
+    // Writing to RAM forgets the code that overlaps what was written.
+    #[cfg(feature = "std")]
+    decoded: Vec<Option<Instruction>>,
+    #[cfg(feature = "std")]
+    blocks: BlockCache,
 }
 
 impl Processor {
     pub fn new() -> Self {
+        Processor::with_config(MachineConfig::chip8())
+    }
+
+    pub fn with_config(config: MachineConfig) -> Self {
+        Processor::with_rng(config, XorShift::new(fresh_seed()))
+    }
+
+    // Makes CXKK produce the same numbers every time for the same seed.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = XorShift::new(seed);
+    }
+
+    // Everything needed to carry on exactly where the processor is now,
+    // except the machine config, which load_state checks matches.
+    #[cfg(feature = "std")]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = STATE_MAGIC.to_vec();
+        state.push(STATE_VERSION);
+        state.push(self.config.name.len() as u8);
+        state.extend_from_slice(self.config.name.as_bytes());
+        state.extend_from_slice(&self.ram);
+        for row in self.vram.iter() {
+            state.extend_from_slice(row);
+        }
+        for &addr in &self.stack {
+            state.extend_from_slice(&(addr as u16).to_be_bytes());
+        }
+        state.extend_from_slice(&self.v);
+        state.extend_from_slice(&(self.i as u16).to_be_bytes());
+        state.extend_from_slice(&(self.pc as u16).to_be_bytes());
+        state.extend_from_slice(&[
+            self.sp as u8,
+            self.delay_timer,
+            self.sound_timer,
+            self.keypad_waiting as u8,
+            self.keypad_register as u8,
+            self.frame_cycles as u8,
+            self.vblank_waiting as u8,
+        ]);
+        state.extend_from_slice(&self.flags);
+        for word in &self.rng.state() {
+            state.extend_from_slice(&word.to_be_bytes());
+        }
+        state
+    }
+
+    // Restores a state from save_state. Nothing changes if it can't be
+    // used.
+    #[cfg(feature = "std")]
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let name = self.config.name.as_bytes();
+        let header_len = STATE_MAGIC.len() + 2 + name.len();
+        // RAM, VRAM, the stack, V0 to VF, I and the PC, the seven one-byte
+        // fields, the flags and the generator.
+        let expected_len = header_len + CHIP8_RAM + VRAM_HEIGHT * CHIP8_WIDTH + 32 + 16 + 4 + 7 + 16 + 16;
+        if !state.starts_with(STATE_MAGIC) || state.len() < STATE_MAGIC.len() + 2 {
+            return Err("not a save state".to_string());
+        }
+        if state[STATE_MAGIC.len()] != STATE_VERSION {
+            return Err(format!("unsupported save state version {}", state[STATE_MAGIC.len()]));
+        }
+        let saved_name = &state[STATE_MAGIC.len() + 2..];
+        let saved_name = &saved_name[..(state[STATE_MAGIC.len() + 1] as usize).min(saved_name.len())];
+        if saved_name != name {
+            return Err(format!(
+                "save state is for the {} machine, not {}",
+                String::from_utf8_lossy(saved_name),
+                self.config.name
+            ));
+        }
+        if state.len() != expected_len {
+            return Err("save state is the wrong size".to_string());
+        }
+        // The stack and I are used to index RAM, so they're checked before
+        // anything is changed.
+        let registers = &state[header_len + CHIP8_RAM + VRAM_HEIGHT * CHIP8_WIDTH..];
+        let word = |at: usize| (registers[at] as usize) << 8 | registers[at + 1] as usize;
+        if (0..16).any(|entry| word(entry * 2) > self.config.ram_size - OPCODE_SIZE) {
+            return Err("save state's stack points outside RAM".to_string());
+        }
+        if word(32 + 16) >= self.config.ram_size {
+            return Err("save state's I points outside RAM".to_string());
+        }
+
+        let mut rest = &state[header_len..];
+        let mut take = |len: usize| {
+            let (taken, left) = rest.split_at(len);
+            rest = left;
+            taken
+        };
+        self.ram.copy_from_slice(take(CHIP8_RAM));
+        for row in self.vram.iter_mut() {
+            row.copy_from_slice(take(CHIP8_WIDTH));
+        }
+        for addr in self.stack.iter_mut() {
+            let bytes = take(2);
+            *addr = (bytes[0] as usize) << 8 | bytes[1] as usize;
+        }
+        self.v.copy_from_slice(take(16));
+        let bytes = take(4);
+        self.i = (bytes[0] as usize) << 8 | bytes[1] as usize;
+        self.pc = ((bytes[2] as usize) << 8 | bytes[3] as usize).min(self.config.ram_size - OPCODE_SIZE);
+        let bytes = take(7);
+        self.sp = (bytes[0] as usize).min(self.stack.len());
+        self.delay_timer = bytes[1];
+        self.sound_timer = bytes[2];
+        self.keypad_waiting = bytes[3] != 0;
+        self.keypad_register = bytes[4] as usize & 0xF;
+        self.frame_cycles = bytes[5] as u32 % CYCLES_PER_FRAME;
+        self.vblank_waiting = bytes[6] != 0;
+        self.flags.copy_from_slice(take(16));
+        let mut rng = [0; 4];
+        for word in rng.iter_mut() {
+            let bytes = take(4);
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        self.rng = XorShift::from_state(rng);
+        self.forget_code(0, CHIP8_RAM);
+        Ok(())
+    }
+}
+
+impl<R: Random> Processor<R> {
+    // A processor that gets its random numbers from rng.
+    pub fn with_rng(config: MachineConfig, rng: R) -> Self {
+        assert!(config.ram_size <= CHIP8_RAM);
+        assert!(config.display_height <= VRAM_HEIGHT);
+        assert!(config.flag_registers <= 16);
+        assert!(config.font_base + FONT_SET.len() <= config.ram_size);
 
         let mut ram = [0u8; CHIP8_RAM];
         for i in 0..FONT_SET.len() {
-            ram[i] = FONT_SET[i];
+            ram[config.font_base + i] = FONT_SET[i];
         }
+        let pc = config.entry_point;
 
         Processor {
-            vram: [[0; CHIP8_WIDTH]; CHIP8_HEIGHT],
+            config,
+            vram: [[0; CHIP8_WIDTH]; VRAM_HEIGHT],
             vram_changed: false,
             ram: ram,
             stack: [0; 16],
             v: [0; 16],
             i: 0,
-            pc: 0x200,
+            pc,
             sp: 0,
             delay_timer: 0,
             sound_timer: 0,
             keypad: [false; 16],
             keypad_waiting: false,
             keypad_register: 0,
+            flags: [0; 16],
+            flags_changed: false,
+            frame_cycles: 0,
+            vblank_waiting: false,
+            tracing: false,
+            manual_timers: false,
+            rng,
+            backend: Backend::DecodeCache,
+            #[cfg(feature = "std")]
+            decoded: vec![None; CHIP8_RAM],
+            #[cfg(feature = "std")]
+            blocks: BlockCache::new(),
         }
     }
 
+    // Copies the cartridge to the machine's load address. Anything that
+    // would run past the end of RAM or into a reserved region is dropped.
     pub fn load(&mut self, data: &[u8]) {
         for (i, &byte) in data.iter().enumerate() {
-            let addr = 0x200 + i;
-            if addr < 4096 {
-                self.ram[0x200 + i] = byte;
+            let addr = self.config.load_address + i;
+            if addr < self.config.ram_size && !self.config.is_reserved(addr) {
+                self.ram[addr] = byte;
             } else {
                 break;
             }
         }
+        self.forget_code(self.config.load_address, data.len());
+    }
+
+    // The SCHIP RPL user flags (or XO-CHIP's 16 flag registers). These are
+    // meant to survive between runs, so the frontend saves them whenever
+    // `flags_changed` is set and restores them before the first tick.
+    pub fn flags(&self) -> &[u8] {
+        &self.flags[..self.config.flag_registers]
+    }
+
+    pub fn set_flags(&mut self, flags: &[u8]) {
+        for (i, &flag) in flags.iter().take(self.config.flag_registers).enumerate() {
+            self.flags[i] = flag;
+        }
+    }
+
+    pub fn vram(&self) -> &[[u8; CHIP8_WIDTH]] {
+        &self.vram[..self.config.display_height]
+    }
+
+    // Makes every tick report the instruction it ran in OutputState::trace.
+    pub fn set_tracing(&mut self, tracing: bool) {
+        self.tracing = tracing;
+    }
+
+    // Stops running from counting the timers down, for hosts that call
+    // tick_timers at 60Hz themselves.
+    pub fn set_manual_timers(&mut self, manual_timers: bool) {
+        self.manual_timers = manual_timers;
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+        self.forget_code(0, CHIP8_RAM);
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            v: self.v,
+            i: self.i as u16,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    pub fn set_registers(&mut self, registers: &Registers) {
+        self.v = registers.v;
+        self.i = registers.i as usize;
+        self.delay_timer = registers.delay_timer;
+        self.sound_timer = registers.sound_timer;
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    // Kept inside RAM so the next fetch can't run off the end.
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc.min(self.config.ram_size - OPCODE_SIZE);
+    }
+
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    pub fn set_sp(&mut self, sp: usize) {
+        self.sp = sp.min(self.stack.len());
+    }
+
+    // Return addresses of the subroutines we're in, outermost first.
+    pub fn stack(&self) -> &[usize] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram[..self.config.ram_size]
+    }
+
+    // We can't tell what the caller will change, so every decoded
+    // instruction is forgotten.
+    pub fn ram_mut(&mut self) -> &mut [u8] {
+        self.forget_code(0, CHIP8_RAM);
+        &mut self.ram[..self.config.ram_size]
+    }
+
+    // Writes one byte, forgetting only the code it overlaps, so it's cheap
+    // enough to do every frame. Writing the value already there does
+    // nothing.
+    pub fn write_ram(&mut self, addr: usize, value: u8) {
+        if self.ram[addr] != value {
+            self.ram[addr] = value;
+            self.forget_code(addr, 1);
+        }
+    }
+
+    // Counts the delay and sound timers down by one, without running an
+    // instruction. Every cycle does this already, except while the machine
+    // waits for a key or once set_manual_timers is on.
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1
+        }
     }
 
     pub fn tick(&mut self, keypad: [bool; 16]) -> OutputState {
         self.keypad = keypad;
         self.vram_changed = false;
+        self.flags_changed = false;
+        let mut trace = None;
 
         if self.keypad_waiting {
             for i in 0..keypad.len() {
@@ -95,76 +449,202 @@ impl Processor {
                 }
             }
         } else {
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1
+            if !self.manual_timers {
+                self.tick_timers();
             }
-            if self.sound_timer > 0 {
-                self.sound_timer -= 1
+            if !self.vblank_waiting {
+                let pc = self.pc as u16;
+                let opcode = self.get_opcode();
+                // Copying the registers costs as much as running most
+                // instructions, so only do it when someone's looking.
+                let before = if self.tracing { Some(self.registers()) } else { None };
+                if self.backend == Backend::Interpreter {
+                    self.run_opcode(opcode);
+                } else {
+                    let instruction = self.decode_cached(opcode);
+                    self.execute(instruction);
+                }
+                if let Some(before) = before {
+                    trace = Some(TraceEntry {
+                        pc,
+                        opcode,
+                        before,
+                        after: self.registers(),
+                    });
+                }
             }
-            let opcode = self.get_opcode();
-            self.run_opcode(opcode);
+        }
+
+        self.frame_cycles += 1;
+        if self.frame_cycles == CYCLES_PER_FRAME {
+            self.frame_cycles = 0;
+            self.vblank_waiting = false;
         }
 
         OutputState {
-            vram: &self.vram,
             vram_changed: self.vram_changed,
+            flags_changed: self.flags_changed,
             beep: self.sound_timer > 0,
+            trace,
+        }
+    }
+
+    // Runs the given number of cycles with the same keys held, exactly as
+    // if tick were called that many times, and reports what happened over
+    // all of them. beep is as of the last cycle. Nothing is traced; use tick
+    // for that. With the Blocks backend this is much faster than ticking.
+    pub fn run(&mut self, cycles: u32, keypad: [bool; 16]) -> OutputState {
+        let mut output = OutputState {
+            vram_changed: false,
+            flags_changed: false,
+            beep: false,
+            trace: None,
+        };
+        let mut remaining = cycles;
+        while remaining > 0 {
+            let ran = if self.backend == Backend::Blocks {
+                self.run_blocks(remaining, keypad)
+            } else {
+                0
+            };
+            if ran > 0 {
+                output.vram_changed |= self.vram_changed;
+                output.flags_changed |= self.flags_changed;
+                remaining -= ran;
+            } else {
+                let tick = self.tick(keypad);
+                output.vram_changed |= tick.vram_changed;
+                output.flags_changed |= tick.flags_changed;
+                remaining -= 1;
+            }
         }
+        output.beep = self.sound_timer > 0;
+        output
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn run_blocks(&mut self, _cycles: u32, _keypad: [bool; 16]) -> u32 {
+        0
+    }
+
+    // Runs translated blocks one after another, doing what tick does for
+    // each instruction, until the cycles run out or the processor has to
+    // wait for a key or the display. Leaves a block early if it overwrites
+    // itself. Returns the cycles run, which is none if it couldn't start.
+    #[cfg(feature = "std")]
+    fn run_blocks(&mut self, cycles: u32, keypad: [bool; 16]) -> u32 {
+        self.keypad = keypad;
+        self.vram_changed = false;
+        self.flags_changed = false;
+        let mut ran = 0;
+        while ran < cycles && !self.keypad_waiting && !self.vblank_waiting {
+            let generation = self.blocks.generation();
+            let code = self.blocks.lookup(&self.ram[..self.config.ram_size], self.pc);
+            if code.start == code.end {
+                break;
+            }
+            for index in code {
+                if ran == cycles {
+                    break;
+                }
+                if !self.manual_timers {
+                    self.tick_timers();
+                }
+                let instruction = self.blocks.instruction(index);
+                self.execute(instruction);
+                ran += 1;
+                self.frame_cycles += 1;
+                if self.frame_cycles == CYCLES_PER_FRAME {
+                    self.frame_cycles = 0;
+                    self.vblank_waiting = false;
+                }
+                if self.blocks.generation() != generation {
+                    break;
+                }
+            }
+        }
+        ran
     }
 
     fn get_opcode(&self) -> u16 {
         (self.ram[self.pc] as u16) << 8 | (self.ram[self.pc + 1] as u16)
     }
 
+    // The instruction at the program counter, which is opcode.
+    #[cfg(feature = "std")]
+    fn decode_cached(&mut self, opcode: u16) -> Instruction {
+        match self.decoded[self.pc] {
+            Some(instruction) => instruction,
+            None => {
+                let instruction = Instruction::decode(opcode);
+                self.decoded[self.pc] = Some(instruction);
+                instruction
+            }
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn decode_cached(&mut self, opcode: u16) -> Instruction {
+        Instruction::decode(opcode)
+    }
+
+    // Called after writing len bytes from start. The instruction starting
+    // the byte before overlaps the first of them.
+    #[cfg(not(feature = "std"))]
+    fn forget_code(&mut self, _start: usize, _len: usize) {}
+
+    #[cfg(feature = "std")]
+    fn forget_code(&mut self, start: usize, len: usize) {
+        let end = (start + len).min(CHIP8_RAM);
+        for decoded in &mut self.decoded[start.saturating_sub(1).min(end)..end] {
+            *decoded = None;
+        }
+        self.blocks.invalidate(start, len);
+    }
+
     fn run_opcode(&mut self, opcode: u16) {
-        let nibbles = (
-            (opcode & 0xF000) >> 12 as u8,
-            (opcode & 0x0F00) >> 8 as u8,
-            (opcode & 0x00F0) >> 4 as u8,
-            (opcode & 0x000F) as u8,
-        );
-        let nnn = (opcode & 0x0FFF) as usize;
-        let kk = (opcode & 0x00FF) as u8;
-        let x = nibbles.1 as usize;
-        let y = nibbles.2 as usize;
-        let n = nibbles.3 as usize;
-
-        let pc_change = match nibbles { 
-            (0x00, 0x00, 0x0e, 0x00) => self.op_00e0(),
-            (0x00, 0x00, 0x0e, 0x0e) => self.op_00ee(),
-            (0x01, _, _, _) => self.op_1nnn(nnn),
-            (0x02, _, _, _) => self.op_2nnn(nnn),
-            (0x03, _, _, _) => self.op_3xkk(x, kk),
-            (0x04, _, _, _) => self.op_4xkk(x, kk),
-            (0x05, _, _, 0x00) => self.op_5xy0(x, y),
-            (0x06, _, _, _) => self.op_6xkk(x, kk),
-            (0x07, _, _, _) => self.op_7xkk(x, kk),
-            (0x08, _, _, 0x00) => self.op_8xy0(x, y),
-            (0x08, _, _, 0x01) => self.op_8xy1(x, y),
-            (0x08, _, _, 0x02) => self.op_8xy2(x, y),
-            (0x08, _, _, 0x03) => self.op_8xy3(x, y),
-            (0x08, _, _, 0x04) => self.op_8xy4(x, y),
-            (0x08, _, _, 0x05) => self.op_8xy5(x, y),
-            (0x08, _, _, 0x06) => self.op_8x06(x),
-            (0x08, _, _, 0x07) => self.op_8xy7(x, y),
-            (0x08, _, _, 0x0e) => self.op_8x0e(x),
-            (0x09, _, _, 0x00) => self.op_9xy0(x, y),
-            (0x0a, _, _, _) => self.op_annn(nnn),
-            (0x0b, _, _, _) => self.op_bnnn(nnn),
-            (0x0c, _, _, _) => self.op_cxkk(x, kk),
-            (0x0d, _, _, _) => self.op_dxyn(x, y, n),
-            (0x0e, _, 0x09, 0x0e) => self.op_ex9e(x),
-            (0x0e, _, 0x0a, 0x01) => self.op_exa1(x),
-            (0x0f, _, 0x00, 0x07) => self.op_fx07(x),
-            (0x0f, _, 0x00, 0x0a) => self.op_fx0a(x),
-            (0x0f, _, 0x01, 0x05) => self.op_fx15(x),
-            (0x0f, _, 0x01, 0x08) => self.op_fx18(x),
-            (0x0f, _, 0x01, 0x0e) => self.op_fx1e(x),
-            (0x0f, _, 0x02, 0x09) => self.op_fx29(x),
-            (0x0f, _, 0x03, 0x03) => self.op_fx33(x),
-            (0x0f, _, 0x05, 0x05) => self.op_fx55(x),
-            (0x0f, _, 0x06, 0x05) => self.op_fx65(x),
-            _ => ProgramCounter::Next,
+        self.execute(Instruction::decode(opcode));
+    }
+
+    fn execute(&mut self, instruction: Instruction) {
+        let pc_change = match instruction {
+            Instruction::Cls => self.op_00e0(),
+            Instruction::Ret => self.op_00ee(),
+            Instruction::Jp(nnn) => self.op_1nnn(nnn as usize),
+            Instruction::Call(nnn) => self.op_2nnn(nnn as usize),
+            Instruction::SeByte(x, kk) => self.op_3xkk(x as usize, kk),
+            Instruction::SneByte(x, kk) => self.op_4xkk(x as usize, kk),
+            Instruction::SeReg(x, y) => self.op_5xy0(x as usize, y as usize),
+            Instruction::LdByte(x, kk) => self.op_6xkk(x as usize, kk),
+            Instruction::AddByte(x, kk) => self.op_7xkk(x as usize, kk),
+            Instruction::LdReg(x, y) => self.op_8xy0(x as usize, y as usize),
+            Instruction::Or(x, y) => self.op_8xy1(x as usize, y as usize),
+            Instruction::And(x, y) => self.op_8xy2(x as usize, y as usize),
+            Instruction::Xor(x, y) => self.op_8xy3(x as usize, y as usize),
+            Instruction::AddReg(x, y) => self.op_8xy4(x as usize, y as usize),
+            Instruction::Sub(x, y) => self.op_8xy5(x as usize, y as usize),
+            Instruction::Shr(x, y) => self.op_8x06(x as usize, y as usize),
+            Instruction::Subn(x, y) => self.op_8xy7(x as usize, y as usize),
+            Instruction::Shl(x, y) => self.op_8x0e(x as usize, y as usize),
+            Instruction::SneReg(x, y) => self.op_9xy0(x as usize, y as usize),
+            Instruction::LdI(nnn) => self.op_annn(nnn as usize),
+            Instruction::JpV0(x, nnn) => self.op_bnnn(x as usize, nnn as usize),
+            Instruction::Rnd(x, kk) => self.op_cxkk(x as usize, kk),
+            Instruction::Drw(x, y, n) => self.op_dxyn(x as usize, y as usize, n as usize),
+            Instruction::Skp(x) => self.op_ex9e(x as usize),
+            Instruction::Sknp(x) => self.op_exa1(x as usize),
+            Instruction::LdFromDelay(x) => self.op_fx07(x as usize),
+            Instruction::LdKey(x) => self.op_fx0a(x as usize),
+            Instruction::LdDelay(x) => self.op_fx15(x as usize),
+            Instruction::LdSound(x) => self.op_fx18(x as usize),
+            Instruction::AddI(x) => self.op_fx1e(x as usize),
+            Instruction::LdFont(x) => self.op_fx29(x as usize),
+            Instruction::LdBcd(x) => self.op_fx33(x as usize),
+            Instruction::Store(x) => self.op_fx55(x as usize),
+            Instruction::Load(x) => self.op_fx65(x as usize),
+            Instruction::StoreFlags(x) => self.op_fx75(x as usize),
+            Instruction::LoadFlags(x) => self.op_fx85(x as usize),
+            Instruction::Unknown => ProgramCounter::Next,
         };
 
         match pc_change {
@@ -179,7 +659,7 @@ impl Processor {
 
     // CLS: Clear the display.
     fn op_00e0(&mut self) -> ProgramCounter {
-        for y in 0..CHIP8_HEIGHT {
+        for y in 0..self.config.display_height {
             for x in 0..CHIP8_WIDTH {
                 self.vram[y][x] = 0;
             }
@@ -248,20 +728,29 @@ impl Processor {
     // Set Vx = Vx OR Vy.
     fn op_8xy1(&mut self, x: usize, y: usize) -> ProgramCounter {
         self.v[x] |= self.v[y];
+        self.reset_vf();
         ProgramCounter::Next
     }
     // AND Vx, Vy
     // Set Vx = Vx AND Vy.
     fn op_8xy2(&mut self, x: usize, y: usize) -> ProgramCounter {
         self.v[x] &= self.v[y];
+        self.reset_vf();
         ProgramCounter::Next
     }
     // XOR Vx, Vy
     // Set Vx = Vx XOR Vy.
     fn op_8xy3(&mut self, x: usize, y: usize) -> ProgramCounter {
         self.v[x] ^= self.v[y];
+        self.reset_vf();
         ProgramCounter::Next
     }
+    // The VIP's logic operations clobber VF.
+    fn reset_vf(&mut self) {
+        if self.config.quirks.vf_reset {
+            self.v[0x0f] = 0;
+        }
+    }
     // ADD Vx, Vy
     // The values of Vx and Vy are added together. If the result is
     // greater than 8 bits (i.e., > 255,) VF is set to 1, otherwise 0.
@@ -284,7 +773,11 @@ impl Processor {
     // SHR Vx {, Vy}
     // If the least-significant bit of Vx is 1, then VF is set to 1,
     // otherwise 0. Then Vx is divided by 2.
-    fn op_8x06(&mut self, x: usize) -> ProgramCounter {
+    // On the VIP, Vy is copied into Vx first.
+    fn op_8x06(&mut self, x: usize, y: usize) -> ProgramCounter {
+        if !self.config.quirks.shifting {
+            self.v[x] = self.v[y];
+        }
         self.v[0x0f] = self.v[x] & 1;
         self.v[x] >>= 1;
         ProgramCounter::Next
@@ -300,7 +793,11 @@ impl Processor {
     // SHL Vx {, Vy}
     // If the most-significant bit of Vx is 1, then VF is set to 1,
     // otherwise to 0. Then Vx is multiplied by 2.
-    fn op_8x0e(&mut self, x: usize) -> ProgramCounter {
+    // On the VIP, Vy is copied into Vx first.
+    fn op_8x0e(&mut self, x: usize, y: usize) -> ProgramCounter {
+        if !self.config.quirks.shifting {
+            self.v[x] = self.v[y];
+        }
         self.v[0x0f] = (self.v[x] & 0b10000000) >> 7;
         self.v[x] <<= 1;
         ProgramCounter::Next
@@ -318,15 +815,16 @@ impl Processor {
     }
     // JP V0, addr
     // The program counter is set to nnn plus the value of V0.
-    fn op_bnnn(&mut self, nnn: usize) -> ProgramCounter {
-        ProgramCounter::Jump((self.v[0] as usize) + nnn)
+    // SCHIP's version adds Vx instead, where x is the top digit of nnn.
+    fn op_bnnn(&mut self, x: usize, nnn: usize) -> ProgramCounter {
+        let offset = if self.config.quirks.jumping { self.v[x] } else { self.v[0] };
+        ProgramCounter::Jump((offset as usize) + nnn)
     }
     // RND Vx, byte
     // The interpreter generates a random number from 0 to 255,
     // which is then ANDed with the value kk. The results are stored in Vx.
     fn op_cxkk(&mut self, x: usize, kk: u8) -> ProgramCounter {
-        let mut rng = rand::thread_rng();
-        self.v[x] = rng.gen::<u8>() & kk;
+        self.v[x] = self.rng.next_byte() & kk;
         ProgramCounter::Next
     }
     // DRW Vx, Vy, n
@@ -334,22 +832,52 @@ impl Processor {
     // stored in I. These bytes are then displayed as sprites on screen at
     // coordinates (Vx, Vy). Sprites are XORed onto the existing screen.
     // If this causes any pixels to be erased, VF is set to 1, otherwise
-    // it is set to 0. If the sprite is positioned so part of it is outside
-    // the coordinates of the display, it wraps around to the opposite side
-    // of the screen.
+    // it is set to 0.
+    // The starting position always wraps around the screen. Pixels that
+    // then run off an edge either wrap too or are clipped, depending on the
+    // quirks.
     fn op_dxyn(&mut self, x: usize, y: usize, n: usize) -> ProgramCounter {
-        self.v[0x0f] = 0;
+        let quirks = self.config.quirks;
+        let height = self.config.display_height;
+        let count_rows = quirks.row_collisions && height > CHIP8_HEIGHT;
+        let start_x = self.v[x] as usize % CHIP8_WIDTH;
+        let start_y = self.v[y] as usize % height;
+        let mut collided_rows = 0;
         for byte in 0..n {
-            let y = (self.v[y] as usize + byte) % CHIP8_HEIGHT;
+            let mut y = start_y + byte;
+            if y >= height {
+                if quirks.clipping {
+                    if count_rows {
+                        collided_rows += 1;
+                    }
+                    continue;
+                }
+                y %= height;
+            }
+            let mut collided = false;
             for bit in 0..8 {
-                let x = (self.v[x] as usize + bit) % CHIP8_WIDTH;
+                let mut x = start_x + bit;
+                if x >= CHIP8_WIDTH {
+                    if quirks.clipping {
+                        break;
+                    }
+                    x %= CHIP8_WIDTH;
+                }
                 let color = (self.ram[self.i + byte] >> (7 - bit)) & 1;
-                self.v[0x0f] |= color & self.vram[y][x];
+                collided |= color & self.vram[y][x] == 1;
                 self.vram[y][x] ^= color;
-
+            }
+            if collided {
+                collided_rows += 1;
             }
         }
+        self.v[0x0f] = if count_rows {
+            collided_rows
+        } else {
+            (collided_rows > 0) as u8
+        };
         self.vram_changed = true;
+        self.vblank_waiting = quirks.display_wait;
         ProgramCounter::Next
     }
     // SKP Vx
@@ -397,7 +925,7 @@ impl Processor {
     // LD F, Vx
     // Set I = location of sprite for digit Vx.
     fn op_fx29(&mut self, x: usize) -> ProgramCounter {
-        self.i = (self.v[x] as usize) * 5;
+        self.i = self.config.font_base + (self.v[x] as usize) * 5;
         ProgramCounter::Next
     }
 
@@ -409,26 +937,54 @@ impl Processor {
         self.ram[self.i] = self.v[x] / 100;
         self.ram[self.i + 1] = (self.v[x] % 100) / 10;
         self.ram[self.i + 2] = self.v[x] % 10;
+        self.forget_code(self.i, 3);
         ProgramCounter::Next
     }
 
     // LD [I], Vx
     // The interpreter copies the values of registers V0 through Vx
-    // into memory, starting at the address in I.
+    // into memory, starting at the address in I. The VIP leaves I
+    // pointing at the byte after the last one written.
     fn op_fx55(&mut self, x: usize) -> ProgramCounter {
         for i in 0..x + 1 {
             self.ram[self.i + i] = self.v[i];
         }
+        self.forget_code(self.i, x + 1);
+        if self.config.quirks.memory_increment {
+            self.i += x + 1;
+        }
         ProgramCounter::Next
     }
 
     // LD Vx, [I]
     // The interpreter reads values from memory starting at location
-    // I into registers V0 through Vx.
+    // I into registers V0 through Vx. The VIP leaves I pointing at the
+    // byte after the last one read.
     fn op_fx65(&mut self, x: usize) -> ProgramCounter {
         for i in 0..x + 1 {
             self.v[i] = self.ram[self.i + i];
         }
+        if self.config.quirks.memory_increment {
+            self.i += x + 1;
+        }
+        ProgramCounter::Next
+    }
+
+    // LD R, Vx
+    // Store registers V0 through Vx in the user flags. SCHIP only has
+    // eight of them, so anything past the last flag is ignored.
+    fn op_fx75(&mut self, x: usize) -> ProgramCounter {
+        let count = (x + 1).min(self.config.flag_registers);
+        self.flags[..count].copy_from_slice(&self.v[..count]);
+        self.flags_changed = true;
+        ProgramCounter::Next
+    }
+
+    // LD Vx, R
+    // Read registers V0 through Vx from the user flags.
+    fn op_fx85(&mut self, x: usize) -> ProgramCounter {
+        let count = (x + 1).min(self.config.flag_registers);
+        self.v[..count].copy_from_slice(&self.flags[..count]);
         ProgramCounter::Next
     }
 }
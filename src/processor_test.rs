@@ -1,9 +1,16 @@
 use super::*;
+use machine::{Quirks, Region};
+use CHIP8_HEIGHT;
 const START_PC: usize = 0xF00;
 const NEXT_PC: usize = START_PC + OPCODE_SIZE;
 const SKIPPED_PC: usize = START_PC + (2 * OPCODE_SIZE);
 fn build_processor() -> Processor {
-    let mut processor = Processor::new();
+    build_processor_with_quirks(Quirks::legacy())
+}
+fn build_processor_with_quirks(quirks: Quirks) -> Processor {
+    let mut config = MachineConfig::chip8();
+    config.quirks = quirks;
+    let mut processor = Processor::with_config(config);
     processor.pc = START_PC;
     processor.v = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7];
     processor
@@ -27,17 +34,52 @@ fn test_initial_state() {
 #[test]
 fn test_load_data() {
     let mut processor = Processor::new();
-    processor.load(vec![1, 2, 3]);
+    processor.load(&[1, 2, 3]);
     assert_eq!(processor.ram[0x200], 1);
     assert_eq!(processor.ram[0x201], 2);
     assert_eq!(processor.ram[0x202], 3);
 }
+#[test]
+fn test_load_stops_at_reserved_region() {
+    let mut config = MachineConfig::chip8();
+    config.reserved = &[Region { start: 0x000, end: 0x200 }, Region { start: 0x202, end: 0x300 }];
+    let mut processor = Processor::with_config(config);
+    processor.load(&[1, 2, 3]);
+    assert_eq!(processor.ram[0x201], 2);
+    assert_eq!(processor.ram[0x202], 0);
+}
+#[test]
+fn test_eti660_layout() {
+    let mut processor = Processor::with_config(MachineConfig::eti660());
+    processor.load(&[1, 2, 3]);
+    assert_eq!(processor.pc, 0x600);
+    assert_eq!(processor.ram[0x600], 1);
+    assert_eq!(processor.ram[0x200], 0);
+}
+#[test]
+fn test_hires_layout() {
+    let mut processor = Processor::with_config(MachineConfig::hires());
+    processor.load(&[0x12, 0x60]);
+    assert_eq!(processor.pc, 0x2C0);
+    assert_eq!(processor.ram[0x200], 0x12);
+    assert_eq!(processor.vram().len(), VRAM_HEIGHT);
+}
+#[test]
+fn test_font_base() {
+    let mut config = MachineConfig::chip8();
+    config.font_base = 0x50;
+    let mut processor = Processor::with_config(config);
+    assert_eq!(processor.ram[0x50..0x55], [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    processor.v[5] = 2;
+    processor.run_opcode(0xf529);
+    assert_eq!(processor.i, 0x50 + 2 * 5);
+}
 
 // CLS
 #[test]
 fn test_op_00e0() {
     let mut processor = build_processor();
-    processor.vram = [[128; CHIP8_WIDTH]; CHIP8_HEIGHT];
+    processor.vram = [[128; CHIP8_WIDTH]; VRAM_HEIGHT];
     processor.run_opcode(0x00e0);
 
     for y in 0..CHIP8_HEIGHT {
@@ -175,6 +217,15 @@ fn test_op_8x06() {
     // 5 >> 1 == 2 with carry
     check_math(0x05, 0, 6, 0x02, 1);
 }
+// SHR Vx, Vy on the VIP
+#[test]
+fn test_op_8x06_shifts_vy() {
+    let mut processor = build_processor_with_quirks(Quirks::vip());
+    processor.v[1] = 0x05;
+    processor.run_opcode(0x8016);
+    assert_eq!(processor.v[0], 0x02);
+    assert_eq!(processor.v[0x0f], 1);
+}
 // SUBN Vx, Vy
 #[test]
 fn test_op_8xy7() {
@@ -189,6 +240,27 @@ fn test_op_8x0e() {
     check_math(0b00000111, 0, 0x0e, 0b00001110, 0);
 }
 
+// SHL Vx, Vy on the VIP
+#[test]
+fn test_op_8x0e_shifts_vy() {
+    let mut processor = build_processor_with_quirks(Quirks::vip());
+    processor.v[1] = 0b11000000;
+    processor.run_opcode(0x801e);
+    assert_eq!(processor.v[0], 0b10000000);
+    assert_eq!(processor.v[0x0f], 1);
+}
+
+// OR, AND and XOR reset VF on the VIP
+#[test]
+fn test_vf_reset() {
+    for op in 1..4 {
+        let mut processor = build_processor_with_quirks(Quirks::vip());
+        processor.v[0x0f] = 5;
+        processor.run_opcode(0x8010 + op);
+        assert_eq!(processor.v[0x0f], 0);
+    }
+}
+
 // SNE VX, VY
 #[test]
 fn test_op_9xy0() {
@@ -217,6 +289,16 @@ fn test_op_bnnn() {
     assert_eq!(processor.pc, 0x126);
 }
 
+// JP Vx, addr on SCHIP
+#[test]
+fn test_op_bnnn_jumping() {
+    let mut processor = build_processor_with_quirks(Quirks::schip());
+    processor.v[0] = 3;
+    processor.v[1] = 5;
+    processor.run_opcode(0xb123);
+    assert_eq!(processor.pc, 0x128);
+}
+
 // RND Vx, byte
 // Generates random u8, then ANDs it with kk.
 // We can't test randomness, but we can test the AND.
@@ -297,6 +379,117 @@ fn test_op_dxyn_wrap_vertical() {
     assert_eq!(processor.v[0x0f], 0);
 }
 
+// DRW Vx, Vy, nibble
+#[test]
+fn test_op_dxyn_wrap_vertical_tall_display() {
+    let mut processor = Processor::with_config(MachineConfig::two_page());
+    let y = CHIP8_HEIGHT;
+
+    processor.i = 0;
+    processor.ram[0] = 0b11111111;
+    processor.v[0] = 0;
+    processor.v[1] = y as u8;
+    processor.run_opcode(0xd011);
+
+    assert_eq!(processor.vram[y][0], 1);
+    assert_eq!(processor.vram[0][0], 0);
+}
+
+// DRW Vx, Vy, nibble
+#[test]
+fn test_op_dxyn_clipping() {
+    let mut processor = build_processor_with_quirks(Quirks::vip());
+    let x = CHIP8_WIDTH - 4;
+    let y = CHIP8_HEIGHT - 1;
+
+    processor.i = 0;
+    processor.ram[0] = 0b11111111;
+    processor.ram[1] = 0b11111111;
+    processor.v[0] = x as u8;
+    processor.v[1] = y as u8;
+    processor.run_opcode(0xd012);
+
+    assert_eq!(processor.vram[y][x], 1);
+    assert_eq!(processor.vram[y][x + 3], 1);
+    assert_eq!(processor.vram[y][0], 0);
+    assert_eq!(processor.vram[0][x], 0);
+    assert_eq!(processor.vram[0][0], 0);
+}
+
+// DRW Vx, Vy, nibble
+#[test]
+fn test_op_dxyn_clipping_wraps_start() {
+    let mut processor = build_processor_with_quirks(Quirks::vip());
+
+    processor.i = 0;
+    processor.ram[0] = 0b10000000;
+    processor.v[0] = (CHIP8_WIDTH + 2) as u8;
+    processor.v[1] = (CHIP8_HEIGHT + 3) as u8;
+    processor.run_opcode(0xd011);
+
+    assert_eq!(processor.vram[3][2], 1);
+}
+
+// DRW Vx, Vy, nibble
+#[test]
+fn test_op_dxyn_row_collisions() {
+    let mut config = MachineConfig::two_page();
+    config.quirks = Quirks::schip();
+    let mut processor = Processor::with_config(config);
+    let y = VRAM_HEIGHT - 3;
+
+    processor.i = 0;
+    processor.ram[0] = 0b10000000;
+    processor.ram[1] = 0b10000000;
+    processor.ram[2] = 0b10000000;
+    processor.ram[3] = 0b10000000;
+    processor.vram[y][0] = 1;
+    processor.vram[y + 2][0] = 1;
+    processor.v[0] = 0;
+    processor.v[1] = y as u8;
+    processor.run_opcode(0xd014);
+
+    // Two rows collided and one was clipped off the bottom.
+    assert_eq!(processor.v[0x0f], 3);
+}
+
+// DRW Vx, Vy, nibble
+#[test]
+fn test_op_dxyn_row_collisions_low_res() {
+    let mut processor = build_processor_with_quirks(Quirks::schip());
+
+    processor.i = 0;
+    processor.ram[0] = 0b10000000;
+    processor.ram[1] = 0b10000000;
+    processor.vram[0][0] = 1;
+    processor.vram[1][0] = 1;
+    processor.v[0] = 0;
+    processor.v[1] = 0;
+    processor.run_opcode(0xd012);
+
+    assert_eq!(processor.v[0x0f], 1);
+}
+
+#[test]
+fn test_display_wait() {
+    let mut processor = Processor::with_config(MachineConfig {
+        quirks: Quirks::vip(),
+        ..MachineConfig::chip8()
+    });
+    processor.load(&[
+        0xD0, 0x01, // DRW V0, V0, 1
+        0x70, 0x01, // ADD V0, 1
+    ]);
+
+    processor.tick([false; 16]);
+    for _ in 1..CYCLES_PER_FRAME {
+        processor.tick([false; 16]);
+        assert_eq!(processor.v[0], 0);
+    }
+    processor.tick([false; 16]);
+    assert_eq!(processor.v[0], 1);
+}
+
 
 // SKP Vx
 #[test]
@@ -450,6 +643,17 @@ fn test_op_fx65() {
 
 }
 
+// FX55 and FX65 move I on the VIP
+#[test]
+fn test_memory_increment() {
+    let mut processor = build_processor_with_quirks(Quirks::vip());
+    processor.i = 1000;
+    processor.run_opcode(0xf355);
+    assert_eq!(processor.i, 1004);
+    processor.run_opcode(0xf265);
+    assert_eq!(processor.i, 1007);
+}
+
 #[test]
 fn test_timers() {
     let mut processor = build_processor();
@@ -459,3 +663,243 @@ fn test_timers() {
     assert_eq!(processor.delay_timer, 199);
     assert_eq!(processor.sound_timer, 99);
 }
+
+#[test]
+fn test_tick_timers() {
+    let mut processor = build_processor();
+    processor.delay_timer = 1;
+    processor.sound_timer = 2;
+    let pc = processor.pc;
+    processor.tick_timers();
+    processor.tick_timers();
+    assert_eq!(processor.delay_timer, 0);
+    assert_eq!(processor.sound_timer, 0);
+    assert_eq!(processor.pc, pc);
+}
+
+#[test]
+fn test_manual_timers() {
+    for &backend in &[Backend::Interpreter, Backend::Blocks] {
+        let mut processor = Processor::new();
+        processor.set_backend(backend);
+        processor.set_manual_timers(true);
+        processor.load(&[0x12, 0x00]); // JP 0x200
+        processor.sound_timer = 2;
+        processor.run(16, [false; 16]);
+        assert_eq!(processor.sound_timer, 2);
+        processor.tick_timers();
+        assert_eq!(processor.sound_timer, 1);
+    }
+}
+
+// LD R, Vx
+#[test]
+fn test_op_fx75() {
+    let mut processor = build_processor();
+    processor.run_opcode(0xff75);
+    assert_eq!(processor.flags(), &[0, 0, 1, 1, 2, 2, 3, 3]);
+    assert_eq!(processor.flags[8], 0);
+    assert!(processor.flags_changed);
+    assert_eq!(processor.pc, NEXT_PC);
+}
+
+// LD R, Vx with XO-CHIP's 16 flags
+#[test]
+fn test_op_fx75_sixteen_flags() {
+    let mut config = MachineConfig::chip8();
+    config.flag_registers = 16;
+    let mut processor = Processor::with_config(config);
+    processor.v = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7];
+    processor.run_opcode(0xff75);
+    assert_eq!(processor.flags(), &processor.v[..]);
+}
+
+// LD Vx, R
+#[test]
+fn test_op_fx85() {
+    let mut processor = build_processor();
+    processor.set_flags(&[9, 8, 7, 6, 5, 4, 3, 2, 1, 0]);
+    processor.run_opcode(0xf285);
+    assert_eq!(processor.v[0..4], [9, 8, 7, 1]);
+    assert_eq!(processor.flags[8], 0);
+    assert!(!processor.flags_changed);
+    assert_eq!(processor.pc, NEXT_PC);
+}
+
+#[test]
+fn test_tracing() {
+    let mut processor = Processor::new();
+    processor.load(&[
+        0x60, 0x05, // LD V0, 5
+        0xA3, 0x00, // LD I, 0x300
+    ]);
+    assert!(processor.tick([false; 16]).trace.is_none());
+
+    processor.set_tracing(true);
+    let entry = processor.tick([false; 16]).trace.unwrap();
+    assert_eq!(entry.pc, 0x202);
+    assert_eq!(entry.opcode, 0xA300);
+    assert_eq!(entry.before.v[0], 5);
+    assert_eq!(entry.before.i, 0);
+    assert_eq!(entry.after.i, 0x300);
+}
+fn run_self_modifying(program: &[u8], backend: Backend) -> u8 {
+    let mut processor = Processor::new();
+    processor.set_backend(backend);
+    processor.load(program);
+    for _ in 0..10 {
+        processor.tick([false; 16]);
+    }
+    assert_eq!(processor.pc, 0x20C);
+    processor.v[2]
+}
+#[test]
+fn test_decode_cache_sees_fx55() {
+    let program = [
+        0x22, 0x10, // CALL 0x210
+        0xA2, 0x10, // LD I, 0x210
+        0x60, 0x62, // LD V0, 0x62
+        0x61, 0x07, // LD V1, 0x07
+        0xF1, 0x55, // LD [I], V1: 0x210 becomes LD V2, 7
+        0x22, 0x10, // CALL 0x210
+        0x12, 0x0C, // JP 0x20C
+        0x00, 0x00,
+        0x62, 0x05, // LD V2, 5
+        0x00, 0xEE, // RET
+    ];
+    assert_eq!(run_self_modifying(&program, Backend::DecodeCache), 7);
+    assert_eq!(run_self_modifying(&program, Backend::Interpreter), 7);
+}
+#[test]
+fn test_decode_cache_sees_fx33() {
+    let program = [
+        0x22, 0x10, // CALL 0x210
+        0xA2, 0x0F, // LD I, 0x20F
+        0x60, 0x07, // LD V0, 7
+        0xF0, 0x33, // LD B, V0: 0x210 becomes 0x0007, a no-op
+        0x62, 0x00, // LD V2, 0
+        0x22, 0x10, // CALL 0x210
+        0x12, 0x0C, // JP 0x20C
+        0x00, 0x00,
+        0x62, 0x05, // LD V2, 5
+        0x00, 0xEE, // RET
+    ];
+    assert_eq!(run_self_modifying(&program, Backend::DecodeCache), 0);
+    assert_eq!(run_self_modifying(&program, Backend::Interpreter), 0);
+}
+#[test]
+fn test_decode_cache_sees_ram_mut() {
+    let mut processor = Processor::new();
+    processor.load(&[
+        0x70, 0x01, // ADD V0, 1
+        0x12, 0x00, // JP 0x200
+    ]);
+    processor.tick([false; 16]);
+    processor.tick([false; 16]);
+    processor.ram_mut()[0x201] = 0x10;
+    processor.tick([false; 16]);
+    assert_eq!(processor.v[0], 0x11);
+}
+#[test]
+fn test_write_ram() {
+    for &backend in &[Backend::DecodeCache, Backend::Blocks] {
+        let mut processor = Processor::new();
+        processor.set_backend(backend);
+        processor.load(&[
+            0x70, 0x01, // ADD V0, 1
+            0x12, 0x00, // JP 0x200
+        ]);
+        processor.run(4, [false; 16]);
+        processor.write_ram(0x201, 0x10);
+        processor.run(2, [false; 16]);
+        assert_eq!(processor.v[0], 0x12);
+        assert_eq!(processor.ram()[0x201], 0x10);
+    }
+}
+// Draws a random sprite at a random place, over and over.
+const RANDOM_SPRITES: [u8; 12] = [
+    0xC0, 0x3F, // RND V0, 0x3F
+    0xC1, 0x1F, // RND V1, 0x1F
+    0xC2, 0x0F, // RND V2, 0x0F
+    0xF2, 0x29, // LD F, V2
+    0xD0, 0x15, // DRW V0, V1, 5
+    0x12, 0x00, // JP 0x200
+];
+#[test]
+fn test_seed_rng() {
+    let run = |seed| {
+        let mut processor = Processor::new();
+        processor.seed_rng(seed);
+        processor.load(&RANDOM_SPRITES);
+        processor.run(500, [false; 16]);
+        processor.vram().to_vec()
+    };
+    assert!(run(1) == run(1));
+    assert!(run(1) != run(2));
+}
+#[test]
+fn test_save_and_load_state() {
+    let mut processor = Processor::new();
+    processor.load(&RANDOM_SPRITES);
+    processor.run(301, [false; 16]);
+    processor.sound_timer = 9;
+    processor.set_flags(&[1, 2, 3]);
+    let state = processor.save_state();
+
+    let mut restored = Processor::new();
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.save_state(), state);
+    for _ in 0..200 {
+        processor.tick([false; 16]);
+        restored.tick([false; 16]);
+    }
+    assert_eq!(restored.save_state(), processor.save_state());
+    assert!(restored.vram() == processor.vram());
+}
+#[test]
+fn test_load_state_forgets_code() {
+    let mut processor = Processor::new();
+    processor.load(&[0x60, 0x01, 0x12, 0x00]); // LD V0, 1; JP 0x200
+    let state = processor.save_state();
+    processor.load(&[0x60, 0x02]);
+    processor.tick([false; 16]);
+    processor.load_state(&state).unwrap();
+    processor.tick([false; 16]);
+    assert_eq!(processor.v[0], 1);
+}
+#[test]
+fn test_load_bad_state() {
+    let mut processor = Processor::new();
+    let state = processor.save_state();
+    assert_eq!(processor.load_state(b"nonsense"), Err("not a save state".to_string()));
+    assert_eq!(
+        processor.load_state(&state[..state.len() - 1]),
+        Err("save state is the wrong size".to_string())
+    );
+    assert_eq!(
+        Processor::with_config(MachineConfig::eti660()).load_state(&state),
+        Err("save state is for the chip8 machine, not eti660".to_string())
+    );
+    let mut newer = state.clone();
+    newer[4] = 2;
+    assert_eq!(processor.load_state(&newer), Err("unsupported save state version 2".to_string()));
+
+    // The stack comes after RAM and VRAM, then V0 to VF and I.
+    let stack = state.len() - (32 + 16 + 4 + 7 + 16 + 16);
+    let mut bad_stack = state.clone();
+    bad_stack[stack + 30] = 0xFF;
+    assert_eq!(processor.load_state(&bad_stack), Err("save state's stack points outside RAM".to_string()));
+    let mut bad_i = state.clone();
+    bad_i[stack + 48] = 0x10;
+    assert_eq!(processor.load_state(&bad_i), Err("save state's I points outside RAM".to_string()));
+    assert_eq!(processor.registers().i, 0);
+}
+#[test]
+fn test_memory_access_overlaps() {
+    let access = MemoryAccess::of(0xF233, 0x300).unwrap();
+    assert!(access.overlaps(0x302, 1));
+    assert!(!access.overlaps(0x303, 1));
+    assert!(access.overlaps(0x2FF, usize::MAX));
+    let access = MemoryAccess { start: usize::MAX, len: 2, write: false };
+    assert!(!access.overlaps(0, 1));
+}
@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use disassembler::disassemble;
+use processor::MemoryAccess;
+use trace::TraceEntry;
+use CHIP8_RAM;
+
+// How many hot loops the report lists.
+const HOT_LOOPS: usize = 10;
+// Bytes per row of the coverage map.
+const COVERAGE_ROW: usize = 32;
+
+#[derive(Clone, Copy, Default)]
+struct Subroutine {
+    calls: u64,
+    // Instructions run by the subroutine and everything it calls.
+    inclusive: u64,
+    // Instructions run by the subroutine itself.
+    exclusive: u64,
+}
+
+// Builds an execution profile from the instructions a processor reports
+// while tracing. Besides counting how often each address runs, it follows
+// CALL and RET to attribute instructions to subroutines, treats every
+// backward jump as a loop, and notes which bytes instructions read or
+// write as data.
+pub struct Profiler {
+    load_address: usize,
+    rom_size: usize,
+    total: u64,
+    counts: Vec<u64>,
+    opcodes: Vec<u16>,
+    data: Vec<bool>,
+    subroutines: BTreeMap<u16, Subroutine>,
+    calls: BTreeMap<(u16, u16), u64>,
+    // Iterations of each loop, keyed by the first and last address.
+    loops: BTreeMap<(u16, u16), u64>,
+    // The subroutines we're in, innermost last. The first entry is the
+    // address execution started at.
+    stack: Vec<u16>,
+    last_jump: Option<u16>,
+}
+
+impl Profiler {
+    // The ROM occupies rom_size bytes from load_address. Only those bytes
+    // appear in the coverage map.
+    pub fn new(load_address: usize, rom_size: usize) -> Self {
+        Profiler {
+            load_address,
+            rom_size,
+            total: 0,
+            counts: vec![0; CHIP8_RAM],
+            opcodes: vec![0; CHIP8_RAM],
+            data: vec![false; CHIP8_RAM],
+            subroutines: BTreeMap::new(),
+            calls: BTreeMap::new(),
+            loops: BTreeMap::new(),
+            stack: Vec::new(),
+            last_jump: None,
+        }
+    }
+
+    // Forgets the call stack, for when the processor is reset. The counts
+    // carry on accumulating.
+    pub fn restart(&mut self) {
+        self.stack.clear();
+        self.last_jump = None;
+    }
+
+    pub fn record(&mut self, entry: &TraceEntry) {
+        let pc = entry.pc as usize;
+        self.total += 1;
+        self.counts[pc] += 1;
+        self.opcodes[pc] = entry.opcode;
+
+        if let Some(from) = self.last_jump.take() {
+            if entry.pc <= from {
+                *self.loops.entry((entry.pc, from)).or_insert(0) += 1;
+            }
+        }
+        if self.stack.is_empty() {
+            self.stack.push(entry.pc);
+            self.subroutines.entry(entry.pc).or_default().calls += 1;
+        }
+
+        let current = self.stack[self.stack.len() - 1];
+        self.subroutines.entry(current).or_default().exclusive += 1;
+        for (depth, &address) in self.stack.iter().enumerate() {
+            // Count recursive subroutines once.
+            if !self.stack[..depth].contains(&address) {
+                self.subroutines.entry(address).or_default().inclusive += 1;
+            }
+        }
+
+        let nnn = entry.opcode & 0x0FFF;
+        match entry.opcode & 0xF000 {
+            0x1000 | 0xB000 => self.last_jump = Some(entry.pc),
+            0x2000 => {
+                *self.calls.entry((current, nnn)).or_insert(0) += 1;
+                self.subroutines.entry(nnn).or_default().calls += 1;
+                self.stack.push(nnn);
+            }
+            _ if entry.opcode == 0x00EE && self.stack.len() > 1 => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+        if let Some(access) = MemoryAccess::of(entry.opcode, entry.before.i as usize) {
+            let end = (access.start + access.len).min(CHIP8_RAM);
+            for addr in access.start..end {
+                self.data[addr] = true;
+            }
+        }
+    }
+
+    // The report followed by the coverage map.
+    pub fn summary(&self) -> String {
+        format!("{}\n{}", self.report(), self.coverage())
+    }
+
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let total = self.total;
+        let percent = |count: u64| 100.0 * count as f64 / total.max(1) as f64;
+        let entry_point = self.stack.first().cloned();
+
+        writeln!(out, "{} instructions executed", total).unwrap();
+
+        writeln!(out, "\nSubroutines").unwrap();
+        writeln!(out, "  address  calls  inclusive          self").unwrap();
+        let mut subroutines: Vec<_> = self.subroutines.iter().collect();
+        subroutines.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(b.0)));
+        for (&address, subroutine) in subroutines {
+            writeln!(
+                out,
+                "  0x{:03X} {:>7} {:>10} {:5.1}% {:>7} {:5.1}%{}",
+                address,
+                subroutine.calls,
+                subroutine.inclusive,
+                percent(subroutine.inclusive),
+                subroutine.exclusive,
+                percent(subroutine.exclusive),
+                if Some(address) == entry_point { "  (entry)" } else { "" }
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "\nCalls").unwrap();
+        for (&(caller, callee), &count) in &self.calls {
+            writeln!(out, "  0x{:03X} -> 0x{:03X} {:>7}", caller, callee, count).unwrap();
+        }
+
+        writeln!(out, "\nHot loops").unwrap();
+        writeln!(out, "  addresses    iterations  instructions").unwrap();
+        let mut loops: Vec<_> = self
+            .loops
+            .iter()
+            .map(|(&(start, end), &iterations)| {
+                let body = self.counts[start as usize..=end as usize].iter().sum::<u64>();
+                (start, end, iterations, body)
+            })
+            .collect();
+        loops.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+        for &(start, end, iterations, body) in loops.iter().take(HOT_LOOPS) {
+            writeln!(
+                out,
+                "  0x{:03X}-0x{:03X} {:>10} {:>13} {:5.1}%",
+                start,
+                end,
+                iterations,
+                body,
+                percent(body)
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "\nInstructions").unwrap();
+        for (address, &count) in self.counts.iter().enumerate() {
+            if count > 0 {
+                writeln!(
+                    out,
+                    "  0x{:03X}  {:04X}  {:<16} {:>10} {:5.1}%",
+                    address,
+                    self.opcodes[address],
+                    disassemble(self.opcodes[address]),
+                    count,
+                    percent(count)
+                )
+                .unwrap();
+            }
+        }
+        out
+    }
+
+    // A map of the ROM with one character per byte: X for bytes that were
+    // executed, d for bytes only read or written as data, and . for bytes
+    // nothing touched. An instruction marks both of its bytes.
+    pub fn coverage(&self) -> String {
+        let start = self.load_address;
+        let end = (start + self.rom_size).min(CHIP8_RAM);
+        let executed = |address: usize| {
+            self.counts[address] > 0 || (address > 0 && self.counts[address - 1] > 0)
+        };
+        let cells: Vec<char> = (start..end)
+            .map(|address| {
+                if executed(address) {
+                    'X'
+                } else if self.data[address] {
+                    'd'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let code = cells.iter().filter(|&&cell| cell == 'X').count();
+        let data = cells.iter().filter(|&&cell| cell == 'd').count();
+
+        let mut map = String::new();
+        for (row, chunk) in cells.chunks(COVERAGE_ROW).enumerate() {
+            write!(map, "{:03X} ", start + row * COVERAGE_ROW).unwrap();
+            map.extend(chunk);
+            map.push('\n');
+        }
+
+        format!(
+            "Coverage of {} bytes from 0x{:03X}: {} executed, {} read or written as data, {} never touched\n{}",
+            end - start,
+            start,
+            code,
+            data,
+            end - start - code - data,
+            map
+        )
+    }
+}
+
+#[cfg(test)]
+#[path = "./profiler_test.rs"]
+mod profiler_test;
@@ -0,0 +1,66 @@
+use super::*;
+use processor::Processor;
+const PROGRAM: [u8; 20] = [
+    0x60, 0x03, // LD V0, 3
+    0x22, 0x0C, // loop: CALL draw
+    0x70, 0xFF, // ADD V0, 0xFF
+    0x30, 0x00, // SE V0, 0
+    0x12, 0x02, // JP loop
+    0x12, 0x0A, // halt: JP halt
+    0xA2, 0x12, // draw: LD I, sprite
+    0xD0, 0x01, // DRW V0, V0, 1
+    0x00, 0xEE, // RET
+    0x80, 0x00, // sprite
+];
+fn profile(ticks: usize) -> Profiler {
+    let mut processor = Processor::new();
+    processor.load(&PROGRAM);
+    processor.set_tracing(true);
+    let mut profiler = Profiler::new(0x200, PROGRAM.len());
+    for _ in 0..ticks {
+        profiler.record(&processor.tick([false; 16]).trace.unwrap());
+    }
+    profiler
+}
+#[test]
+fn test_counts() {
+    let profiler = profile(24);
+    assert_eq!(profiler.total, 24);
+    assert_eq!(profiler.counts[0x200], 1);
+    assert_eq!(profiler.counts[0x202], 3);
+    assert_eq!(profiler.counts[0x208], 2);
+    assert_eq!(profiler.counts[0x20A], 3);
+}
+#[test]
+fn test_subroutines() {
+    let profiler = profile(24);
+    let main = profiler.subroutines[&0x200];
+    assert_eq!((main.calls, main.inclusive, main.exclusive), (1, 24, 15));
+    let draw = profiler.subroutines[&0x20C];
+    assert_eq!((draw.calls, draw.inclusive, draw.exclusive), (3, 9, 9));
+    assert_eq!(profiler.calls[&(0x200, 0x20C)], 3);
+}
+#[test]
+fn test_loops() {
+    let profiler = profile(24);
+    assert_eq!(profiler.loops[&(0x202, 0x208)], 2);
+    assert_eq!(profiler.loops[&(0x20A, 0x20A)], 2);
+    assert_eq!(profiler.loops.len(), 2);
+}
+#[test]
+fn test_report() {
+    let report = profile(24).report();
+    assert!(report.starts_with("24 instructions executed\n"));
+    assert!(report.contains("  0x200       1         24 100.0%      15  62.5%  (entry)\n"));
+    assert!(report.contains("  0x200 -> 0x20C       3\n"));
+    assert!(report.contains("  0x202-0x208          2            11  45.8%\n"));
+    assert!(report.contains("  0x20E  D001  DRW V0, V0, 1             3  12.5%\n"));
+}
+#[test]
+fn test_coverage() {
+    assert_eq!(
+        profile(24).coverage(),
+        "Coverage of 20 bytes from 0x200: 18 executed, 1 read or written as data, 1 never touched\n\
+         200 XXXXXXXXXXXXXXXXXXd.\n"
+    );
+}
@@ -0,0 +1,58 @@
+// Where CXKK gets its random bytes. Processors come with XorShift, which is
+// seedable, so runs can be repeated, and small enough to go in a save
+// state. A port to a device with a hardware random number generator can
+// plug that in instead with Processor::with_rng.
+pub trait Random {
+    fn next_byte(&mut self) -> u8;
+}
+
+// Marsaglia's xorshift128.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XorShift {
+    // Never all zeros, or it would only ever produce zeros.
+    state: [u32; 4],
+}
+
+impl XorShift {
+    // The same seed always gives the same numbers.
+    pub fn new(seed: u64) -> Self {
+        // Mixing in constants keeps the state from being all zeros: the
+        // first and third words can't both be.
+        let mixed = seed ^ 0x9E37_79B9_7F4A_7C15;
+        XorShift {
+            state: [
+                mixed as u32,
+                (mixed >> 32) as u32,
+                seed as u32 ^ 0x85EB_CA6B,
+                (seed >> 32) as u32 ^ 0xC2B2_AE35,
+            ],
+        }
+    }
+
+    pub fn state(&self) -> [u32; 4] {
+        self.state
+    }
+
+    // Picks up where a generator with this state left off.
+    pub fn from_state(state: [u32; 4]) -> Self {
+        if state == [0; 4] {
+            XorShift { state: [1, 0, 0, 0] }
+        } else {
+            XorShift { state }
+        }
+    }
+}
+
+impl Random for XorShift {
+    fn next_byte(&mut self) -> u8 {
+        let [x, y, z, w] = self.state;
+        let t = x ^ (x << 11);
+        let next = w ^ (w >> 19) ^ (t ^ (t >> 8));
+        self.state = [y, z, w, next];
+        next as u8
+    }
+}
+
+#[cfg(test)]
+#[path = "./random_test.rs"]
+mod random_test;
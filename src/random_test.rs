@@ -0,0 +1,27 @@
+use super::*;
+
+#[test]
+fn test_same_seed_same_bytes() {
+    let mut a = XorShift::new(7);
+    let mut b = XorShift::new(7);
+    let mut c = XorShift::new(8);
+    let bytes_a: Vec<u8> = (0..32).map(|_| a.next_byte()).collect();
+    let bytes_b: Vec<u8> = (0..32).map(|_| b.next_byte()).collect();
+    let bytes_c: Vec<u8> = (0..32).map(|_| c.next_byte()).collect();
+    assert_eq!(bytes_a, bytes_b);
+    assert_ne!(bytes_a, bytes_c);
+}
+
+#[test]
+fn test_from_state_carries_on() {
+    let mut rng = XorShift::new(0);
+    rng.next_byte();
+    let mut copy = XorShift::from_state(rng.state());
+    assert_eq!(copy.next_byte(), rng.next_byte());
+}
+
+#[test]
+fn test_zero_state_isnt_stuck() {
+    let mut rng = XorShift::from_state([0; 4]);
+    assert!((0..8).any(|_| rng.next_byte() != 0));
+}
@@ -0,0 +1,168 @@
+use std::ops::Range;
+
+use instruction::Instruction;
+use CHIP8_RAM;
+
+// Blocks stop after this many instructions even without a branch, so a
+// long run of straight-line code doesn't make one huge block.
+const MAX_BLOCK_LEN: usize = 64;
+
+// A run of instructions with one way in and one way out, translated ahead
+// of time so they can run back to back without fetching or decoding. Only
+// the last instruction can jump, skip, call or return, draw (which may wait
+// for the display) or wait for a key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    // The address just past the last instruction.
+    pub end: usize,
+    pub instructions: Vec<Instruction>,
+}
+
+// Does the instruction end a block? Anything that changes the program
+// counter other than by moving on to the next instruction does, and so do
+// instructions that can leave the processor waiting.
+pub fn ends_block(instruction: &Instruction) -> bool {
+    matches!(
+        *instruction,
+        Instruction::Ret
+        | Instruction::Jp(_)
+        | Instruction::Call(_)
+        | Instruction::SeByte(..)
+        | Instruction::SneByte(..)
+        | Instruction::SeReg(..)
+        | Instruction::SneReg(..)
+        | Instruction::JpV0(..)
+        | Instruction::Drw(..)
+        | Instruction::Skp(_)
+        | Instruction::Sknp(_)
+        | Instruction::LdKey(_)
+    )
+}
+
+// Translates the block starting at the address.
+pub fn translate(ram: &[u8], start: usize) -> Block {
+    let mut instructions = Vec::new();
+    let mut addr = start;
+    while addr + 1 < ram.len() && instructions.len() < MAX_BLOCK_LEN {
+        let instruction = Instruction::decode((ram[addr] as u16) << 8 | ram[addr + 1] as u16);
+        instructions.push(instruction);
+        addr += 2;
+        if ends_block(&instruction) {
+            break;
+        }
+    }
+    Block {
+        start,
+        end: addr,
+        instructions,
+    }
+}
+
+// Once this many instructions have been translated, everything is thrown
+// away and translated again, so code that keeps rewriting itself doesn't
+// use ever more memory.
+const MAX_CODE: usize = 1 << 16;
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    // Where the block's instructions start in BlockCache::code.
+    code: usize,
+    len: usize,
+    end: usize,
+}
+
+// Translated blocks, by start address. Blocks can overlap, since a block
+// can be entered part way through when a jump lands there. Writing to
+// memory a block was translated from throws the block away.
+//
+// The instructions of every block live one after another in one vector,
+// and a block is a range of it, so running a block needs nothing more than
+// indexing.
+#[derive(Clone)]
+pub struct BlockCache {
+    entries: Vec<Option<Entry>>,
+    code: Vec<Instruction>,
+    // How many blocks each byte of memory belongs to, so writes to memory
+    // with no code in it are cheap.
+    coverage: Vec<u32>,
+    // Counts invalidations, so code running a block can tell if the block
+    // it's running was thrown away.
+    generation: u64,
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        BlockCache::new()
+    }
+}
+
+impl BlockCache {
+    pub fn new() -> Self {
+        BlockCache {
+            entries: vec![None; CHIP8_RAM],
+            code: Vec::new(),
+            coverage: vec![0; CHIP8_RAM],
+            generation: 0,
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // The range of code holding the block starting at the address,
+    // translating it first if need be. The block is empty if there's no
+    // room for an instruction there.
+    pub fn lookup(&mut self, ram: &[u8], start: usize) -> Range<usize> {
+        let entry = match self.entries[start] {
+            Some(entry) => entry,
+            None => {
+                let block = translate(ram, start);
+                if self.code.len() + block.instructions.len() > MAX_CODE {
+                    self.invalidate(0, CHIP8_RAM);
+                    self.code.clear();
+                }
+                for count in &mut self.coverage[block.start..block.end] {
+                    *count += 1;
+                }
+                let entry = Entry {
+                    code: self.code.len(),
+                    len: block.instructions.len(),
+                    end: block.end,
+                };
+                self.code.extend(block.instructions);
+                self.entries[start] = Some(entry);
+                entry
+            }
+        };
+        entry.code..entry.code + entry.len
+    }
+
+    pub fn instruction(&self, index: usize) -> Instruction {
+        self.code[index]
+    }
+
+    // Throws away every block containing any of the len bytes from start.
+    pub fn invalidate(&mut self, start: usize, len: usize) {
+        let end = (start + len).min(CHIP8_RAM);
+        if self.coverage[start.min(end)..end].iter().all(|&count| count == 0) {
+            return;
+        }
+        self.generation += 1;
+        for addr in 0..CHIP8_RAM {
+            if let Some(entry) = self.entries[addr] {
+                if addr < end && start < entry.end {
+                    self.entries[addr] = None;
+                    for count in &mut self.coverage[addr..entry.end] {
+                        *count -= 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./recompiler_test.rs"]
+mod recompiler_test;
@@ -0,0 +1,214 @@
+use super::*;
+use machine::{MachineConfig, Quirks};
+use processor::{Backend, Processor};
+use rand::{Rng, SeedableRng, XorShiftRng};
+#[test]
+fn test_translate_stops_at_branch() {
+    let ram = [
+        0x60, 0x01, // LD V0, 1
+        0x70, 0x01, // ADD V0, 1
+        0x30, 0x03, // SE V0, 3
+        0x12, 0x00, // JP 0x000
+    ];
+    let block = translate(&ram, 0);
+    assert_eq!(block.end, 6);
+    assert_eq!(
+        block.instructions,
+        vec![Instruction::LdByte(0, 1), Instruction::AddByte(0, 1), Instruction::SeByte(0, 3)]
+    );
+    assert_eq!(translate(&ram, 6).instructions, vec![Instruction::Jp(0x200)]);
+}
+#[test]
+fn test_translate_limits() {
+    let ram = [0x70; 4096];
+    assert_eq!(translate(&ram, 0).instructions.len(), MAX_BLOCK_LEN);
+    let block = translate(&ram, 4094);
+    assert_eq!(block.end, 4096);
+    assert_eq!(translate(&ram, 4095).instructions, vec![]);
+}
+#[test]
+fn test_invalidate() {
+    let mut ram = [0u8; 4096];
+    ram[0x200..0x206].copy_from_slice(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x00]);
+    let mut cache = BlockCache::new();
+    let code = cache.lookup(&ram, 0x200);
+    assert_eq!(code.len(), 3);
+    assert_eq!(cache.lookup(&ram, 0x202).len(), 2);
+
+    // Writes elsewhere leave the blocks alone.
+    let generation = cache.generation();
+    cache.invalidate(0x206, 10);
+    assert_eq!(cache.generation(), generation);
+    assert_eq!(cache.lookup(&ram, 0x200), code);
+
+    ram[0x201] = 0x05;
+    cache.invalidate(0x201, 1);
+    assert!(cache.generation() > generation);
+    let code = cache.lookup(&ram, 0x200);
+    assert_eq!(cache.instruction(code.start), Instruction::LdByte(0, 5));
+}
+
+// Everything about a processor that a program can see.
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    pc: usize,
+    sp: usize,
+    stack: Vec<usize>,
+    registers: ::trace::Registers,
+    ram: Vec<u8>,
+    vram: Vec<Vec<u8>>,
+    flags: Vec<u8>,
+}
+fn snapshot(processor: &Processor) -> Snapshot {
+    Snapshot {
+        pc: processor.pc(),
+        sp: processor.sp(),
+        stack: processor.stack().to_vec(),
+        registers: processor.registers(),
+        ram: processor.ram().to_vec(),
+        vram: processor.vram().iter().map(|row| row.to_vec()).collect(),
+        flags: processor.flags().to_vec(),
+    }
+}
+// Runs the program with the interpreter, a tick at a time, and with
+// translated blocks, in randomly sized batches, with the same keys held at
+// every cycle. They must agree after every batch.
+fn check_equivalent(name: &str, program: &[u8], quirks: Quirks, rng: &mut XorShiftRng, cycles: u32) {
+    let mut config = MachineConfig::chip8();
+    config.quirks = quirks;
+    let mut interpreter = Processor::with_config(config.clone());
+    interpreter.set_backend(Backend::Interpreter);
+    interpreter.load(program);
+    let mut blocks = Processor::with_config(config);
+    blocks.set_backend(Backend::Blocks);
+    blocks.load(program);
+
+    let mut cycle = 0;
+    while cycle < cycles {
+        let batch = rng.gen_range(1, 200);
+        let mut keypad = [false; 16];
+        if rng.gen_weighted_bool(3) {
+            keypad[rng.gen_range(0, 16)] = true;
+        }
+
+        let mut vram_changed = false;
+        let mut flags_changed = false;
+        let mut beep = false;
+        for _ in 0..batch {
+            let output = interpreter.tick(keypad);
+            vram_changed |= output.vram_changed;
+            flags_changed |= output.flags_changed;
+            beep = output.beep;
+        }
+        let output = blocks.run(batch, keypad);
+        cycle += batch;
+
+        let context = format!("{} with {} quirks, after {} cycles", name, quirks.name, cycle);
+        assert_eq!(snapshot(&blocks), snapshot(&interpreter), "{}", context);
+        assert_eq!(output.vram_changed, vram_changed, "{}", context);
+        assert_eq!(output.flags_changed, flags_changed, "{}", context);
+        assert_eq!(output.beep, beep, "{}", context);
+    }
+}
+
+const PROGRAM_START: usize = 0x200;
+// A LD VA, kk followed by a no-op, which programs rewrite with FX33. The
+// three digits written from the kk byte on always make a LD and a no-op.
+const PATCH_SITE: usize = PROGRAM_START;
+// Where programs keep data, out of the way of the code.
+const DATA: u16 = 0x600;
+
+// Generates a random program that can't crash the interpreter: no stack
+// underflow or overflow, no jumps out of the program, and no keys outside
+// the keypad. It's built from short units, and jumps only land at the start
+// of one. The program loops forever. The subroutine it calls comes after the
+// loop.
+fn random_program(rng: &mut XorShiftRng) -> Vec<u8> {
+    let units = rng.gen_range(4, 40);
+    let mut program: Vec<Vec<u16>> = Vec::new();
+    let mut starts = Vec::new();
+    // The patch site, then the units, then a jump back to the start and
+    // the subroutine.
+    let mut addr = PATCH_SITE + 4;
+    // Where jumps are, and which unit they go to or None for a call.
+    let mut jumps = Vec::new();
+    for _ in 0..units {
+        let x = rng.gen_range(0, 16) as u16;
+        let y = rng.gen_range(0, 16) as u16;
+        let kk = rng.gen::<u8>() as u16;
+        let n = rng.gen_range(0, 16) as u16;
+        let data = DATA + rng.gen_range(0, 0xF0);
+        let unit = match rng.gen_range(0, 18) {
+            0 => vec![0x6000 | x << 8 | kk],
+            1 => vec![0x7000 | x << 8 | kk],
+            2 => vec![0x8000 | x << 8 | y << 4 | [0, 1, 2, 3, 4, 5, 6, 7, 0xE][rng.gen_range(0, 9)]],
+            3 => vec![[0x3000, 0x4000][rng.gen_range(0, 2)] | x << 8 | kk, 0x6000 | y << 8 | kk],
+            4 => vec![[0x5000, 0x9000][rng.gen_range(0, 2)] | x << 8 | y << 4, 0x7000 | y << 8 | kk],
+            5 => vec![0x6000 | x << 8 | (kk & 0xF), [0xE09E, 0xE0A1][rng.gen_range(0, 2)] | x << 8, 0x6000 | y << 8 | kk],
+            6 => vec![0xF00A | x << 8],
+            7 => vec![[0xF007, 0xF015, 0xF018][rng.gen_range(0, 3)] | x << 8],
+            8 => vec![0xA000 | data, 0xF055 | x << 8],
+            9 => vec![0xA000 | data, 0xF065 | x << 8],
+            10 => vec![0xA000 | data, 0xF033 | x << 8],
+            // Rewrites the patch site.
+            11 => vec![0xA000 | (PATCH_SITE as u16 + 1), 0xF033 | x << 8],
+            12 => vec![0xA000 | data, 0xD000 | x << 8 | y << 4 | n],
+            13 => vec![0xF029 | x << 8, 0xD000 | x << 8 | y << 4 | 5],
+            14 => vec![[0xF075, 0xF085][rng.gen_range(0, 2)] | (x & 7) << 8],
+            // Jumps, patched once we know where units start. The indirect
+            // jump clears the registers it might add to the address.
+            15 => {
+                jumps.push((addr + 2, Some(rng.gen_range(0, units))));
+                vec![[0x3000, 0x4000][rng.gen_range(0, 2)] | x << 8 | kk, 0x1000]
+            }
+            16 => {
+                jumps.push((addr + 8, Some(rng.gen_range(0, units))));
+                vec![0x6000, 0x6200, 0x6300, [0x3000, 0x4000][rng.gen_range(0, 2)] | x << 8 | kk, 0xB000]
+            }
+            _ => {
+                jumps.push((addr, None));
+                vec![0x2000]
+            }
+        };
+        starts.push(addr);
+        addr += unit.len() * 2;
+        program.push(unit);
+    }
+    let subroutine = addr + 2;
+
+    let mut words = vec![0x6A00, 0x0000];
+    for unit in program {
+        words.extend(unit);
+    }
+    words.push(0x1000 | PROGRAM_START as u16);
+    words.extend(&[0x7701, 0x00EE]);
+    for (at, target) in jumps {
+        let target = target.map_or(subroutine, |unit| starts[unit]);
+        words[(at - PROGRAM_START) / 2] |= target as u16;
+    }
+    words.iter().flat_map(|&word| vec![(word >> 8) as u8, word as u8]).collect()
+}
+#[test]
+fn test_test_roms_match_interpreter() {
+    let roms: [(&str, &[u8]); 3] = [
+        ("arithmetic.ch8", include_bytes!("../tests/roms/arithmetic.ch8")),
+        ("digits.ch8", include_bytes!("../tests/roms/digits.ch8")),
+        ("keypad.ch8", include_bytes!("../tests/roms/keypad.ch8")),
+    ];
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    for &(name, rom) in &roms {
+        for quirks in Quirks::all() {
+            check_equivalent(name, rom, quirks, &mut rng, 2000);
+        }
+    }
+}
+#[test]
+fn test_random_programs_match_interpreter() {
+    let presets = Quirks::all();
+    for seed in 1..150 {
+        let mut rng = XorShiftRng::from_seed([seed, 0x9E37_79B9, seed * 7, 0x2545_F491]);
+        let program = random_program(&mut rng);
+        let quirks = presets[seed as usize % presets.len()];
+        check_equivalent(&format!("program {}", seed), &program, quirks, &mut rng, 3000);
+    }
+}
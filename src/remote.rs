@@ -0,0 +1,551 @@
+// Remote control for scripts and test harnesses: a JSON-RPC 2.0 server,
+// one request or response per line, on a local TCP port or a Unix socket.
+// Clients can load ROMs, pause and resume, hold keys down, read registers
+// and memory, take screenshots, save and load states, and subscribe to
+// events as the game runs.
+//
+// The server doesn't own the emulator. The frontend hands it a Frontend
+// once a frame, in poll, and asks it before each cycle whether a
+// breakpoint was hit.
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+use serde_json::Value;
+
+use line_map::parse_address;
+use processor::Processor;
+use CHIP8_WIDTH;
+
+// JSON-RPC's error codes, and one of ours for requests that were fine but
+// couldn't be carried out.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const FAILED: i64 = -32000;
+
+pub const EVENTS: [&str; 4] = ["frame", "beepStart", "beepStop", "breakpoint"];
+
+// The emulator as the server sees it.
+pub trait Frontend {
+    fn processor(&mut self) -> &mut Processor;
+    fn paused(&self) -> bool;
+    fn set_paused(&mut self, paused: bool);
+    // Loads a ROM from a file and boots it, as if it were given on the
+    // command line.
+    fn load(&mut self, path: &str) -> Result<(), String>;
+}
+
+// A request that failed, as a JSON-RPC error.
+struct Error {
+    code: i64,
+    message: String,
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error { code: FAILED, message }
+    }
+}
+
+fn invalid_params(message: &str) -> Error {
+    Error {
+        code: INVALID_PARAMS,
+        message: message.to_string(),
+    }
+}
+
+// The protocol, apart from how messages get to and from clients. Clients
+// are told apart by a number the transport gives them.
+#[derive(Default)]
+pub struct Remote {
+    // Keys each client is holding down.
+    keys: HashMap<usize, [bool; 16]>,
+    subscriptions: HashMap<usize, HashSet<String>>,
+    // Notifications waiting to go out, and who they're for.
+    events: Vec<(usize, Value)>,
+    breakpoints: Vec<usize>,
+    // The breakpoint the game is paused on, so resuming doesn't stop on it
+    // again straight away.
+    stopped_at: Option<usize>,
+    frame: u64,
+    beep: bool,
+}
+
+impl Remote {
+    pub fn new() -> Self {
+        Remote::default()
+    }
+
+    // Handles one request from a client and returns the response, or None
+    // for a notification, which gets no response.
+    pub fn handle<F: Frontend>(&mut self, client: usize, request: &Value, frontend: &mut F) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let result = match request["method"].as_str() {
+            Some(method) if request["jsonrpc"] == "2.0" => {
+                self.call(client, method, &request["params"], frontend)
+            }
+            _ => Err(Error {
+                code: INVALID_REQUEST,
+                message: "not a JSON-RPC 2.0 request".to_string(),
+            }),
+        };
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, e),
+        })
+    }
+
+    fn call<F: Frontend>(
+        &mut self,
+        client: usize,
+        method: &str,
+        params: &Value,
+        frontend: &mut F,
+    ) -> Result<Value, Error> {
+        let result = match method {
+            "load" => {
+                let path = params["path"].as_str().ok_or_else(|| invalid_params("load needs a path"))?;
+                frontend.load(path)?;
+                self.stopped_at = None;
+                json!({})
+            }
+            "pause" => {
+                frontend.set_paused(true);
+                json!({})
+            }
+            "resume" => {
+                frontend.set_paused(false);
+                json!({})
+            }
+            "status" => json!({
+                "paused": frontend.paused(),
+                "frame": self.frame,
+                "pc": frontend.processor().pc(),
+            }),
+            "press" | "release" => {
+                let key = key(&params["key"]);
+                let key = key.ok_or_else(|| invalid_params("key must be 0 to 15, or a hex digit"))?;
+                self.keys.entry(client).or_insert([false; 16])[key] = method == "press";
+                json!({})
+            }
+            "registers" => {
+                let processor = frontend.processor();
+                let registers = processor.registers();
+                json!({
+                    "v": registers.v,
+                    "i": registers.i,
+                    "pc": processor.pc(),
+                    "sp": processor.sp(),
+                    "delayTimer": registers.delay_timer,
+                    "soundTimer": registers.sound_timer,
+                    "stack": processor.stack(),
+                })
+            }
+            "readMemory" => {
+                let ram = frontend.processor().ram();
+                let address = address(&params["address"])?;
+                let length = params["length"].as_u64().unwrap_or(1) as usize;
+                let end = in_ram(address, length, ram)?;
+                json!({ "address": address, "data": to_hex(&ram[address..end]) })
+            }
+            "writeMemory" => {
+                let address = address(&params["address"])?;
+                let data = params["data"].as_str().and_then(from_hex);
+                let data = data.ok_or_else(|| invalid_params("data must be a hex string"))?;
+                let processor = frontend.processor();
+                in_ram(address, data.len(), processor.ram())?;
+                for (offset, &byte) in data.iter().enumerate() {
+                    processor.write_ram(address + offset, byte);
+                }
+                json!({})
+            }
+            "screenshot" => {
+                let vram = frontend.processor().vram();
+                let (width, height) = (CHIP8_WIDTH, vram.len());
+                match params["path"].as_str() {
+                    Some(path) => {
+                        fs::write(path, pbm(vram)).map_err(|e| format!("{}: {}", path, e))?;
+                        json!({ "width": width, "height": height, "path": path })
+                    }
+                    None => json!({ "width": width, "height": height, "rows": rows(vram) }),
+                }
+            }
+            "saveState" => {
+                let state = frontend.processor().save_state();
+                match params["path"].as_str() {
+                    Some(path) => {
+                        fs::write(path, &state).map_err(|e| format!("{}: {}", path, e))?;
+                        json!({ "path": path })
+                    }
+                    None => json!({ "state": to_hex(&state) }),
+                }
+            }
+            "loadState" => {
+                let state = match (params["state"].as_str(), params["path"].as_str()) {
+                    (Some(state), _) => {
+                        from_hex(state).ok_or_else(|| invalid_params("state must be a hex string"))?
+                    }
+                    (None, Some(path)) => fs::read(path).map_err(|e| format!("{}: {}", path, e))?,
+                    (None, None) => return Err(invalid_params("loadState needs a state or a path")),
+                };
+                frontend.processor().load_state(&state)?;
+                self.stopped_at = None;
+                json!({})
+            }
+            "setBreakpoints" => {
+                let addresses = params["addresses"].as_array();
+                let addresses = addresses.ok_or_else(|| invalid_params("setBreakpoints needs addresses"))?;
+                self.breakpoints = addresses.iter().map(address).collect::<Result<_, _>>()?;
+                json!({ "addresses": self.breakpoints })
+            }
+            "subscribe" | "unsubscribe" => {
+                let events = params["events"].as_array();
+                let events = events.ok_or_else(|| invalid_params("events must be a list"))?;
+                let subscriptions = self.subscriptions.entry(client).or_default();
+                for event in events {
+                    let event = event.as_str().filter(|event| EVENTS.contains(event));
+                    let event = event.ok_or_else(|| {
+                        invalid_params("events can be frame, beepStart, beepStop or breakpoint")
+                    })?;
+                    if method == "subscribe" {
+                        subscriptions.insert(event.to_string());
+                    } else {
+                        subscriptions.remove(event);
+                    }
+                }
+                let mut events: Vec<&String> = subscriptions.iter().collect();
+                events.sort();
+                json!({ "events": events })
+            }
+            _ => {
+                return Err(Error {
+                    code: METHOD_NOT_FOUND,
+                    message: format!("no method {}", method),
+                })
+            }
+        };
+        Ok(result)
+    }
+
+    // Forgets a client that went away, and lets go of its keys.
+    pub fn disconnect(&mut self, client: usize) {
+        self.keys.remove(&client);
+        self.subscriptions.remove(&client);
+    }
+
+    // The keypad with every key a client is holding down pressed too.
+    pub fn keypad(&self, keypad: [bool; 16]) -> [bool; 16] {
+        let mut keypad = keypad;
+        for keys in self.keys.values() {
+            for (pressed, &held) in keypad.iter_mut().zip(keys) {
+                *pressed |= held;
+            }
+        }
+        keypad
+    }
+
+    // Call before each cycle. True means the next instruction has a
+    // breakpoint on it, and the frontend should pause before running it.
+    pub fn at_breakpoint(&mut self, processor: &Processor) -> bool {
+        let pc = processor.pc();
+        if self.stopped_at == Some(pc) {
+            self.stopped_at = None;
+            return false;
+        }
+        if !self.breakpoints.contains(&pc) {
+            return false;
+        }
+        self.stopped_at = Some(pc);
+        self.notify("breakpoint", json!({ "pc": pc }));
+        true
+    }
+
+    // Call at the end of every frame with how many cycles ran (none while
+    // paused) and whether the machine is beeping. Frames where nothing ran
+    // don't count.
+    pub fn frame_done(&mut self, cycles: u32, beep: bool) {
+        if cycles == 0 {
+            return;
+        }
+        self.notify("frame", json!({ "frame": self.frame, "cycles": cycles }));
+        self.frame += 1;
+        if beep != self.beep {
+            self.beep = beep;
+            self.notify(if beep { "beepStart" } else { "beepStop" }, json!({ "frame": self.frame }));
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<(usize, Value)> {
+        self.events.split_off(0)
+    }
+
+    fn notify(&mut self, event: &str, params: Value) {
+        let notification = json!({ "jsonrpc": "2.0", "method": event, "params": params });
+        let mut clients: Vec<usize> = self
+            .subscriptions
+            .iter()
+            .filter(|(_, events)| events.contains(event))
+            .map(|(&client, _)| client)
+            .collect();
+        clients.sort();
+        for client in clients {
+            self.events.push((client, notification.clone()));
+        }
+    }
+}
+
+fn error_response(id: Value, e: Error) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": e.code, "message": e.message } })
+}
+
+// Keys are numbers, or hex digits as they're labelled on the keypad.
+fn key(value: &Value) -> Option<usize> {
+    let key = match value.as_str() {
+        Some(text) if text.len() == 1 => usize::from_str_radix(text, 16).ok()?,
+        Some(_) => return None,
+        None => value.as_u64()? as usize,
+    };
+    if key < 16 {
+        Some(key)
+    } else {
+        None
+    }
+}
+
+// Addresses are numbers, or hex strings like "0x3A0" or "3A0".
+fn address(value: &Value) -> Result<usize, Error> {
+    let addr = match value.as_str() {
+        Some(text) => parse_address(text),
+        None => value.as_u64().map(|addr| addr as usize),
+    };
+    addr.ok_or_else(|| invalid_params("addresses are numbers or hex strings"))
+}
+
+// Where a run of bytes ends, if it's all in RAM.
+fn in_ram(address: usize, length: usize, ram: &[u8]) -> Result<usize, Error> {
+    address
+        .checked_add(length)
+        .filter(|&end| end <= ram.len())
+        .ok_or_else(|| invalid_params("that's past the end of RAM"))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+// The screen as text, # for lit pixels and . for dark ones, the same as
+// the golden images the ROM tests compare with.
+fn rows(vram: &[[u8; CHIP8_WIDTH]]) -> Vec<String> {
+    vram.iter()
+        .map(|row| row.iter().map(|&pixel| if pixel == 0 { '.' } else { '#' }).collect())
+        .collect()
+}
+
+// The screen as a plain PBM image, which most image tools can read.
+fn pbm(vram: &[[u8; CHIP8_WIDTH]]) -> String {
+    let mut image = format!("P1\n{} {}\n", CHIP8_WIDTH, vram.len());
+    for row in vram {
+        let pixels: Vec<&str> = row.iter().map(|&pixel| if pixel == 0 { "0" } else { "1" }).collect();
+        image.push_str(&pixels.join(" "));
+        image.push('\n');
+    }
+    image
+}
+
+// What the connection threads tell the server.
+enum Incoming {
+    Connected(usize, Box<dyn Write + Send>),
+    Line(usize, String),
+    Closed(usize),
+}
+
+// Reads a client's requests a line at a time until it disconnects.
+fn read_client<R: io::Read>(client: usize, stream: R, sender: &Sender<Incoming>) {
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        if sender.send(Incoming::Line(client, line)).is_err() {
+            return;
+        }
+    }
+    let _ = sender.send(Incoming::Closed(client));
+}
+
+// Accepts clients on a listener, giving each one a thread to read its
+// requests. split makes a reader and a writer for one connection.
+fn accept<S, I, F>(incoming: I, sender: Sender<Incoming>, split: F)
+where
+    S: io::Read + Send + 'static,
+    I: Iterator<Item = io::Result<S>>,
+    F: Fn(&S) -> io::Result<Box<dyn Write + Send>>,
+{
+    for (client, stream) in incoming.filter_map(Result::ok).enumerate() {
+        let writer = match split(&stream) {
+            Ok(writer) => writer,
+            Err(_) => continue,
+        };
+        if sender.send(Incoming::Connected(client, writer)).is_err() {
+            return;
+        }
+        let sender = sender.clone();
+        thread::spawn(move || read_client(client, stream, &sender));
+    }
+}
+
+// The remote control server. Clients connect in the background, and their
+// requests are handled in poll, between frames.
+pub struct Server {
+    pub remote: Remote,
+    address: String,
+    receiver: Receiver<Incoming>,
+    clients: HashMap<usize, Box<dyn Write + Send>>,
+    // The Unix socket's file, to remove when the server stops.
+    socket: Option<PathBuf>,
+}
+
+impl Server {
+    // Listens on a port on localhost, given as a number, or a Unix socket,
+    // given as a path. Port 0 picks any free port.
+    pub fn bind(address: &str) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let (address, socket) = match address.parse::<u16>() {
+            Ok(port) => {
+                let listener = TcpListener::bind(("127.0.0.1", port))?;
+                let address = listener.local_addr()?.to_string();
+                thread::spawn(move || {
+                    accept(listener.incoming(), sender, |stream: &TcpStream| {
+                        stream.set_nodelay(true)?;
+                        Ok(Box::new(stream.try_clone()?) as Box<dyn Write + Send>)
+                    })
+                });
+                (address, None)
+            }
+            Err(_) => (address.to_string(), Some(bind_unix(address, sender)?)),
+        };
+        Ok(Server {
+            remote: Remote::new(),
+            address,
+            receiver,
+            clients: HashMap::new(),
+            socket,
+        })
+    }
+
+    // Where clients connect: host:port, or the socket's path.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    // Handles every request that's come in since the last poll, then sends
+    // out the events waiting. Call once a frame.
+    pub fn poll<F: Frontend>(&mut self, frontend: &mut F) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Incoming::Connected(client, writer)) => {
+                    self.clients.insert(client, writer);
+                }
+                Ok(Incoming::Line(client, line)) => {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let response = match serde_json::from_str::<Value>(&line) {
+                        Ok(request) => self.remote.handle(client, &request, frontend),
+                        Err(e) => Some(error_response(
+                            Value::Null,
+                            Error {
+                                code: PARSE_ERROR,
+                                message: e.to_string(),
+                            },
+                        )),
+                    };
+                    if let Some(response) = response {
+                        self.send(client, &response);
+                    }
+                }
+                Ok(Incoming::Closed(client)) => {
+                    self.clients.remove(&client);
+                    self.remote.disconnect(client);
+                }
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        for (client, event) in self.remote.take_events() {
+            self.send(client, &event);
+        }
+    }
+
+    fn send(&mut self, client: usize, message: &Value) {
+        let sent = match self.clients.get_mut(&client) {
+            Some(writer) => writeln!(writer, "{}", message).and_then(|_| writer.flush()),
+            None => return,
+        };
+        // A client that can't be written to has gone; its reader thread
+        // will say so too.
+        if sent.is_err() {
+            self.clients.remove(&client);
+            self.remote.disconnect(client);
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.socket {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &str, sender: Sender<Incoming>) -> io::Result<PathBuf> {
+    // A socket left behind by an emulator that didn't exit cleanly would
+    // stop us binding, but one that's still answering is in use. Anything
+    // else there, like a ROM passed by mistake, is left alone.
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            let message = format!("{} is already there and isn't a socket", path);
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, message));
+        }
+        if UnixStream::connect(path).is_ok() {
+            let message = format!("something is already listening on {}", path);
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, message));
+        }
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    thread::spawn(move || {
+        accept(listener.incoming(), sender, |stream: &UnixStream| {
+            Ok(Box::new(stream.try_clone()?) as Box<dyn Write + Send>)
+        })
+    });
+    Ok(PathBuf::from(path))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(path: &str, _sender: Sender<Incoming>) -> io::Result<PathBuf> {
+    let message = format!("{} isn't a port, and Unix sockets aren't available here", path);
+    Err(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+#[cfg(test)]
+#[path = "./remote_test.rs"]
+mod remote_test;
@@ -0,0 +1,44 @@
+use chip8::processor::Processor;
+use chip8::remote::Frontend;
+use drivers::{CartridgeDriver, StorageDriver};
+use options::Options;
+use boot;
+
+// The emulator's state, lent to the remote control server while it
+// handles requests.
+pub struct Emulator<'a> {
+    pub options: &'a Options,
+    pub processor: &'a mut Processor,
+    pub paused: &'a mut bool,
+    pub cartridge_driver: &'a mut CartridgeDriver,
+    // Headless runs don't use saved flags.
+    pub storage_driver: Option<&'a mut StorageDriver>,
+    pub tracing: bool,
+}
+
+impl<'a> Frontend for Emulator<'a> {
+    fn processor(&mut self) -> &mut Processor {
+        self.processor
+    }
+
+    fn paused(&self) -> bool {
+        *self.paused
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        *self.paused = paused;
+    }
+
+    // The new ROM gets its own saved flags, and a patch next to it is
+    // applied, as for the ROM on the command line.
+    fn load(&mut self, path: &str) -> Result<(), String> {
+        let cartridge_driver = CartridgeDriver::new(path, None).map_err(|e| e.to_string())?;
+        if let Some(ref mut storage_driver) = self.storage_driver {
+            **storage_driver = StorageDriver::new(cartridge_driver.hash());
+        }
+        *self.processor = boot(self.options, &cartridge_driver, self.storage_driver.as_deref());
+        self.processor.set_tracing(self.tracing);
+        *self.cartridge_driver = cartridge_driver;
+        Ok(())
+    }
+}
@@ -0,0 +1,286 @@
+use super::*;
+use std::env;
+use std::time::Duration;
+
+use machine::MachineConfig;
+
+const PROGRAM: [u8; 8] = [
+    0x60, 0x05, // LD V0, 5
+    0xF0, 0x18, // LD ST, V0
+    0x70, 0x01, // loop: ADD V0, 1
+    0x12, 0x04, // JP loop
+];
+
+// A frontend with nothing around the processor, loading ROMs the way the
+// emulator does.
+struct TestFrontend {
+    processor: Processor,
+    paused: bool,
+}
+
+impl Frontend for TestFrontend {
+    fn processor(&mut self) -> &mut Processor {
+        &mut self.processor
+    }
+
+    fn paused(&self) -> bool {
+        self.paused
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    fn load(&mut self, path: &str) -> Result<(), String> {
+        let rom = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        self.processor = Processor::with_config(MachineConfig::chip8());
+        self.processor.load(&rom);
+        Ok(())
+    }
+}
+
+fn frontend() -> TestFrontend {
+    let mut processor = Processor::new();
+    processor.load(&PROGRAM);
+    TestFrontend {
+        processor,
+        paused: false,
+    }
+}
+
+fn call(remote: &mut Remote, frontend: &mut TestFrontend, method: &str, params: Value) -> Value {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    remote.handle(0, &request, frontend).unwrap()
+}
+
+// Runs a frame the way the emulator does: stopping at breakpoints, and
+// not at all while paused.
+fn run_frame(remote: &mut Remote, frontend: &mut TestFrontend) {
+    let mut cycles = 0;
+    let mut beep = false;
+    while !frontend.paused && cycles < 8 {
+        if remote.at_breakpoint(&frontend.processor) {
+            frontend.paused = true;
+            break;
+        }
+        let keypad = remote.keypad([false; 16]);
+        beep = frontend.processor.tick(keypad).beep;
+        cycles += 1;
+    }
+    remote.frame_done(cycles, beep);
+}
+
+#[test]
+fn test_registers_and_memory() {
+    let mut remote = Remote::new();
+    let mut frontend = frontend();
+    frontend.processor.run(1, [false; 16]);
+
+    let response = call(&mut remote, &mut frontend, "registers", json!({}));
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["result"]["v"][0], 5);
+    assert_eq!(response["result"]["pc"], 0x202);
+
+    let params = json!({ "address": "0x300", "data": "c0ffee" });
+    let response = call(&mut remote, &mut frontend, "writeMemory", params);
+    assert_eq!(response["result"], json!({}));
+    let response = call(&mut remote, &mut frontend, "readMemory", json!({ "address": 0x2FF, "length": 4 }));
+    assert_eq!(response["result"]["data"], "00c0ffee");
+
+    let response = call(&mut remote, &mut frontend, "readMemory", json!({ "address": "FFF", "length": 2 }));
+    assert_eq!(response["error"]["code"], INVALID_PARAMS);
+}
+
+#[test]
+fn test_memory_past_the_end_of_addresses() {
+    let mut remote = Remote::new();
+    let mut frontend = frontend();
+    let params = json!({ "address": 16, "length": u64::MAX });
+    let response = call(&mut remote, &mut frontend, "readMemory", params);
+    assert_eq!(response["error"]["code"], INVALID_PARAMS);
+
+    let params = json!({ "address": u64::MAX, "data": "00" });
+    let response = call(&mut remote, &mut frontend, "writeMemory", params);
+    assert_eq!(response["error"]["code"], INVALID_PARAMS);
+}
+
+#[test]
+fn test_errors() {
+    let mut remote = Remote::new();
+    let mut frontend = frontend();
+    let response = call(&mut remote, &mut frontend, "explode", json!({}));
+    assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+    let response = remote.handle(0, &json!({ "id": 2, "method": "pause" }), &mut frontend).unwrap();
+    assert_eq!(response["error"]["code"], INVALID_REQUEST);
+    assert!(!frontend.paused);
+
+    // Notifications get no response, but still happen.
+    let notification = json!({ "jsonrpc": "2.0", "method": "pause" });
+    assert_eq!(remote.handle(0, &notification, &mut frontend), None);
+    assert!(frontend.paused);
+
+    let response = call(&mut remote, &mut frontend, "load", json!({ "path": "/nonexistent/rom.ch8" }));
+    assert_eq!(response["error"]["code"], FAILED);
+}
+
+#[test]
+fn test_keys_are_held_per_client() {
+    let mut remote = Remote::new();
+    let mut frontend = frontend();
+    call(&mut remote, &mut frontend, "press", json!({ "key": "a" }));
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "press", "params": { "key": 3 } });
+    remote.handle(1, &request, &mut frontend);
+
+    let keypad = remote.keypad([false; 16]);
+    let pressed: Vec<usize> = (0..16).filter(|&key| keypad[key]).collect();
+    assert_eq!(pressed, vec![0x3, 0xA]);
+
+    // A client that goes away lets go of its keys.
+    remote.disconnect(1);
+    call(&mut remote, &mut frontend, "release", json!({ "key": "A" }));
+    assert_eq!(remote.keypad([false; 16]), [false; 16]);
+
+    let response = call(&mut remote, &mut frontend, "press", json!({ "key": 16 }));
+    assert_eq!(response["error"]["code"], INVALID_PARAMS);
+}
+
+#[test]
+fn test_save_and_load_state() {
+    let mut remote = Remote::new();
+    let mut frontend = frontend();
+    let state = call(&mut remote, &mut frontend, "saveState", json!({}))["result"]["state"].clone();
+    frontend.processor.run(8, [false; 16]);
+    assert_ne!(frontend.processor.pc(), 0x200);
+
+    let response = call(&mut remote, &mut frontend, "loadState", json!({ "state": state }));
+    assert_eq!(response["result"], json!({}));
+    assert_eq!(frontend.processor.pc(), 0x200);
+
+    let response = call(&mut remote, &mut frontend, "loadState", json!({ "state": "00" }));
+    assert_eq!(response["error"]["code"], FAILED);
+}
+
+#[test]
+fn test_screenshot() {
+    let mut remote = Remote::new();
+    let mut frontend = TestFrontend {
+        processor: Processor::new(),
+        paused: false,
+    };
+    // Draw the 0 from the font in the top left corner.
+    frontend.processor.load(&[0xD0, 0x05]);
+    frontend.processor.run(1, [false; 16]);
+
+    let result = call(&mut remote, &mut frontend, "screenshot", json!({}))["result"].clone();
+    assert_eq!(result["width"], 64);
+    assert_eq!(result["height"], 32);
+    assert_eq!(result["rows"][0].as_str().unwrap()[..8], *"####....");
+    assert_eq!(result["rows"][1].as_str().unwrap()[..8], *"#..#....");
+
+    let path = env::temp_dir().join("chip8_remote_test_screenshot.pbm");
+    let path = path.to_string_lossy().into_owned();
+    call(&mut remote, &mut frontend, "screenshot", json!({ "path": path }));
+    let image = fs::read_to_string(&path).unwrap();
+    assert!(image.starts_with("P1\n64 32\n1 1 1 1 0 0 0 0 "));
+}
+
+#[test]
+fn test_events() {
+    let mut remote = Remote::new();
+    let mut frontend = frontend();
+    let response = call(&mut remote, &mut frontend, "subscribe", json!({ "events": ["frame", "beepStart"] }));
+    assert_eq!(response["result"]["events"], json!(["beepStart", "frame"]));
+    call(&mut remote, &mut frontend, "setBreakpoints", json!({ "addresses": ["0x206"] }));
+
+    // The sound timer starts in the first frame, which stops at the
+    // breakpoint after the ADD.
+    run_frame(&mut remote, &mut frontend);
+    let events = remote.take_events();
+    let methods: Vec<&str> = events.iter().map(|(_, event)| event["method"].as_str().unwrap()).collect();
+    assert_eq!(methods, vec!["frame", "beepStart"]);
+    assert_eq!(events[0].1["params"], json!({ "frame": 0, "cycles": 3 }));
+    assert!(frontend.paused);
+
+    // Nothing runs while paused. Resuming doesn't stop on the breakpoint
+    // it's paused on, but does the next time round the loop.
+    call(&mut remote, &mut frontend, "subscribe", json!({ "events": ["breakpoint"] }));
+    call(&mut remote, &mut frontend, "unsubscribe", json!({ "events": ["frame"] }));
+    run_frame(&mut remote, &mut frontend);
+    assert_eq!(remote.take_events(), vec![]);
+    call(&mut remote, &mut frontend, "resume", json!({}));
+    run_frame(&mut remote, &mut frontend);
+    assert_eq!(
+        remote.take_events(),
+        vec![(0, json!({ "jsonrpc": "2.0", "method": "breakpoint", "params": { "pc": 0x206 } }))]
+    );
+    assert_eq!(frontend.processor.registers().v[0], 7);
+
+    let response = call(&mut remote, &mut frontend, "subscribe", json!({ "events": ["explosion"] }));
+    assert_eq!(response["error"]["code"], INVALID_PARAMS);
+}
+
+// Sends a line to the server and polls it until the answer comes back.
+fn request<S>(server: &mut Server, frontend: &mut TestFrontend, stream: &mut S, line: &str) -> Value
+where
+    S: io::Read + Write + Send,
+{
+    writeln!(stream, "{}", line).unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut response = String::new();
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            reader.read_line(&mut response).unwrap();
+            sender.send(()).unwrap();
+        });
+        while receiver.try_recv().is_err() {
+            server.poll(frontend);
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+    serde_json::from_str(&response).unwrap()
+}
+
+#[test]
+fn test_tcp() {
+    let mut server = Server::bind("0").unwrap();
+    let mut frontend = frontend();
+    let mut stream = TcpStream::connect(server.address()).unwrap();
+
+    let line = r#"{"jsonrpc":"2.0","id":7,"method":"pause"}"#;
+    let response = request(&mut server, &mut frontend, &mut stream, line);
+    assert_eq!(response, json!({ "jsonrpc": "2.0", "id": 7, "result": {} }));
+    assert!(frontend.paused);
+
+    let response = request(&mut server, &mut frontend, &mut stream, "{oops");
+    assert_eq!(response["id"], Value::Null);
+    assert_eq!(response["error"]["code"], PARSE_ERROR);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_socket() {
+    let path = env::temp_dir().join("chip8_remote_test.sock");
+    let path = path.to_string_lossy().into_owned();
+    // A file that isn't a socket is left alone.
+    fs::write(&path, "rom").unwrap();
+    assert!(Server::bind(&path).is_err());
+    assert_eq!(fs::read(&path).unwrap(), b"rom");
+    fs::remove_file(&path).unwrap();
+
+    // A socket left behind doesn't stop the server starting.
+    drop(UnixListener::bind(&path).unwrap());
+    let mut server = Server::bind(&path).unwrap();
+    // But one that's in use does.
+    assert!(Server::bind(&path).is_err());
+    let mut frontend = frontend();
+    let mut stream = UnixStream::connect(&path).unwrap();
+
+    let line = r#"{"jsonrpc":"2.0","id":1,"method":"status"}"#;
+    let response = request(&mut server, &mut frontend, &mut stream, line);
+    assert_eq!(response["result"], json!({ "paused": false, "frame": 0, "pc": 0x200 }));
+
+    drop(server);
+    assert!(fs::metadata(&path).is_err());
+}
@@ -0,0 +1,413 @@
+// A self-test that needs no external files. Each case is a tiny
+// hand-assembled ROM which leaves its results in V0 onwards, puts the
+// number of results in VE and jumps to REPORT. That draws the results as
+// hex digits along the top of the screen, and we read them back by
+// matching the screen against the font.
+//
+// Cases either have one right answer, or an answer that depends on the
+// quirks being tested.
+
+use font::FONT_SET;
+use machine::{MachineConfig, Quirks};
+use processor::Processor;
+use CYCLES_PER_FRAME;
+use CHIP8_WIDTH;
+
+const REPORT_ADDRESS: usize = 0x300;
+// Long enough for every case to finish, with plenty to spare.
+const FRAMES: u32 = 30;
+// Digits are drawn 5 pixels apart, so this many fit across the screen.
+const MAX_DIGITS: usize = CHIP8_WIDTH / 5;
+
+// Saves V0-VE at 0x380, then draws the first VE of them.
+const REPORT: &[u8] = &[
+    0xA3, 0x80, // LD I, 0x380
+    0xFE, 0x55, // LD [I], VE
+    0x6C, 0x00, // LD VC, 0
+    0x6D, 0x00, // LD VD, 0
+    0x6B, 0x00, // LD VB, 0
+    // loop:
+    0xA3, 0x80, // LD I, 0x380
+    0xFC, 0x1E, // ADD I, VC
+    0xF0, 0x65, // LD V0, [I]
+    0xF0, 0x29, // LD F, V0
+    0xDD, 0xB5, // DRW VD, VB, 5
+    0x7D, 0x05, // ADD VD, 5
+    0x7C, 0x01, // ADD VC, 1
+    0x5C, 0xE0, // SE VC, VE
+    0x13, 0x0A, // JP loop
+    // halt:
+    0x13, 0x1C, // JP halt
+];
+
+// Leaves 1 in V0-V4 for each skip that behaves.
+const SKIPS: &[u8] = &[
+        0x68, 0x05, // LD V8, 5
+        0x69, 0x05, // LD V9, 5
+        0x6A, 0x06, // LD VA, 6
+        0x60, 0x01, // LD V0, 1
+        0x38, 0x05, // SE V8, 5
+        0x60, 0x00, // LD V0, 0
+        0x61, 0x01, // LD V1, 1
+        0x48, 0x06, // SNE V8, 6
+        0x61, 0x00, // LD V1, 0
+        0x62, 0x01, // LD V2, 1
+        0x58, 0x90, // SE V8, V9
+        0x62, 0x00, // LD V2, 0
+        0x63, 0x01, // LD V3, 1
+        0x98, 0xA0, // SNE V8, VA
+        0x63, 0x00, // LD V3, 0
+        0x64, 0x00, // LD V4, 0
+        0x38, 0x06, // SE V8, 6
+        0x64, 0x01, // LD V4, 1
+        0x6E, 0x05, // LD VE, 5
+        0x13, 0x00, // JP 0x300
+];
+
+// Copies, ORs, ANDs and XORs 0xC with 0xA, then adds 3 to 0xFE.
+const LOGIC: &[u8] = &[
+        0x68, 0x0C, // LD V8, 0x0C
+        0x69, 0x0A, // LD V9, 0x0A
+        0x80, 0x80, // LD V0, V8
+        0x81, 0x80, // LD V1, V8
+        0x81, 0x91, // OR V1, V9
+        0x82, 0x80, // LD V2, V8
+        0x82, 0x92, // AND V2, V9
+        0x83, 0x80, // LD V3, V8
+        0x83, 0x93, // XOR V3, V9
+        0x64, 0xFE, // LD V4, 0xFE
+        0x74, 0x03, // ADD V4, 3
+        0x6E, 0x05, // LD VE, 5
+        0x13, 0x00, // JP 0x300
+];
+
+// Results and VF for 0xFF + 2, 5 - 3, SUBN 3 from 5, and VF for 3 - 5.
+const CARRY: &[u8] = &[
+        0x60, 0xFF, // LD V0, 0xFF
+        0x68, 0x02, // LD V8, 2
+        0x80, 0x84, // ADD V0, V8
+        0x81, 0xF0, // LD V1, VF
+        0x62, 0x05, // LD V2, 5
+        0x68, 0x03, // LD V8, 3
+        0x82, 0x85, // SUB V2, V8
+        0x83, 0xF0, // LD V3, VF
+        0x64, 0x03, // LD V4, 3
+        0x68, 0x05, // LD V8, 5
+        0x84, 0x87, // SUBN V4, V8
+        0x85, 0xF0, // LD V5, VF
+        0x66, 0x03, // LD V6, 3
+        0x86, 0x85, // SUB V6, V8
+        0x86, 0xF0, // LD V6, VF
+        0x6E, 0x07, // LD VE, 7
+        0x13, 0x00, // JP 0x300
+];
+
+// Calls a subroutine that sets V0, then sets V1 after it returns.
+const CALLS: &[u8] = &[
+        0x60, 0x00, // LD V0, 0
+        0x61, 0x00, // LD V1, 0
+        0x22, 0x0C, // CALL sub
+        0x61, 0x02, // LD V1, 2
+        0x6E, 0x02, // LD VE, 2
+        0x13, 0x00, // JP 0x300
+        // sub:
+        0x60, 0x01, // LD V0, 1
+        0x00, 0xEE, // RET
+];
+
+// The decimal digits of 137.
+const BCD: &[u8] = &[
+        0x68, 0x89, // LD V8, 137
+        0xA3, 0xA0, // LD I, 0x3A0
+        0xF8, 0x33, // LD B, V8
+        0xA3, 0xA0, // LD I, 0x3A0
+        0xF2, 0x65, // LD V2, [I]
+        0x6E, 0x03, // LD VE, 3
+        0x13, 0x00, // JP 0x300
+];
+
+// Adds 5 to I and stores 7 there, then reads it back.
+const ADD_I: &[u8] = &[
+        0x61, 0x07, // LD V1, 7
+        0xA3, 0xA0, // LD I, 0x3A0
+        0x60, 0x05, // LD V0, 5
+        0xF0, 0x1E, // ADD I, V0
+        0xF1, 0x55, // LD [I], V1
+        0xA3, 0xA6, // LD I, 0x3A6
+        0xF0, 0x65, // LD V0, [I]
+        0x6E, 0x01, // LD VE, 1
+        0x13, 0x00, // JP 0x300
+];
+
+// Sets the delay timer and spins until it reaches 0.
+const TIMERS: &[u8] = &[
+        0x60, 0x09, // LD V0, 9
+        0xF0, 0x15, // LD DT, V0
+        // wait:
+        0xF1, 0x07, // LD V1, DT
+        0x31, 0x00, // SE V1, 0
+        0x12, 0x04, // JP wait
+        0x60, 0x01, // LD V0, 1
+        0x6E, 0x01, // LD VE, 1
+        0x13, 0x00, // JP 0x300
+];
+
+// Checks SKP and SKNP with nothing pressed.
+const KEYS: &[u8] = &[
+        0x68, 0x05, // LD V8, 5
+        0x60, 0x00, // LD V0, 0
+        0xE8, 0x9E, // SKP V8
+        0x60, 0x01, // LD V0, 1
+        0x61, 0x01, // LD V1, 1
+        0xE8, 0xA1, // SKNP V8
+        0x61, 0x00, // LD V1, 0
+        0x6E, 0x02, // LD VE, 2
+        0x13, 0x00, // JP 0x300
+];
+
+// VF after drawing a sprite, drawing it again on top, and drawing it
+// after clearing the screen.
+const DRAW: &[u8] = &[
+        0x68, 0x00, // LD V8, 0
+        0xF8, 0x29, // LD F, V8
+        0x69, 0x14, // LD V9, 20
+        0x6A, 0x14, // LD VA, 20
+        0xD9, 0xA5, // DRW V9, VA, 5
+        0x80, 0xF0, // LD V0, VF
+        0xD9, 0xA5, // DRW V9, VA, 5
+        0x81, 0xF0, // LD V1, VF
+        0xD9, 0xA5, // DRW V9, VA, 5
+        0x00, 0xE0, // CLS
+        0xD9, 0xA5, // DRW V9, VA, 5
+        0x82, 0xF0, // LD V2, VF
+        0x00, 0xE0, // CLS
+        0x6E, 0x03, // LD VE, 3
+        0x13, 0x00, // JP 0x300
+];
+
+// VF after OR, AND and XOR, having set it to 5 before each one.
+const VF_RESET: &[u8] = &[
+        0x68, 0x01, // LD V8, 1
+        0x69, 0x02, // LD V9, 2
+        0x6F, 0x05, // LD VF, 5
+        0x88, 0x91, // OR V8, V9
+        0x80, 0xF0, // LD V0, VF
+        0x6F, 0x05, // LD VF, 5
+        0x88, 0x92, // AND V8, V9
+        0x81, 0xF0, // LD V1, VF
+        0x6F, 0x05, // LD VF, 5
+        0x88, 0x93, // XOR V8, V9
+        0x82, 0xF0, // LD V2, VF
+        0x6E, 0x03, // LD VE, 3
+        0x13, 0x00, // JP 0x300
+];
+
+// Stores with FX55 twice without reloading I, then loads with FX65
+// twice. Both read back the first value if I moves and the second if not.
+const MEMORY: &[u8] = &[
+        0x60, 0x01, // LD V0, 1
+        0xA3, 0xA0, // LD I, 0x3A0
+        0xF0, 0x55, // LD [I], V0
+        0x60, 0x02, // LD V0, 2
+        0xF0, 0x55, // LD [I], V0
+        0xA3, 0xA0, // LD I, 0x3A0
+        0xF0, 0x65, // LD V0, [I]
+        0x8A, 0x00, // LD VA, V0
+        0x60, 0x03, // LD V0, 3
+        0x61, 0x04, // LD V1, 4
+        0xA3, 0xB0, // LD I, 0x3B0
+        0xF1, 0x55, // LD [I], V1
+        0xA3, 0xB0, // LD I, 0x3B0
+        0xF0, 0x65, // LD V0, [I]
+        0xF0, 0x65, // LD V0, [I]
+        0x81, 0x00, // LD V1, V0
+        0x80, 0xA0, // LD V0, VA
+        0x6E, 0x02, // LD VE, 2
+        0x13, 0x00, // JP 0x300
+];
+
+// SHR and SHL with different values in VX and VY, and VF after each.
+const SHIFTING: &[u8] = &[
+        0x60, 0x03, // LD V0, 3
+        0x68, 0x0C, // LD V8, 0x0C
+        0x80, 0x86, // SHR V0, V8
+        0x81, 0xF0, // LD V1, VF
+        0x62, 0x01, // LD V2, 1
+        0x69, 0x84, // LD V9, 0x84
+        0x82, 0x9E, // SHL V2, V9
+        0x83, 0xF0, // LD V3, VF
+        0x6E, 0x04, // LD VE, 4
+        0x13, 0x00, // JP 0x300
+];
+
+// BNNN with V0 = 2 and V2 = 4, landing on a JP that picks the result.
+const JUMPING: &[u8] = &[
+        0x60, 0x02, // LD V0, 2
+        0x62, 0x04, // LD V2, 4
+        0xB2, 0x10, // JP V0, 0x210
+        0x00, 0x00,
+        0x00, 0x00,
+        0x00, 0x00,
+        0x00, 0x00,
+        0x00, 0x00,
+        0x00, 0x00,
+        0x12, 0x16, // JP classic
+        0x12, 0x1C, // JP quirk
+        // classic:
+        0x60, 0x01, // LD V0, 1
+        0x6E, 0x01, // LD VE, 1
+        0x13, 0x00, // JP 0x300
+        // quirk:
+        0x60, 0x02, // LD V0, 2
+        0x6E, 0x01, // LD VE, 1
+        0x13, 0x00, // JP 0x300
+];
+
+// Draws a sprite off the right edge and one off the bottom edge, and
+// checks for a collision with a probe where the wrapped pixels would be.
+const CLIPPING: &[u8] = &[
+        0x68, 0x00, // LD V8, 0
+        0xF8, 0x29, // LD F, V8
+        0x69, 0x3E, // LD V9, 62
+        0x6A, 0x0A, // LD VA, 10
+        0xD9, 0xA1, // DRW V9, VA, 1
+        0x69, 0x00, // LD V9, 0
+        0xD9, 0xA1, // DRW V9, VA, 1
+        0x80, 0xF0, // LD V0, VF
+        0x69, 0x14, // LD V9, 20
+        0x6A, 0x1F, // LD VA, 31
+        0xD9, 0xA5, // DRW V9, VA, 5
+        0x6A, 0x00, // LD VA, 0
+        0xD9, 0xA1, // DRW V9, VA, 1
+        0x81, 0xF0, // LD V1, VF
+        0x00, 0xE0, // CLS
+        0x6E, 0x02, // LD VE, 2
+        0x13, 0x00, // JP 0x300
+];
+
+// Draws twice and checks whether that took long enough for the delay
+// timer to fall below 14.
+const DISPLAY_WAIT: &[u8] = &[
+        0x68, 0x00, // LD V8, 0
+        0xF8, 0x29, // LD F, V8
+        0x60, 0x14, // LD V0, 20
+        0xF0, 0x15, // LD DT, V0
+        0xD8, 0x81, // DRW V8, V8, 1
+        0xD8, 0x81, // DRW V8, V8, 1
+        0xF2, 0x07, // LD V2, DT
+        0x63, 0x0E, // LD V3, 14
+        0x83, 0x25, // SUB V3, V2
+        0x80, 0xF0, // LD V0, VF
+        0x6E, 0x01, // LD VE, 1
+        0x13, 0x00, // JP 0x300
+];
+
+pub struct Case {
+    pub name: &'static str,
+    program: &'static [u8],
+    // What the case should draw with the given quirks.
+    expected: fn(&Quirks) -> &'static str,
+}
+
+pub struct Outcome {
+    pub expected: &'static str,
+    pub got: String,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.expected == self.got
+    }
+}
+
+pub fn cases() -> Vec<Case> {
+    vec![
+        Case { name: "3XNN 4XNN 5XY0 9XY0", program: SKIPS, expected: |_| "11111" },
+        Case { name: "8XY0-8XY3 7XNN", program: LOGIC, expected: |_| "CE861" },
+        Case { name: "8XY4 8XY5 8XY7", program: CARRY, expected: |_| "1121210" },
+        Case { name: "2NNN 00EE", program: CALLS, expected: |_| "12" },
+        Case { name: "FX33", program: BCD, expected: |_| "137" },
+        Case { name: "FX1E", program: ADD_I, expected: |_| "7" },
+        Case { name: "FX07 FX15", program: TIMERS, expected: |_| "1" },
+        Case { name: "EX9E EXA1", program: KEYS, expected: |_| "11" },
+        Case { name: "DXYN 00E0", program: DRAW, expected: |_| "010" },
+        Case {
+            name: "VF reset",
+            program: VF_RESET,
+            expected: |quirks| if quirks.vf_reset { "000" } else { "555" },
+        },
+        Case {
+            name: "Memory increment",
+            program: MEMORY,
+            expected: |quirks| if quirks.memory_increment { "14" } else { "23" },
+        },
+        Case {
+            name: "Shifting",
+            program: SHIFTING,
+            expected: |quirks| if quirks.shifting { "1120" } else { "6081" },
+        },
+        Case {
+            name: "Jumping",
+            program: JUMPING,
+            expected: |quirks| if quirks.jumping { "2" } else { "1" },
+        },
+        Case {
+            name: "Clipping",
+            program: CLIPPING,
+            expected: |quirks| if quirks.clipping { "00" } else { "11" },
+        },
+        Case {
+            name: "Display wait",
+            program: DISPLAY_WAIT,
+            expected: |quirks| if quirks.display_wait { "1" } else { "0" },
+        },
+    ]
+}
+
+pub fn run_case(case: &Case, quirks: Quirks) -> Outcome {
+    let mut rom = case.program.to_vec();
+    assert!(rom.len() <= REPORT_ADDRESS - 0x200);
+    rom.resize(REPORT_ADDRESS - 0x200, 0);
+    rom.extend_from_slice(REPORT);
+
+    let mut config = MachineConfig::chip8();
+    config.quirks = quirks;
+    let mut processor = Processor::with_config(config);
+    processor.load(&rom);
+    for _ in 0..FRAMES * CYCLES_PER_FRAME {
+        processor.tick([false; 16]);
+    }
+
+    Outcome {
+        expected: (case.expected)(&quirks),
+        got: read_digits(processor.vram()),
+    }
+}
+
+// Reads hex digits drawn with the built-in font along the top of the
+// screen, stopping at the first blank space. Anything that isn't a digit
+// comes out as a '?'.
+pub fn read_digits(vram: &[[u8; CHIP8_WIDTH]]) -> String {
+    let mut digits = String::new();
+    for position in 0..MAX_DIGITS {
+        let mut glyph = [0u8; 5];
+        for (row, byte) in glyph.iter_mut().enumerate() {
+            for bit in 0..4 {
+                *byte |= vram[row][position * 5 + bit] << (7 - bit);
+            }
+        }
+        if glyph == [0; 5] {
+            break;
+        }
+        let digit = FONT_SET.chunks(5).position(|font_glyph| font_glyph == glyph);
+        digits.push(match digit {
+            Some(digit) => ::std::char::from_digit(digit as u32, 16).unwrap().to_ascii_uppercase(),
+            None => '?',
+        });
+    }
+    digits
+}
+
+#[cfg(test)]
+#[path = "./selftest_test.rs"]
+mod selftest_test;
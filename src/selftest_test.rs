@@ -0,0 +1,55 @@
+use super::*;
+#[test]
+fn test_every_case_passes_with_every_quirk_profile() {
+    for quirks in Quirks::all() {
+        for case in cases() {
+            let outcome = run_case(&case, quirks);
+            assert!(
+                outcome.passed(),
+                "{} with {} quirks: expected {:?}, got {}",
+                case.name,
+                quirks.name,
+                outcome.expected,
+                outcome.got
+            );
+        }
+    }
+}
+#[test]
+fn test_drawing_quirks() {
+    let clipping = &cases()[13];
+    assert_eq!(clipping.name, "Clipping");
+    assert_eq!(run_case(clipping, Quirks::legacy()).got, "11");
+    assert_eq!(run_case(clipping, Quirks::vip()).got, "00");
+
+    let display_wait = &cases()[14];
+    assert_eq!(display_wait.name, "Display wait");
+    assert_eq!(run_case(display_wait, Quirks::legacy()).got, "0");
+    assert_eq!(run_case(display_wait, Quirks::vip()).got, "1");
+}
+#[test]
+fn test_read_digits() {
+    let mut processor = Processor::new();
+    processor.load(&[
+        0x60, 0x03, // LD V0, 3
+        0xF0, 0x29, // LD F, V0
+        0xD1, 0x15, // DRW V1, V1, 5
+        0x60, 0x0A, // LD V0, 0x0A
+        0xF0, 0x29, // LD F, V0
+        0x61, 0x05, // LD V1, 5
+        0xD1, 0x25, // DRW V1, V2, 5
+        0x61, 0x0F, // LD V1, 15
+        0xD1, 0x25, // DRW V1, V2, 5
+    ]);
+    for _ in 0..9 {
+        processor.tick([false; 16]);
+    }
+    // Reading stops at the gap before the last digit.
+    assert_eq!(read_digits(processor.vram()), "3A");
+}
+#[test]
+fn test_unknown_glyphs() {
+    let mut vram = [[0u8; CHIP8_WIDTH]; 32];
+    vram[0][0] = 1;
+    assert_eq!(read_digits(&vram), "?");
+}
@@ -0,0 +1,92 @@
+use std::f32::consts::PI;
+
+// How long the beep takes to fade in and out. Starting or stopping a wave
+// mid-cycle makes an audible click; a few milliseconds of ramp hides it.
+const ATTACK_SECONDS: f32 = 0.005;
+const RELEASE_SECONDS: f32 = 0.005;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "sine" => Some(Waveform::Sine),
+            "triangle" => Some(Waveform::Triangle),
+            _ => None,
+        }
+    }
+
+    // The value of the wave at a point in its cycle, from 0.0 to 1.0.
+    fn sample(&self, phase: f32) -> f32 {
+        match *self {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneSettings {
+    pub frequency: f32,
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for ToneSettings {
+    fn default() -> Self {
+        ToneSettings {
+            frequency: 240.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+// Generates the beep one sample at a time. The oscillator runs
+// continuously and the gate only moves the envelope, so turning the beep
+// on and off never cuts a wave off abruptly.
+pub struct Tone {
+    settings: ToneSettings,
+    phase_inc: f32,
+    phase: f32,
+    envelope: f32,
+    attack_step: f32,
+    release_step: f32,
+}
+
+impl Tone {
+    pub fn new(settings: ToneSettings, sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f32;
+        Tone {
+            settings,
+            phase_inc: settings.frequency / sample_rate,
+            phase: 0.0,
+            envelope: 0.0,
+            attack_step: 1.0 / (ATTACK_SECONDS * sample_rate),
+            release_step: 1.0 / (RELEASE_SECONDS * sample_rate),
+        }
+    }
+
+    pub fn next_sample(&mut self, gate: bool) -> f32 {
+        if gate {
+            self.envelope = (self.envelope + self.attack_step).min(1.0);
+        } else {
+            self.envelope = (self.envelope - self.release_step).max(0.0);
+        }
+
+        let sample = self.settings.waveform.sample(self.phase);
+        self.phase = (self.phase + self.phase_inc) % 1.0;
+        sample * self.settings.volume * self.envelope
+    }
+}
+
+#[cfg(test)]
+#[path = "./tone_test.rs"]
+mod tone_test;
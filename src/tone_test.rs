@@ -0,0 +1,62 @@
+use super::*;
+const SAMPLE_RATE: u32 = 44100;
+fn build_tone(waveform: Waveform) -> Tone {
+    Tone::new(
+        ToneSettings {
+            frequency: 441.0,
+            volume: 0.5,
+            waveform,
+        },
+        SAMPLE_RATE,
+    )
+}
+#[test]
+fn test_silent_until_gated() {
+    let mut tone = build_tone(Waveform::Square);
+    for _ in 0..100 {
+        assert_eq!(tone.next_sample(false), 0.0);
+    }
+}
+#[test]
+fn test_attack_ramps_up() {
+    let mut tone = build_tone(Waveform::Square);
+    let first = tone.next_sample(true);
+    assert!(first > 0.0 && first < 0.01);
+    // 5ms of attack at 44.1kHz
+    for _ in 0..220 {
+        tone.next_sample(true);
+    }
+    assert_eq!(tone.next_sample(true).abs(), 0.5);
+}
+#[test]
+fn test_release_ramps_down() {
+    let mut tone = build_tone(Waveform::Square);
+    for _ in 0..1000 {
+        tone.next_sample(true);
+    }
+    let released = tone.next_sample(false).abs();
+    assert!(released > 0.49 && released < 0.5);
+    for _ in 0..220 {
+        tone.next_sample(false);
+    }
+    assert_eq!(tone.next_sample(false), 0.0);
+}
+#[test]
+fn test_waveforms() {
+    assert_eq!(Waveform::Square.sample(0.25), 1.0);
+    assert_eq!(Waveform::Square.sample(0.75), -1.0);
+    assert_eq!(Waveform::Sine.sample(0.25), 1.0);
+    assert_eq!(Waveform::Triangle.sample(0.0), -1.0);
+    assert_eq!(Waveform::Triangle.sample(0.5), 1.0);
+    assert_eq!(Waveform::Triangle.sample(0.75), 0.0);
+}
+#[test]
+fn test_frequency() {
+    // 441Hz at 44.1kHz is 100 samples per cycle, the first half positive.
+    let mut tone = build_tone(Waveform::Square);
+    for _ in 0..1000 {
+        tone.next_sample(true);
+    }
+    let samples: Vec<f32> = (0..100).map(|_| tone.next_sample(true)).collect();
+    assert_eq!(samples.iter().filter(|&&s| s > 0.0).count(), 50);
+}
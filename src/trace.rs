@@ -0,0 +1,266 @@
+use core::fmt;
+
+#[cfg(feature = "std")]
+use disassembler::disassemble;
+
+// The registers an instruction can change, apart from the program counter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+// One executed instruction, with the registers as it found them and as it
+// left them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub before: Registers,
+    pub after: Registers,
+}
+
+// Trace files have one instruction per line:
+//
+//   0200 6001 V=00000000000000000000000000000000 I=000 DT=00 ST=00 -> V=01000000000000000000000000000000 I=000 DT=00 ST=00 ; LD V0, 0x01
+//
+// The program counter and opcode come first, then the registers before and
+// after the instruction as KEY=VALUE pairs, then the disassembly after a
+// semicolon. Everything is hex.
+#[cfg(feature = "std")]
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04X} {:04X} {} -> {} ; {}",
+            self.pc,
+            self.opcode,
+            self.before,
+            self.after,
+            disassemble(self.opcode)
+        )
+    }
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "V=")?;
+        for v in &self.v {
+            write!(f, "{:02X}", v)?;
+        }
+        write!(f, " I={:03X} DT={:02X} ST={:02X}", self.i, self.delay_timer, self.sound_timer)
+    }
+}
+
+// A line read back from a trace file. Only the program counter and opcode
+// are required: traces from other emulators can leave out any registers
+// they don't record, and only the registers both traces have are compared.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceRecord {
+    // Where the record came from in its file, counting from 1.
+    pub line_number: usize,
+    pub line: String,
+    pub pc: u16,
+    pub opcode: u16,
+    pub before: Vec<(String, String)>,
+    pub after: Vec<(String, String)>,
+}
+
+#[cfg(feature = "std")]
+impl TraceRecord {
+    fn same_instruction(&self, other: &TraceRecord) -> bool {
+        self.pc == other.pc && self.opcode == other.opcode
+    }
+}
+
+// Reads a trace file. Blank lines and lines starting with # are skipped.
+#[cfg(feature = "std")]
+pub fn parse(text: &str) -> Result<Vec<TraceRecord>, String> {
+    let mut records = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let code = line.split(';').next().unwrap_or("").trim();
+        if code.is_empty() || code.starts_with('#') {
+            continue;
+        }
+        let mut tokens = code.split_whitespace();
+        let mut hex = |what| {
+            tokens
+                .next()
+                .and_then(|token| u16::from_str_radix(token, 16).ok())
+                .ok_or_else(|| format!("line {}: expected {}", line_number, what))
+        };
+        let pc = hex("a program counter")?;
+        let opcode = hex("an opcode")?;
+
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        let mut registers = &mut before;
+        for token in tokens {
+            if token == "->" {
+                registers = &mut after;
+                continue;
+            }
+            let mut pair = token.splitn(2, '=');
+            match (pair.next(), pair.next()) {
+                (Some(key), Some(value)) => {
+                    registers.push((key.to_uppercase(), value.to_uppercase()))
+                }
+                _ => return Err(format!("line {}: expected KEY=VALUE, got {}", line_number, token)),
+            }
+        }
+
+        records.push(TraceRecord {
+            line_number,
+            line: line.to_string(),
+            pc,
+            opcode,
+            before,
+            after,
+        });
+    }
+    Ok(records)
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq)]
+pub enum Comparison {
+    // Every instruction both traces have agrees. One trace may carry on
+    // past the end of the other.
+    Match {
+        compared: usize,
+        skipped_a: usize,
+        skipped_b: usize,
+    },
+    Diverged(Divergence),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    // Instructions compared before this one, after alignment.
+    pub index: usize,
+    // Positions of the diverging records in each trace, or None when there
+    // was nothing to line up at all.
+    pub a: Option<usize>,
+    pub b: Option<usize>,
+    // What differs, e.g. "pc: 0202 != 0204" or "V3 after: 05 != 06".
+    pub differences: Vec<String>,
+}
+
+// Lines the traces up on their first common instruction, then walks them
+// in step until they disagree. Traces rarely start at exactly the same
+// point (another emulator might log its boot code, or start logging
+// late), so leading records with no counterpart are skipped.
+#[cfg(feature = "std")]
+pub fn compare(a: &[TraceRecord], b: &[TraceRecord]) -> Comparison {
+    let (skipped_a, skipped_b) = match align(a, b) {
+        Some(offsets) => offsets,
+        None => {
+            return Comparison::Diverged(Divergence {
+                index: 0,
+                a: None,
+                b: None,
+                differences: vec!["the traces have no instruction in common".to_string()],
+            })
+        }
+    };
+
+    let pairs = a[skipped_a..].iter().zip(&b[skipped_b..]);
+    for (index, (record_a, record_b)) in pairs.enumerate() {
+        let differences = differences(record_a, record_b);
+        if !differences.is_empty() {
+            return Comparison::Diverged(Divergence {
+                index,
+                a: Some(skipped_a + index),
+                b: Some(skipped_b + index),
+                differences,
+            });
+        }
+    }
+
+    Comparison::Match {
+        compared: (a.len() - skipped_a).min(b.len() - skipped_b),
+        skipped_a,
+        skipped_b,
+    }
+}
+
+// The smallest number of leading records to drop from either trace so that
+// both start on the same instruction.
+#[cfg(feature = "std")]
+fn align(a: &[TraceRecord], b: &[TraceRecord]) -> Option<(usize, usize)> {
+    let (first_a, first_b) = match (a.first(), b.first()) {
+        (Some(first_a), Some(first_b)) => (first_a, first_b),
+        _ => return Some((0, 0)),
+    };
+    for offset in 0..a.len().max(b.len()) {
+        if b.get(offset).is_some_and(|record| record.same_instruction(first_a)) {
+            return Some((0, offset));
+        }
+        if a.get(offset).is_some_and(|record| record.same_instruction(first_b)) {
+            return Some((offset, 0));
+        }
+    }
+    None
+}
+
+#[cfg(feature = "std")]
+fn differences(a: &TraceRecord, b: &TraceRecord) -> Vec<String> {
+    let mut differences = Vec::new();
+    if a.pc != b.pc {
+        differences.push(format!("pc: {:04X} != {:04X}", a.pc, b.pc));
+    }
+    if a.opcode != b.opcode {
+        differences.push(format!("opcode: {:04X} != {:04X}", a.opcode, b.opcode));
+    }
+    compare_registers(&a.before, &b.before, "before", &mut differences);
+    compare_registers(&a.after, &b.after, "after", &mut differences);
+    differences
+}
+
+#[cfg(feature = "std")]
+fn compare_registers(
+    a: &[(String, String)],
+    b: &[(String, String)],
+    when: &str,
+    differences: &mut Vec<String>,
+) {
+    for (key, value_a) in a {
+        let value_b = match b.iter().find(|(other, _)| other == key) {
+            Some((_, value_b)) => value_b,
+            None => continue,
+        };
+        // Hand-edited traces might not be ASCII, and can't be sliced by
+        // byte then.
+        let per_register = |value: &str| value.len() == 32 && value.is_ascii();
+        if key == "V" && per_register(value_a) && per_register(value_b) {
+            // Point at the registers that differ rather than the whole set.
+            for register in 0..16 {
+                let byte_a = &value_a[register * 2..register * 2 + 2];
+                let byte_b = &value_b[register * 2..register * 2 + 2];
+                if byte_a != byte_b {
+                    differences.push(format!("V{:X} {}: {} != {}", register, when, byte_a, byte_b));
+                }
+            }
+        } else if !same_value(value_a, value_b) {
+            differences.push(format!("{} {}: {} != {}", key, when, value_a, value_b));
+        }
+    }
+}
+
+// Values are hex, but not every emulator pads them the same way.
+#[cfg(feature = "std")]
+fn same_value(a: &str, b: &str) -> bool {
+    match (u32::from_str_radix(a, 16), u32::from_str_radix(b, 16)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+#[path = "./trace_test.rs"]
+mod trace_test;
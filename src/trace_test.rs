@@ -0,0 +1,122 @@
+use super::*;
+use processor::Processor;
+fn entry(pc: u16, opcode: u16, v0: u8) -> TraceEntry {
+    let mut before = Registers { v: [0; 16], i: 0x300, delay_timer: 2, sound_timer: 0 };
+    before.v[0xF] = 1;
+    let mut after = before;
+    after.v[0] = v0;
+    TraceEntry { pc, opcode, before, after }
+}
+fn records(entries: &[TraceEntry]) -> Vec<TraceRecord> {
+    let text: Vec<String> = entries.iter().map(|entry| entry.to_string()).collect();
+    parse(&text.join("\n")).unwrap()
+}
+#[test]
+fn test_format() {
+    assert_eq!(
+        entry(0x200, 0x6007, 7).to_string(),
+        "0200 6007 V=00000000000000000000000000000001 I=300 DT=02 ST=00 \
+         -> V=07000000000000000000000000000001 I=300 DT=02 ST=00 ; LD V0, 0x07"
+    );
+}
+#[test]
+fn test_parse() {
+    let text = "# a comment\n\n0200 6007 V=00 I=0300 -> v=07 ; LD V0, 0x07\n0202 1202\n";
+    let records = parse(text).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].line_number, 3);
+    assert_eq!(records[0].pc, 0x200);
+    assert_eq!(records[0].opcode, 0x6007);
+    assert_eq!(records[0].before, vec![("V".to_string(), "00".to_string()), ("I".to_string(), "0300".to_string())]);
+    assert_eq!(records[0].after, vec![("V".to_string(), "07".to_string())]);
+    assert!(records[1].before.is_empty());
+}
+#[test]
+fn test_parse_errors() {
+    assert_eq!(parse("0200\n"), Err("line 1: expected an opcode".to_string()));
+    assert_eq!(parse("xyz 6007\n"), Err("line 1: expected a program counter".to_string()));
+    assert_eq!(parse("0200 6007 V\n"), Err("line 1: expected KEY=VALUE, got V".to_string()));
+}
+#[test]
+fn test_compare_match() {
+    let a = records(&[entry(0x200, 0x6007, 7), entry(0x202, 0x1202, 7)]);
+    let b = records(&[entry(0x200, 0x6007, 7), entry(0x202, 0x1202, 7), entry(0x202, 0x1202, 7)]);
+    assert_eq!(compare(&a, &b), Comparison::Match { compared: 2, skipped_a: 0, skipped_b: 0 });
+}
+#[test]
+fn test_compare_divergence() {
+    let a = records(&[entry(0x200, 0x6007, 7), entry(0x202, 0x6008, 8)]);
+    let b = records(&[entry(0x200, 0x6007, 7), entry(0x202, 0x6008, 9)]);
+    assert_eq!(
+        compare(&a, &b),
+        Comparison::Diverged(Divergence {
+            index: 1,
+            a: Some(1),
+            b: Some(1),
+            differences: vec!["V0 after: 08 != 09".to_string()],
+        })
+    );
+}
+#[test]
+fn test_compare_aligns_start() {
+    let a = records(&[entry(0x202, 0x6008, 8), entry(0x204, 0x1204, 8)]);
+    let b = records(&[entry(0x200, 0x6007, 7), entry(0x202, 0x6008, 8), entry(0x204, 0x1206, 8)]);
+    match compare(&a, &b) {
+        Comparison::Diverged(divergence) => {
+            assert_eq!(divergence.index, 1);
+            assert_eq!(divergence.a, Some(1));
+            assert_eq!(divergence.b, Some(2));
+            assert_eq!(divergence.differences, vec!["opcode: 1204 != 1206".to_string()]);
+        }
+        other => panic!("expected a divergence, got {:?}", other),
+    }
+}
+#[test]
+fn test_compare_only_shared_registers() {
+    let a = parse("0200 6007 V=00000000000000000000000000000000 I=300 -> DT=05\n").unwrap();
+    let b = parse("0200 6007 I=0300 ST=01\n").unwrap();
+    assert_eq!(compare(&a, &b), Comparison::Match { compared: 1, skipped_a: 0, skipped_b: 0 });
+}
+#[test]
+fn test_compare_non_ascii_registers() {
+    let zeros = "0".repeat(32);
+    let odd = format!("0\u{e9}{}", "0".repeat(29));
+    let a = parse(&format!("0200 6007 V={}\n", odd)).unwrap();
+    let b = parse(&format!("0200 6007 V={}\n", zeros)).unwrap();
+    match compare(&a, &b) {
+        Comparison::Diverged(divergence) => {
+            assert_eq!(divergence.differences, vec![format!("V before: {} != {}", odd.to_uppercase(), zeros)]);
+        }
+        other => panic!("expected a divergence, got {:?}", other),
+    }
+}
+#[test]
+fn test_compare_nothing_in_common() {
+    let a = parse("0200 6007\n").unwrap();
+    let b = parse("0300 6007\n").unwrap();
+    match compare(&a, &b) {
+        Comparison::Diverged(divergence) => assert_eq!(divergence.a, None),
+        other => panic!("expected a divergence, got {:?}", other),
+    }
+}
+// What --trace writes for a ROM run with --seed.
+fn trace_with_seed(rom: &[u8], seed: u64) -> Vec<TraceRecord> {
+    let mut processor = Processor::new();
+    processor.seed_rng(seed);
+    processor.set_tracing(true);
+    processor.load(rom);
+    let entries: Vec<TraceEntry> = (0..16).filter_map(|_| processor.tick([false; 16]).trace).collect();
+    records(&entries)
+}
+#[test]
+fn test_compare_seeded_random_numbers() {
+    // C0FF: V0 = random byte, then loop.
+    let rom = [0xC0, 0xFF, 0x12, 0x00];
+    let a = trace_with_seed(&rom, 1);
+    let b = trace_with_seed(&rom, 1);
+    assert_eq!(compare(&a, &b), Comparison::Match { compared: 16, skipped_a: 0, skipped_b: 0 });
+    match compare(&a, &trace_with_seed(&rom, 2)) {
+        Comparison::Diverged(divergence) => assert_eq!(divergence.index, 0),
+        other => panic!("expected a divergence, got {:?}", other),
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use tone::{Tone, ToneSettings};
+
+const HEADER_SIZE: u32 = 44;
+
+// Renders the beep into a 16-bit mono WAV file. Time is measured in
+// emulated cycles rather than on the wall clock, so the file is the same
+// however fast the emulator actually ran.
+pub struct AudioRecorder {
+    out: BufWriter<File>,
+    tone: Tone,
+    sample_rate: u32,
+    cycles_per_second: u32,
+    cycles: u64,
+    samples_written: u32,
+}
+
+impl AudioRecorder {
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        sample_rate: u32,
+        settings: ToneSettings,
+        cycles_per_second: u32,
+    ) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write_header(&mut out, sample_rate, 0)?;
+        Ok(AudioRecorder {
+            out,
+            tone: Tone::new(settings, sample_rate),
+            sample_rate,
+            cycles_per_second,
+            cycles: 0,
+            samples_written: 0,
+        })
+    }
+
+    // Call once per processor cycle with the beep state after that cycle.
+    pub fn cycle(&mut self, beep: bool) -> io::Result<()> {
+        // Work out the sample count from the total number of cycles so far,
+        // so rounding never accumulates.
+        self.cycles += 1;
+        let samples = self.cycles * self.sample_rate as u64 / self.cycles_per_second as u64;
+        while (self.samples_written as u64) < samples {
+            let sample = self.tone.next_sample(beep);
+            let sample = (sample * i16::MAX as f32) as i16;
+            self.out.write_all(&sample.to_le_bytes())?;
+            self.samples_written += 1;
+        }
+        Ok(())
+    }
+
+    // Fills in the sizes in the header now that we know them.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.out, self.sample_rate, self.samples_written)?;
+        self.out.flush()
+    }
+}
+
+fn write_header<W: Write>(out: &mut W, sample_rate: u32, samples: u32) -> io::Result<()> {
+    let data_size = samples * 2;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    out.write_all(b"WAVE")?;
+    out.write_all(b"fmt ")?;
+    out.write_all(&16u32.to_le_bytes())?; // fmt chunk size
+    out.write_all(&1u16.to_le_bytes())?; // PCM
+    out.write_all(&1u16.to_le_bytes())?; // mono
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * 2).to_le_bytes())?; // bytes per second
+    out.write_all(&2u16.to_le_bytes())?; // bytes per sample
+    out.write_all(&16u16.to_le_bytes())?; // bits per sample
+    out.write_all(b"data")?;
+    out.write_all(&data_size.to_le_bytes())
+}
+
+#[cfg(test)]
+#[path = "./wav_test.rs"]
+mod wav_test;
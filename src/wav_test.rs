@@ -0,0 +1,32 @@
+use super::*;
+use std::env;
+use std::fs;
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+#[test]
+fn test_recording() {
+    let path = env::temp_dir().join("chip8_wav_test_recording.wav");
+    let mut recorder = AudioRecorder::create(&path, 8000, ToneSettings::default(), 480).unwrap();
+    // One second of emulated time, beeping for the second half of it.
+    for i in 0..480 {
+        recorder.cycle(i >= 240).unwrap();
+    }
+    recorder.finish().unwrap();
+
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(read_u32(&bytes, 4) as usize, bytes.len() - 8);
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    assert_eq!(read_u32(&bytes, 24), 8000);
+    assert_eq!(&bytes[36..40], b"data");
+    assert_eq!(read_u32(&bytes, 40), 8000 * 2);
+
+    let samples: Vec<i16> = bytes[44..]
+        .chunks(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    assert!(samples[..3990].iter().all(|&s| s == 0));
+    assert!(samples[4000..].iter().any(|&s| s != 0));
+}
@@ -0,0 +1,27 @@
+// Builds the library with default features off, the way a microcontroller
+// port depends on it, to check the core still builds without std. It's
+// built for the host, which is enough to catch anything that needs std.
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_builds_without_std() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(&cargo)
+        .arg("build")
+        .arg("--lib")
+        .arg("--no-default-features")
+        .arg("--manifest-path")
+        .arg(root.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("no_std"))
+        .output()
+        .unwrap_or_else(|e| panic!("couldn't run {}: {}", cargo, e));
+    assert!(
+        output.status.success(),
+        "the library didn't build without std:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,148 @@
+// Runs the ROMs listed in tests/roms/MANIFEST and compares their final
+// screens with the golden images in tests/golden/. To accept the current
+// output as correct, run:
+//
+//     BLESS=1 cargo test --test roms
+//
+// and review the changes to tests/golden/ before committing them. A ROM
+// in the manifest that isn't in tests/roms/ fails the test, unless
+// SKIP_MISSING_ROMS is set.
+extern crate chip8;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8::machine::MachineConfig;
+use chip8::processor::Processor;
+use chip8::CYCLES_PER_FRAME;
+
+struct KeyEvent {
+    frame: u32,
+    key: usize,
+    pressed: bool,
+}
+
+struct Case {
+    name: String,
+    rom: String,
+    config: MachineConfig,
+    frames: u32,
+    input: Vec<KeyEvent>,
+}
+
+fn test_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(name)
+}
+
+fn parse_manifest(text: &str) -> Vec<Case> {
+    let mut cases = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        assert!(fields.len() >= 4, "bad manifest line: {}", line);
+
+        let input = fields[4..]
+            .iter()
+            .map(|event| {
+                let (frame, key) = event.split_at(event.find(':').expect(event));
+                let key = &key[1..];
+                KeyEvent {
+                    frame: frame.parse().expect(event),
+                    key: usize::from_str_radix(&key[1..], 16).expect(event),
+                    pressed: key.starts_with('+'),
+                }
+            })
+            .collect();
+
+        cases.push(Case {
+            name: fields[0].to_string(),
+            rom: fields[1].to_string(),
+            config: MachineConfig::from_name(fields[2]).expect(fields[2]),
+            frames: fields[3].parse().expect(fields[3]),
+            input,
+        });
+    }
+    cases
+}
+
+// Runs the case and draws the final screen as text, one line per row with
+// # for lit pixels and . for dark ones.
+fn run(case: &Case, rom: &[u8]) -> String {
+    let mut processor = Processor::with_config(case.config.clone());
+    // CXKK has to give the same numbers every run to match the golden.
+    processor.seed_rng(0);
+    processor.load(rom);
+
+    let mut keypad = [false; 16];
+    for frame in 0..case.frames {
+        for event in case.input.iter().filter(|event| event.frame == frame) {
+            keypad[event.key] = event.pressed;
+        }
+        for _ in 0..CYCLES_PER_FRAME {
+            processor.tick(keypad);
+        }
+    }
+
+    let mut screen = String::new();
+    for row in processor.vram() {
+        for &pixel in row.iter() {
+            screen.push(if pixel == 0 { '.' } else { '#' });
+        }
+        screen.push('\n');
+    }
+    screen
+}
+
+#[test]
+fn test_roms() {
+    let manifest = fs::read_to_string(test_dir("roms").join("MANIFEST")).unwrap();
+    let bless = env::var_os("BLESS").is_some();
+    let skip_missing = env::var_os("SKIP_MISSING_ROMS").is_some();
+    let mut failures = Vec::new();
+
+    for case in parse_manifest(&manifest) {
+        let rom = match fs::read(test_dir("roms").join(&case.rom)) {
+            Ok(rom) => rom,
+            Err(_) if skip_missing => {
+                println!("skipping {}: {} not found", case.name, case.rom);
+                continue;
+            }
+            Err(_) => {
+                failures.push(format!(
+                    "{}: {} isn't in tests/roms/; copy it in, or set SKIP_MISSING_ROMS=1 to skip it",
+                    case.name, case.rom
+                ));
+                continue;
+            }
+        };
+        let screen = run(&case, &rom);
+        let golden_path = test_dir("golden").join(format!("{}.txt", case.name));
+
+        if bless {
+            fs::write(&golden_path, &screen).unwrap();
+            println!("blessed {}", case.name);
+            continue;
+        }
+
+        match fs::read_to_string(&golden_path) {
+            Ok(ref golden) if *golden == screen => {}
+            Ok(golden) => failures.push(format!(
+                "{} doesn't match its golden image.\nexpected:\n{}\ngot:\n{}",
+                case.name,
+                golden,
+                screen
+            )),
+            Err(_) => failures.push(format!(
+                "{} has no golden image; run with BLESS=1 to create one.\ngot:\n{}",
+                case.name,
+                screen
+            )),
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,54 @@
+; Draws VF after 8XY4 (carry), 8XY5 (no borrow), 8XY5 (borrow), 8XYE,
+; 8XY6 and 8XY7 on the first row, which should read 110101. Below that
+; it draws the BCD digits of 137 from FX33.
+    ld va, 0
+    ld vb, 0
+    ld v0, 0xff
+    ld v1, 1
+    add v0, v1
+    ld v3, vf
+    call digit
+    ld v0, 5
+    ld v1, 3
+    sub v0, v1
+    ld v3, vf
+    call digit
+    ld v0, 3
+    ld v1, 5
+    sub v0, v1
+    ld v3, vf
+    call digit
+    ld v0, 0x81
+    shl v0
+    ld v3, vf
+    call digit
+    ld v0, 0x02
+    shr v0
+    ld v3, vf
+    call digit
+    ld v0, 3
+    ld v1, 5
+    subn v0, v1
+    ld v3, vf
+    call digit
+    ld va, 0
+    ld vb, 8
+    ld v9, 137
+    ld i, scratch
+    ld b, v9
+    ld v2, [i]
+    ld v3, v0
+    call digit
+    ld v3, v1
+    call digit
+    ld v3, v2
+    call digit
+end:
+    jp end
+digit:
+    ld f, v3
+    drw va, vb, 5
+    add va, 5
+    ret
+scratch:
+    db 0, 0, 0
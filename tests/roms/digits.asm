@@ -0,0 +1,19 @@
+; Draws the built-in hex font, 0-7 on the first row and 8-F below.
+    ld v0, 0
+    ld v1, 0
+    ld v2, 0
+loop:
+    ld f, v0
+    drw v1, v2, 5
+    add v0, 1
+    add v1, 8
+    sne v0, 8
+    jp newrow
+    se v0, 16
+    jp loop
+end:
+    jp end
+newrow:
+    ld v1, 0
+    ld v2, 8
+    jp loop
@@ -0,0 +1,15 @@
+; Waits for a key and draws it, then draws 1 if the key is still held
+; down or 0 if it has already been released.
+    ld v0, k
+    ld f, v0
+    ld v1, 8
+    ld v2, 8
+    drw v1, v2, 5
+    ld v3, 0
+    sknp v0
+    ld v3, 1
+    ld f, v3
+    ld v1, 16
+    drw v1, v2, 5
+end:
+    jp end
@@ -0,0 +1,110 @@
+// WebAssembly bindings for the emulator core, for running ROMs in a web
+// page or under node. Build them with `wasm-pack build --target web` (or
+// `--target nodejs`) from this directory.
+use wasm_bindgen::prelude::*;
+
+use chip8::machine::{MachineConfig, Quirks};
+use chip8::processor::Processor;
+use chip8::{CHIP8_WIDTH, CYCLES_PER_FRAME};
+
+/// A CHIP-8 machine. It doesn't keep time, draw or play sound: the page
+/// calls `runFrames` from `requestAnimationFrame` or a timer, draws
+/// `framebuffer()` and beeps while `soundActive` is true.
+#[wasm_bindgen]
+pub struct Chip8 {
+    processor: Processor,
+    keys: [bool; 16],
+}
+
+#[wasm_bindgen]
+impl Chip8 {
+    /// `machine` and `quirks` take the same names as the emulator's
+    /// `--machine` and `--quirks` options; leave them out for the defaults.
+    /// Throws if either is unknown.
+    #[wasm_bindgen(constructor)]
+    pub fn new(machine: Option<String>, quirks: Option<String>) -> Result<Chip8, JsError> {
+        let mut config = match machine {
+            Some(machine) => MachineConfig::from_name(&machine)
+                .ok_or_else(|| JsError::new(&format!("unknown machine: {}", machine)))?,
+            None => MachineConfig::chip8(),
+        };
+        if let Some(quirks) = quirks {
+            config.quirks =
+                Quirks::from_name(&quirks).ok_or_else(|| JsError::new(&format!("unknown quirks: {}", quirks)))?;
+        }
+        Ok(Chip8 {
+            processor: Processor::with_config(config),
+            keys: [false; 16],
+        })
+    }
+
+    /// Copies a ROM, as a `Uint8Array`, to the machine's load address.
+    pub fn load(&mut self, rom: &[u8]) {
+        self.processor.load(rom);
+    }
+
+    /// Seeds the random numbers `CXKK` produces. Without this every run
+    /// gets the same ones, so pass something like `Date.now()` for variety.
+    pub fn seed(&mut self, seed: u32) {
+        self.processor.seed_rng(seed as u64);
+    }
+
+    /// Presses or releases one of the 16 keys. Other numbers are ignored.
+    #[wasm_bindgen(js_name = setKey)]
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if let Some(held) = self.keys.get_mut(key as usize) {
+            *held = pressed;
+        }
+    }
+
+    /// Runs the given number of cycles. Returns whether the display changed.
+    pub fn step(&mut self, cycles: u32) -> bool {
+        self.processor.run(cycles, self.keys).vram_changed
+    }
+
+    /// Runs the given number of 60Hz frames. Returns whether the display
+    /// changed.
+    #[wasm_bindgen(js_name = runFrames)]
+    pub fn run_frames(&mut self, frames: u32) -> bool {
+        self.processor.run(frames.saturating_mul(CYCLES_PER_FRAME), self.keys).vram_changed
+    }
+
+    /// The display as a `Uint8Array`, `width` pixels per row and `height`
+    /// rows, one byte per pixel and 1 for lit.
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let rows = self.processor.vram();
+        let mut pixels = Vec::with_capacity(rows.len() * CHIP8_WIDTH);
+        for row in rows {
+            pixels.extend_from_slice(row);
+        }
+        pixels
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        CHIP8_WIDTH as u32
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.processor.vram().len() as u32
+    }
+
+    /// Whether the buzzer should be sounding.
+    #[wasm_bindgen(getter, js_name = soundActive)]
+    pub fn sound_active(&self) -> bool {
+        self.processor.registers().sound_timer > 0
+    }
+
+    /// The whole machine as a `Uint8Array`, to pass to `loadState` later.
+    #[wasm_bindgen(js_name = saveState)]
+    pub fn save_state(&self) -> Vec<u8> {
+        self.processor.save_state()
+    }
+
+    /// Throws if the state isn't one, or is for another machine.
+    #[wasm_bindgen(js_name = loadState)]
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), JsError> {
+        self.processor.load_state(state).map_err(|e| JsError::new(&e))
+    }
+}
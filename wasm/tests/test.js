@@ -0,0 +1,92 @@
+// Runs the bindings under node. Build them first, then run the tests:
+//
+//   wasm-pack build --target nodejs
+//   node --test tests/
+const assert = require("node:assert");
+const fs = require("node:fs");
+const path = require("node:path");
+const test = require("node:test");
+
+const { Chip8 } = require("../pkg/chip8_wasm.js");
+
+const root = path.join(__dirname, "..", "..");
+
+// Draws a random digit at a random place, over and over.
+const RANDOM_DIGITS = Uint8Array.from([
+  0xc0, 0x3f, // RND V0, 0x3F
+  0xc1, 0x1f, // RND V1, 0x1F
+  0xc2, 0x0f, // RND V2, 0x0F
+  0xf2, 0x29, // LD F, V2
+  0xd0, 0x15, // DRW V0, V1, 5
+  0x12, 0x00, // JP 0x200
+]);
+
+// The screen as the golden images in tests/golden/ have it.
+function screen(chip8) {
+  const pixels = chip8.framebuffer();
+  assert.strictEqual(pixels.length, chip8.width * chip8.height);
+  const rows = [];
+  for (let y = 0; y < chip8.height; y++) {
+    const row = pixels.subarray(y * chip8.width, (y + 1) * chip8.width);
+    rows.push(Array.from(row, (pixel) => (pixel ? "#" : ".")).join(""));
+  }
+  return rows.join("\n") + "\n";
+}
+
+test("runs a ROM and draws what the golden image shows", () => {
+  const chip8 = new Chip8();
+  chip8.load(fs.readFileSync(path.join(root, "tests", "roms", "digits.ch8")));
+  chip8.runFrames(30);
+  const golden = fs.readFileSync(path.join(root, "tests", "golden", "digits.txt"), "utf8");
+  assert.strictEqual(screen(chip8), golden);
+  chip8.free();
+});
+
+test("keys and sound", () => {
+  const chip8 = new Chip8("chip8", "vip");
+  chip8.load(Uint8Array.from([
+    0xf0, 0x0a, // LD V0, K
+    0x61, 0x40, // LD V1, 0x40
+    0xf1, 0x18, // LD ST, V1
+    0x12, 0x06, // JP 0x206
+  ]));
+  chip8.runFrames(5);
+  assert.strictEqual(chip8.soundActive, false);
+  chip8.setKey(3, true);
+  chip8.step(3);
+  chip8.setKey(3, false);
+  assert.strictEqual(chip8.soundActive, true);
+  chip8.runFrames(10);
+  assert.strictEqual(chip8.soundActive, false);
+  chip8.free();
+});
+
+test("seeds and save states", () => {
+  const run = (seed) => {
+    const chip8 = new Chip8();
+    chip8.seed(seed);
+    chip8.load(RANDOM_DIGITS);
+    chip8.runFrames(20);
+    return screen(chip8);
+  };
+  assert.strictEqual(run(1), run(1));
+  assert.notStrictEqual(run(1), run(2));
+
+  const chip8 = new Chip8("two-page");
+  assert.strictEqual(chip8.height, 64);
+  chip8.load(RANDOM_DIGITS);
+  chip8.runFrames(5);
+  const state = chip8.saveState();
+  chip8.runFrames(5);
+  const after = screen(chip8);
+  chip8.loadState(state);
+  chip8.runFrames(5);
+  assert.strictEqual(screen(chip8), after);
+  assert.throws(() => chip8.loadState(Uint8Array.from([1, 2, 3])), /not a save state/);
+  chip8.free();
+});
+
+test("unknown names throw", () => {
+  assert.throws(() => new Chip8("nes"), /unknown machine: nes/);
+  assert.throws(() => new Chip8(undefined, "weird"), /unknown quirks: weird/);
+});